@@ -0,0 +1,86 @@
+//! Static files referenced from rendered pages, such as images under `img/`
+//! or next to a post in its page bundle.
+
+use std::path::{Component, Path, PathBuf};
+
+use crate::html::unescape;
+
+/// Relative and root-relative URLs in the `src` and `href` attributes of
+/// `html`, decoded into paths. Absolute URLs and fragments are skipped.
+pub fn local_references(html: &str) -> Vec<String> {
+    let mut references = Vec::new();
+    for attribute in ["src=\"", "href=\""] {
+        let mut rest = html;
+        while let Some(start) = rest.find(attribute) {
+            rest = &rest[start + attribute.len()..];
+            let Some(end) = rest.find('"') else {
+                break;
+            };
+            let url = &rest[..end];
+            rest = &rest[end..];
+            let path = url.split(['?', '#']).next().unwrap_or_default();
+            if path.is_empty() || path.starts_with("//") || path.contains(':') {
+                continue;
+            }
+            references.push(percent_decode(&unescape(path)));
+        }
+    }
+    references
+}
+
+/// Resolves a reference found on a page in `page_dir` into a path from the
+/// site root, or `None` if it points outside the site.
+pub fn resolve(page_dir: &Path, reference: &str) -> Option<PathBuf> {
+    let (base, reference) = match reference.strip_prefix('/') {
+        Some(reference) => (Path::new(""), reference),
+        None => (page_dir, reference),
+    };
+    let mut resolved = PathBuf::new();
+    for component in base.join(reference).components() {
+        match component {
+            Component::Normal(name) => resolved.push(name),
+            Component::CurDir => {}
+            Component::ParentDir if resolved.pop() => {}
+            _ => return None,
+        }
+    }
+    Some(resolved)
+}
+
+pub fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let decoded = (bytes[i] == b'%')
+            .then(|| text.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match decoded {
+            Some(byte) => {
+                out.push(byte);
+                i += 3;
+            }
+            None => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Encodes `text` for use as a URL query value, leaving only unreserved
+/// characters as they are.
+pub fn percent_encode(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                out.push(byte as char)
+            }
+            _ => out += &format!("%{byte:02X}"),
+        }
+    }
+    out
+}
@@ -0,0 +1,324 @@
+//! Incremental builds.
+//!
+//! `.blog-cache.json` at the site root records, for every file in the output,
+//! what it was built from: a hash of its source, of the templates and of the
+//! configuration. A build only renders pages whose hashes changed and removes
+//! outputs that no source produces any more. Without a usable manifest the
+//! output directory is cleaned and everything is built from scratch.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::json;
+use crate::value::{Table, Value};
+
+pub const FILE_NAME: &str = ".blog-cache.json";
+
+/// Manifests written by other versions of the generator are not trusted,
+/// since the same inputs may now produce different pages.
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Identifies the running generator: its version, and the size and
+/// modification time of its executable so that a rebuild of the same version
+/// is not trusted either.
+fn generator() -> String {
+    let metadata = std::env::current_exe().and_then(std::fs::metadata);
+    let Ok(metadata) = metadata else {
+        return VERSION.to_string();
+    };
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(std::time::UNIX_EPOCH).ok())
+        .unwrap_or_default();
+    format!("{VERSION}+{}.{}", metadata.len(), modified.as_nanos())
+}
+
+/// 64-bit FNV-1a, which unlike the standard library's hasher is stable across
+/// Rust versions.
+pub struct Hasher(u64);
+
+impl Hasher {
+    pub fn new() -> Self {
+        Hasher(0xcbf2_9ce4_8422_2325)
+    }
+
+    /// Adds `text`, followed by a separator so that `"ab", "c"` and
+    /// `"a", "bc"` hash differently.
+    pub fn write(&mut self, text: &str) {
+        self.write_bytes(text.as_bytes());
+        self.write_bytes(&[0xff]);
+    }
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    pub fn finish(&self) -> String {
+        format!("{:016x}", self.0)
+    }
+}
+
+pub fn hash(bytes: &[u8]) -> String {
+    let mut hasher = Hasher::new();
+    hasher.write_bytes(bytes);
+    hasher.finish()
+}
+
+/// What the last build wrote, keyed by path from the output directory with
+/// `/` separators.
+#[derive(Default)]
+pub struct Manifest {
+    /// The output directory, so that building somewhere else starts over.
+    pub output: String,
+    pub outputs: BTreeMap<String, Output>,
+}
+
+/// How one output file was built. Fields that do not apply to the kind of
+/// output are left empty.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Output {
+    /// The source it came from, relative to the site root.
+    pub source: Option<String>,
+    /// Hash of the source and anything else that only this output depends
+    /// on, such as its URL.
+    pub content: Option<String>,
+    pub templates: Option<String>,
+    pub config: Option<String>,
+    /// The page's markdown rendered to HTML, so that an unchanged page does
+    /// not have to be converted again when the site-wide outputs need it.
+    pub html: Option<String>,
+    /// Site-root paths of the assets the page references.
+    pub assets: Vec<String>,
+    /// Site-root paths of the files the page's code blocks include, whose
+    /// contents are part of `content`.
+    pub includes: Vec<String>,
+    /// What the page's code blocks printed when they were run, by
+    /// [`run::key`](crate::run::key).
+    pub runs: BTreeMap<String, String>,
+}
+
+impl Manifest {
+    /// Loads the manifest at `path` if it exists, describes `output` and was
+    /// written by this version of the generator.
+    pub fn load(path: &Path, output: &str) -> Option<Manifest> {
+        let text = std::fs::read_to_string(path).ok()?;
+        let Value::Table(mut table) = json::parse(&text).ok()? else {
+            return None;
+        };
+        let string = |table: &mut Table, key: &str| match table.remove(key) {
+            Some(Value::String(s)) => Some(s),
+            _ => None,
+        };
+        if string(&mut table, "version")? != generator() || string(&mut table, "output")? != output
+        {
+            return None;
+        }
+        let Some(Value::Table(entries)) = table.remove("outputs") else {
+            return None;
+        };
+        let outputs = entries
+            .into_iter()
+            .map(|(key, entry)| {
+                let Value::Table(mut entry) = entry else {
+                    return None;
+                };
+                let strings = |entry: &mut Table, key: &str| match entry.remove(key) {
+                    Some(Value::Array(items)) => items
+                        .into_iter()
+                        .map(|item| match item {
+                            Value::String(s) => Some(s),
+                            _ => None,
+                        })
+                        .collect::<Option<_>>(),
+                    _ => Some(Vec::new()),
+                };
+                let assets = strings(&mut entry, "assets")?;
+                let includes = strings(&mut entry, "includes")?;
+                let runs = match entry.remove("runs") {
+                    Some(Value::Table(runs)) => runs
+                        .into_iter()
+                        .map(|(key, output)| match output {
+                            Value::String(output) => Some((key, output)),
+                            _ => None,
+                        })
+                        .collect::<Option<_>>()?,
+                    _ => BTreeMap::new(),
+                };
+                let output = Output {
+                    source: string(&mut entry, "source"),
+                    content: string(&mut entry, "content"),
+                    templates: string(&mut entry, "templates"),
+                    config: string(&mut entry, "config"),
+                    html: string(&mut entry, "html"),
+                    assets,
+                    includes,
+                    runs,
+                };
+                Some((key, output))
+            })
+            .collect::<Option<_>>()?;
+        Some(Manifest {
+            output: output.to_string(),
+            outputs,
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        let optional = |s: &Option<String>| s.clone().map_or(Value::Null, Value::String);
+        let strings =
+            |items: &[String]| Value::Array(items.iter().cloned().map(Value::String).collect());
+        let outputs = self
+            .outputs
+            .iter()
+            .map(|(key, output)| {
+                let mut entry = Table::from([
+                    ("source".into(), optional(&output.source)),
+                    ("content".into(), optional(&output.content)),
+                    ("templates".into(), optional(&output.templates)),
+                    ("config".into(), optional(&output.config)),
+                    ("html".into(), optional(&output.html)),
+                    ("assets".into(), strings(&output.assets)),
+                    ("includes".into(), strings(&output.includes)),
+                    (
+                        "runs".into(),
+                        Value::Table(
+                            output
+                                .runs
+                                .iter()
+                                .map(|(key, output)| (key.clone(), Value::String(output.clone())))
+                                .collect(),
+                        ),
+                    ),
+                ]);
+                entry.retain(|_, value| match value {
+                    Value::Null => false,
+                    Value::Array(items) => !items.is_empty(),
+                    Value::Table(table) => !table.is_empty(),
+                    _ => true,
+                });
+                (key.clone(), Value::Table(entry))
+            })
+            .collect();
+        let manifest = Table::from([
+            ("version".into(), Value::String(generator())),
+            ("output".into(), Value::String(self.output.clone())),
+            ("outputs".into(), Value::Table(outputs)),
+        ]);
+        std::fs::write(path, json::to_string_pretty(&Value::Table(manifest)))
+    }
+}
+
+/// The manifest key for `path`, relative to the output directory.
+pub fn key(path: &Path) -> String {
+    path.components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::run::TempDir;
+
+    #[test]
+    fn hashes() {
+        // FNV-1a test vectors.
+        assert_eq!(hash(b""), "cbf29ce484222325");
+        assert_eq!(hash(b"a"), "af63dc4c8601ec8c");
+        assert_eq!(hash(b"foobar"), "85944171f73967e8");
+
+        let hash_all = |texts: &[&str]| {
+            let mut hasher = Hasher::new();
+            for text in texts {
+                hasher.write(text);
+            }
+            hasher.finish()
+        };
+        assert_eq!(hash_all(&["ab", "c"]), hash_all(&["ab", "c"]));
+        assert_ne!(hash_all(&["ab", "c"]), hash_all(&["a", "bc"]));
+        assert_ne!(hash_all(&["abc"]), hash(b"abc"));
+        assert_eq!(hash_all(&[]), hash(b""));
+    }
+
+    #[test]
+    fn keys() {
+        assert_eq!(key(Path::new("index.html")), "index.html");
+        assert_eq!(key(&Path::new("a").join("b").join("c.html")), "a/b/c.html");
+    }
+
+    fn manifest() -> Manifest {
+        Manifest {
+            output: "/site/dist".into(),
+            outputs: BTreeMap::from([
+                (
+                    "a/post.html".into(),
+                    Output {
+                        source: Some("a/post.md".into()),
+                        content: Some("0123".into()),
+                        templates: Some("4567".into()),
+                        config: Some("89ab".into()),
+                        html: Some("<p>\"Hi\"\n</p>".into()),
+                        assets: vec!["a/cat.png".into()],
+                        includes: vec!["src/lib.rs".into(), "Cargo.toml".into()],
+                        runs: BTreeMap::from([("cdef".into(), "out\n".into())]),
+                    },
+                ),
+                ("robots.txt".into(), Output::default()),
+            ]),
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path.join(FILE_NAME);
+        manifest().save(&path).unwrap();
+        let loaded = Manifest::load(&path, "/site/dist").unwrap();
+        assert_eq!(loaded.output, "/site/dist");
+        assert_eq!(loaded.outputs, manifest().outputs);
+        // Empty fields are left out.
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert!(text.contains("\"robots.txt\": {}"), "{text}");
+
+        // A manifest for another output directory is not used.
+        assert!(Manifest::load(&path, "/elsewhere").is_none());
+        assert!(Manifest::load(&dir.path.join("missing.json"), "/site/dist").is_none());
+    }
+
+    #[test]
+    fn unusable_manifests() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path.join(FILE_NAME);
+        manifest().save(&path).unwrap();
+        let saved = std::fs::read_to_string(&path).unwrap();
+        let load = |text: &str| {
+            std::fs::write(&path, text).unwrap();
+            Manifest::load(&path, "/site/dist")
+        };
+        assert!(load(&saved).is_some());
+        let other_version = saved.replace(&generator(), "0.0.0");
+        assert_ne!(other_version, saved);
+        let corrupt = [
+            "",
+            "not json",
+            &saved[..saved.len() / 2],
+            "[]",
+            &other_version,
+            &saved
+                .replace("\"outputs\": {", "\"outputs\": [{")
+                .replace("\n  }\n}", "\n  }]\n}"),
+            &saved.replace("\"a/cat.png\"", "1"),
+            &saved.replace("\"out\\n\"", "null"),
+            &saved.replace("\"robots.txt\": {}", "\"robots.txt\": 1"),
+        ];
+        for text in corrupt {
+            assert!(load(text).is_none(), "{text}");
+        }
+    }
+}
@@ -0,0 +1,214 @@
+//! Command-line arguments.
+
+use std::path::PathBuf;
+
+use crate::config;
+
+pub const USAGE: &str = "\
+Usage: markdown_to_html [OPTIONS]
+       markdown_to_html serve [OPTIONS]
+       markdown_to_html check-snippets [OPTIONS]
+
+Commands:
+  serve               Build the site, serve it locally and rebuild and reload
+                      open pages whenever a source changes
+  check-snippets      Compile and run the Rust code blocks in every page, the
+                      way rustdoc runs doctests
+
+Options:
+  -s, --source <DIR>  Directory containing the markdown sources [default: .]
+  -o, --output <DIR>  Directory the site is written to, removed before each
+                      build if an earlier build wrote it
+                      [default: <SOURCE>/dist]
+  -c, --config <FILE> Site configuration [default: <SOURCE>/blog.toml]
+      --set <KEY=VALUE>
+                      Override a configuration key, such as
+                      `--set base_path=/`. May be repeated
+      --drafts        Include drafts and posts scheduled for later, marked
+                      with a banner
+      --run           Run the code blocks marked `run` or `output` and show
+                      what they print, recording it in `.blog-outputs.json`
+                      for builds without `--run`. Only use with sources you
+                      trust
+  -v, --verbose       Print every source that is skipped and why
+  -p, --port <PORT>   Port to serve on, with `serve` [default: 8000]
+  -h, --help          Print this help";
+
+pub const DEFAULT_PORT: u16 = 8000;
+
+pub enum Command {
+    Build,
+    Serve { port: u16 },
+    CheckSnippets,
+}
+
+pub struct Args {
+    pub command: Command,
+    pub source: PathBuf,
+    pub output: PathBuf,
+    pub config: PathBuf,
+    pub overrides: Vec<(String, String)>,
+    /// Whether drafts and scheduled posts are built, for previews.
+    pub drafts: bool,
+    /// Whether code blocks that ask to be run are, which trusts the sources
+    /// with the machine building them.
+    pub run: bool,
+    pub verbose: bool,
+}
+
+impl Args {
+    /// Parses the arguments after the program name. Prints the usage and
+    /// exits when asked for help.
+    pub fn parse(args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut args = args.peekable();
+        let command = args.next_if(|arg| arg == "serve" || arg == "check-snippets");
+        let serve = command.as_deref() == Some("serve");
+        let mut port = None;
+        let mut source = None;
+        let mut output = None;
+        let mut config = None;
+        let mut overrides = Vec::new();
+        let mut drafts = false;
+        let mut run = false;
+        let mut verbose = false;
+        while let Some(arg) = args.next() {
+            let (flag, inline) = match arg.split_once('=') {
+                Some((flag, value)) if flag.starts_with("--") => (flag, Some(value.to_string())),
+                _ => (arg.as_str(), None),
+            };
+            let mut value = || {
+                inline
+                    .clone()
+                    .or_else(|| args.next())
+                    .ok_or_else(|| format!("`{flag}` expects a value\n\n{USAGE}"))
+            };
+            match flag {
+                "-s" | "--source" => source = Some(PathBuf::from(value()?)),
+                "-o" | "--output" => output = Some(PathBuf::from(value()?)),
+                "-c" | "--config" => config = Some(PathBuf::from(value()?)),
+                "--set" => {
+                    let value = value()?;
+                    let Some((key, value)) = value.split_once('=') else {
+                        return Err(format!("`--set` expects KEY=VALUE, found `{value}`"));
+                    };
+                    overrides.push((key.trim().to_string(), value.to_string()));
+                }
+                "--drafts" => drafts = true,
+                "--run" => run = true,
+                "-v" | "--verbose" => verbose = true,
+                "-p" | "--port" if serve => {
+                    let value = value()?;
+                    let parsed = value
+                        .parse()
+                        .map_err(|_| format!("`{flag}` expects a port number, found `{value}`"))?;
+                    port = Some(parsed);
+                }
+                "-h" | "--help" => {
+                    println!("{USAGE}");
+                    std::process::exit(0);
+                }
+                _ => return Err(format!("unexpected argument `{arg}`\n\n{USAGE}")),
+            }
+        }
+        let source = source.unwrap_or_else(|| PathBuf::from("."));
+        let output = output.unwrap_or_else(|| source.join("dist"));
+        let config = config.unwrap_or_else(|| source.join(config::FILE_NAME));
+        let command = match command.as_deref() {
+            Some("serve") => Command::Serve {
+                port: port.unwrap_or(DEFAULT_PORT),
+            },
+            Some(_) => Command::CheckSnippets,
+            None => Command::Build,
+        };
+        Ok(Args {
+            command,
+            source,
+            output,
+            config,
+            overrides,
+            drafts,
+            run,
+            verbose,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, String> {
+        Args::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn defaults() {
+        let args = parse(&[]).unwrap();
+        assert!(matches!(args.command, Command::Build));
+        assert_eq!(args.source, PathBuf::from("."));
+        assert_eq!(args.output, PathBuf::from("./dist"));
+        assert_eq!(args.config, PathBuf::from("./blog.toml"));
+        assert!(args.overrides.is_empty() && !args.drafts && !args.run && !args.verbose);
+
+        let args = parse(&["-s", "site"]).unwrap();
+        assert_eq!(args.output, PathBuf::from("site/dist"));
+        assert_eq!(args.config, PathBuf::from("site/blog.toml"));
+    }
+
+    #[test]
+    fn options() {
+        let args = parse(&[
+            "serve",
+            "--source=site",
+            "-o",
+            "out",
+            "--config",
+            "other.toml",
+            "--set",
+            "feed.limit=5",
+            "--set=title=A = B",
+            "--drafts",
+            "--run",
+            "-v",
+            "-p",
+            "9000",
+        ])
+        .unwrap();
+        assert!(matches!(args.command, Command::Serve { port: 9000 }));
+        assert_eq!(args.source, PathBuf::from("site"));
+        assert_eq!(args.output, PathBuf::from("out"));
+        assert_eq!(args.config, PathBuf::from("other.toml"));
+        assert_eq!(
+            args.overrides,
+            [
+                ("feed.limit".to_string(), "5".to_string()),
+                ("title".to_string(), "A = B".to_string()),
+            ]
+        );
+        assert!(args.drafts && args.run && args.verbose);
+        assert!(matches!(
+            parse(&["serve"]).unwrap().command,
+            Command::Serve { port: DEFAULT_PORT }
+        ));
+        assert!(matches!(
+            parse(&["check-snippets"]).unwrap().command,
+            Command::CheckSnippets
+        ));
+    }
+
+    #[test]
+    fn errors() {
+        let error = |args: &[&str]| parse(args).err().unwrap();
+        assert!(error(&["--output"]).starts_with("`--output` expects a value"));
+        assert_eq!(
+            error(&["--set", "title"]),
+            "`--set` expects KEY=VALUE, found `title`"
+        );
+        assert!(error(&["--port", "80"]).starts_with("unexpected argument `--port`"));
+        assert_eq!(
+            error(&["serve", "-p", "http"]),
+            "`-p` expects a port number, found `http`"
+        );
+        assert!(error(&["build"]).starts_with("unexpected argument `build`"));
+    }
+}
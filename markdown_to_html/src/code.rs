@@ -0,0 +1,714 @@
+//! Fenced code blocks and the attributes in their info string, as in
+//! ```` ```rust title="src/main.rs" linenos hl_lines="3-5" ````.
+//!
+//! The markdown renderer keeps only the language, so blocks are read from the
+//! syntax tree instead and their HTML is replaced one by one. Raw HTML in posts
+//! is escaped, so every `<pre><code>` in the converted page is one of them.
+//!
+//! In Rust code, lines hidden the rustdoc way, with `# `, are left out of the
+//! page but kept in [`CodeBlock::code`], so snippets still compile as a whole.
+//!
+//! A block with a `file` attribute and no code of its own takes its code from
+//! that file, relative to the page or, starting with `/`, to the site root, so
+//! it stays in step with the real project. `lines="5-12"` takes part of the
+//! file, and `region="setup"` the lines between `ANCHOR: setup` and
+//! `ANCHOR_END: setup` comments. Anchor lines themselves are never shown.
+//!
+//! TOML, YAML and JSON blocks must parse, since readers copy them, unless
+//! they are marked `partial`.
+//!
+//! A language such as `diff-rust` marks a diff: lines starting with `+` were
+//! added and lines starting with `-` removed, and the rest is highlighted as
+//! the language after `diff-`.
+
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use markdown::mdast::{self, Node};
+
+use crate::assets::percent_encode;
+use crate::html::escape;
+use crate::{highlight, json, snippets, toml, yaml};
+
+pub struct CodeBlock {
+    /// The language from the info string, such as `rust` or `diff-rust`.
+    pub language: Option<String>,
+    /// Rustdoc-style attributes, written after the language with commas as
+    /// in `rust,no_run` or as attributes without a value.
+    pub flags: Vec<String>,
+    /// Caption naming the file the code comes from, which defaults to the
+    /// `file` attribute.
+    pub title: Option<String>,
+    /// Path of the file the code is or comes from, as written.
+    pub file: Option<String>,
+    /// The part of `file` to include.
+    pub selection: Option<Selection>,
+    /// The file the code was read from, if it was included.
+    pub included: Option<PathBuf>,
+    /// What the code printed when it was run during the build.
+    pub output: Option<String>,
+    pub line_numbers: bool,
+    /// 1-based numbers of the lines to emphasize.
+    pub highlighted_lines: BTreeSet<usize>,
+    pub code: String,
+    /// 1-based line of the opening fence in the markdown.
+    pub line: usize,
+}
+
+pub enum Selection {
+    /// The lines between `ANCHOR: name` and `ANCHOR_END: name`.
+    Region(String),
+    /// A 1-based, inclusive range of lines, which may run to the end of the
+    /// file.
+    Lines(usize, Option<usize>),
+}
+
+/// A code block that could not be rendered, with the 1-based line of its
+/// opening fence.
+#[derive(Debug)]
+pub struct Error {
+    pub line: usize,
+    pub message: String,
+}
+
+/// Where included files are looked for: `dir` is the directory of the page and
+/// `root` the site root.
+pub struct Includes<'a> {
+    pub root: &'a Path,
+    pub dir: &'a Path,
+}
+
+/// Replaces the code blocks in `html` with their rendering by
+/// [`CodeBlock::to_html`]. `blocks` are those of the markdown `html` was
+/// converted from, as read by [`blocks`].
+pub fn render(html: &str, blocks: &[CodeBlock], playground: Option<&str>) -> Result<String, Error> {
+    const END: &str = "</code></pre>";
+    let mut out = String::with_capacity(html.len());
+    let mut rest = html;
+    for block in blocks {
+        let Some(start) = rest.find("<pre><code") else {
+            break;
+        };
+        let Some(end) = rest[start..].find(END) else {
+            break;
+        };
+        out.push_str(&rest[..start]);
+        out.push_str(&block.to_html(playground)?);
+        rest = &rest[start + end + END.len()..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// The fenced and indented code blocks in `markdown`, in order, with included
+/// files read.
+pub fn blocks(markdown: &str, includes: &Includes) -> Result<Vec<CodeBlock>, Error> {
+    let tree =
+        markdown::to_mdast(markdown, &markdown::ParseOptions::gfm()).map_err(|message| Error {
+            line: 1,
+            message: message.to_string(),
+        })?;
+    let mut blocks = Vec::new();
+    collect(&tree, &mut blocks)?;
+    for block in &mut blocks {
+        block.include(includes)?;
+        block.validate()?;
+    }
+    Ok(blocks)
+}
+
+fn collect(node: &Node, blocks: &mut Vec<CodeBlock>) -> Result<(), Error> {
+    if let Node::Code(code) = node {
+        blocks.push(CodeBlock::new(code)?);
+    }
+    for child in node.children().into_iter().flatten() {
+        collect(child, blocks)?;
+    }
+    Ok(())
+}
+
+impl CodeBlock {
+    fn new(code: &mdast::Code) -> Result<Self, Error> {
+        let line = code
+            .position
+            .as_ref()
+            .map_or(1, |position| position.start.line);
+        let error = |message: String| Error { line, message };
+        let mut flags = code.lang.iter().flat_map(|lang| lang.split(','));
+        let language = flags
+            .next()
+            .filter(|language| !language.is_empty())
+            .map(str::to_string);
+        let mut block = CodeBlock {
+            language,
+            flags: flags
+                .map(str::trim)
+                .filter(|flag| !flag.is_empty())
+                .map(str::to_string)
+                .collect(),
+            title: None,
+            file: None,
+            selection: None,
+            included: None,
+            output: None,
+            line_numbers: false,
+            highlighted_lines: BTreeSet::new(),
+            code: code.value.clone(),
+            line,
+        };
+        let attributes = attributes(code.meta.as_deref().unwrap_or_default()).map_err(error)?;
+        let mut region = None;
+        let mut lines = None;
+        for (name, value) in attributes {
+            match (name.as_str(), value) {
+                ("title", Some(value)) => block.title = Some(value),
+                ("file", Some(value)) => block.file = Some(value),
+                ("region", Some(value)) => region = Some(value),
+                ("lines", Some(value)) => lines = Some(value),
+                ("linenos", None) => block.line_numbers = true,
+                ("hl_lines", Some(value)) => {
+                    block.highlighted_lines = line_set(&value).map_err(error)?;
+                }
+                ("title" | "hl_lines" | "file" | "region" | "lines", None) => {
+                    return Err(error(format!(
+                        "`{name}` needs a value, as in `{name}=\"...\"`"
+                    )));
+                }
+                ("linenos", Some(_)) => {
+                    return Err(error("`linenos` does not take a value".to_string()));
+                }
+                (_, None) => block.flags.push(name),
+                // Attributes meant for other tools are left alone.
+                _ => {}
+            }
+        }
+        if region.is_some() || lines.is_some() {
+            if block.file.is_none() || !block.code.is_empty() {
+                return Err(error(
+                    "`region` and `lines` only apply to a block that includes a `file`".to_string(),
+                ));
+            }
+            if region.is_some() && lines.is_some() {
+                return Err(error(
+                    "a block can include a `region` or `lines`, not both".to_string(),
+                ));
+            }
+        }
+        block.selection = match (region, lines) {
+            (Some(region), _) => Some(Selection::Region(region)),
+            (None, Some(lines)) => Some(line_range(&lines).map_err(error)?),
+            (None, None) => None,
+        };
+        if block.title.is_none() {
+            block.title = block.file.clone();
+        }
+        Ok(block)
+    }
+
+    /// Reads the code of a block with a `file` and no code of its own.
+    fn include(&mut self, includes: &Includes) -> Result<(), Error> {
+        let Some(file) = &self.file else {
+            return Ok(());
+        };
+        if !self.code.is_empty() {
+            return Ok(());
+        }
+        let error = |message: String| Error {
+            line: self.line,
+            message,
+        };
+        let path = match file.strip_prefix('/') {
+            Some(file) => includes.root.join(file),
+            None => includes.dir.join(file),
+        };
+        let text = std::fs::read_to_string(&path)
+            .map_err(|e| error(format!("cannot include `{file}`: {e}")))?;
+        let lines: Vec<&str> = text.lines().collect();
+        let selected = match &self.selection {
+            None => &lines[..],
+            Some(Selection::Region(name)) => {
+                let start = lines
+                    .iter()
+                    .position(|line| anchor(line) == Some(("ANCHOR", name.as_str())))
+                    .ok_or_else(|| error(format!("`{file}` has no `ANCHOR: {name}`")))?;
+                let len = lines[start + 1..]
+                    .iter()
+                    .position(|line| anchor(line) == Some(("ANCHOR_END", name.as_str())))
+                    .ok_or_else(|| error(format!("`{file}` has no `ANCHOR_END: {name}`")))?;
+                &lines[start + 1..start + 1 + len]
+            }
+            Some(Selection::Lines(first, last)) => {
+                let (first, last) = (*first, last.unwrap_or(lines.len()));
+                if first > last || last > lines.len() {
+                    return Err(error(format!(
+                        "`lines` asks for lines {first} to {last}, but `{file}` has {} line{}",
+                        lines.len(),
+                        if lines.len() == 1 { "" } else { "s" }
+                    )));
+                }
+                &lines[first - 1..last]
+            }
+        };
+        let selected: Vec<&str> = selected
+            .iter()
+            .copied()
+            .filter(|line| anchor(line).is_none())
+            .collect();
+        // Code taken from inside a function reads better without the
+        // function's indentation.
+        let indent = selected
+            .iter()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| line.len() - line.trim_start().len())
+            .min()
+            .unwrap_or(0);
+        self.code = selected
+            .iter()
+            .map(|line| line.get(indent..).unwrap_or_default())
+            .collect::<Vec<_>>()
+            .join("\n");
+        self.included = Some(path);
+        Ok(())
+    }
+
+    /// Parses TOML, YAML and JSON code, so that snippets readers copy are
+    /// known to be valid. Blocks marked `partial` are left alone.
+    fn validate(&self) -> Result<(), Error> {
+        if self.flags.iter().any(|flag| flag == "partial") {
+            return Ok(());
+        }
+        let result = match self.language.as_deref() {
+            Some("toml") => toml::parse(&self.code).map(drop),
+            Some("yaml" | "yml") => yaml::parse(&self.code).map(drop),
+            Some("json") => json::parse(&self.code).map(drop),
+            _ => return Ok(()),
+        };
+        let Err(e) = result else {
+            return Ok(());
+        };
+        let language = self.language.as_deref().unwrap_or_default().to_uppercase();
+        let hint = "mark the block `partial` if it is not meant to be complete";
+        Err(match &self.file {
+            Some(file) if self.included.is_some() => Error {
+                line: self.line,
+                message: format!(
+                    "invalid {language} in `{file}` at line {}: {}; {hint}",
+                    e.line, e.message
+                ),
+            },
+            // The code starts on the line after the opening fence.
+            _ => Error {
+                line: self.line + e.line,
+                message: format!("invalid {language}: {}; {hint}", e.message),
+            },
+        })
+    }
+
+    /// The edition picked with an attribute such as `edition2018`.
+    pub fn edition(&self) -> Option<&str> {
+        self.flags
+            .iter()
+            .find_map(|flag| flag.strip_prefix("edition"))
+    }
+
+    /// A link that opens the block on the Rust Playground, as rustdoc's "Run"
+    /// button does, for Rust code that is meant to compile.
+    fn playground_url(&self, default_edition: &str) -> Option<String> {
+        let runnable = self.language.as_deref() == Some("rust")
+            && !self
+                .flags
+                .iter()
+                .any(|flag| flag == "ignore" || flag == "compile_fail");
+        runnable.then(|| {
+            format!(
+                "https://play.rust-lang.org/?version=stable&mode=debug&edition={}&code={}",
+                self.edition().unwrap_or(default_edition),
+                percent_encode(&snippets::program(&self.code))
+            )
+        })
+    }
+
+    /// The block as a `<pre>`, in a `<figure>` with the title as caption if it
+    /// has one, and followed by its output if it was run. With `playground` set
+    /// to the default edition, Rust blocks get a link to run them on the Rust
+    /// Playground.
+    pub fn to_html(&self, playground: Option<&str>) -> Result<String, Error> {
+        let (diff, language) = match self.language.as_deref() {
+            Some("diff") => (true, None),
+            Some(language) => match language.strip_prefix("diff-") {
+                Some(language) => (true, Some(language)),
+                None => (false, Some(language)),
+            },
+            None => (false, None),
+        };
+        // Diff markers are taken off so the rest reads as the language, and
+        // hidden lines are left out, though `self.code` keeps them.
+        let mut markers = Vec::new();
+        let mut shown = Vec::new();
+        for line in self.code.split('\n') {
+            let (marker, line) = match (diff, line.chars().next()) {
+                (true, Some(marker @ ('+' | '-' | ' '))) => (Some(marker), &line[1..]),
+                (true, _) => (Some(' '), line),
+                (false, _) => (None, line),
+            };
+            let line = match language {
+                Some("rust") => match rust_line(line) {
+                    (_, true) => continue,
+                    (line, false) => line,
+                },
+                _ => Cow::Borrowed(line),
+            };
+            markers.extend(marker);
+            shown.push(line);
+        }
+        let code = shown.join("\n");
+        let highlighted = language
+            .and_then(|language| highlight::highlight(language, &code))
+            .unwrap_or_else(|| escape(&code));
+        let lines: Vec<&str> = match code.is_empty() {
+            true => Vec::new(),
+            false => highlighted.split('\n').collect(),
+        };
+        if let Some(&last) = self.highlighted_lines.last() {
+            if last > lines.len() {
+                return Err(Error {
+                    line: self.line,
+                    message: format!(
+                        "`hl_lines` includes line {last}, but the block has {} line{}",
+                        lines.len(),
+                        if lines.len() == 1 { "" } else { "s" }
+                    ),
+                });
+            }
+        }
+
+        let run = playground.and_then(|edition| self.playground_url(edition));
+        let mut out = String::new();
+        if self.title.is_some() || run.is_some() {
+            out += "<figure class=\"code-block\">\n";
+        }
+        if let Some(title) = &self.title {
+            out += &format!("<figcaption>{}</figcaption>\n", escape(title));
+        }
+        match &self.language {
+            Some(language) => {
+                out += &format!("<pre><code class=\"language-{}\">", escape(language))
+            }
+            None => out += "<pre><code>",
+        }
+        let per_line = self.line_numbers || diff || !self.highlighted_lines.is_empty();
+        let width = lines.len().to_string().len();
+        for (i, line) in lines.iter().enumerate() {
+            let number = i + 1;
+            if !per_line {
+                out += line;
+                out.push('\n');
+                continue;
+            }
+            let mut classes = vec!["line"];
+            if self.highlighted_lines.contains(&number) {
+                classes.push("highlighted");
+            }
+            match markers.get(i) {
+                Some('+') => classes.push("added"),
+                Some('-') => classes.push("removed"),
+                _ => {}
+            }
+            out += &format!("<span class=\"{}\">", classes.join(" "));
+            if self.line_numbers {
+                out += &format!("<span class=\"line-number\">{number:>width$}</span>");
+            }
+            if let Some(marker) = markers.get(i) {
+                out += &format!("<span class=\"diff-marker\">{marker}</span>");
+            }
+            out += line;
+            out += "</span>\n";
+        }
+        out += "</code></pre>";
+        if let Some(url) = &run {
+            out += &format!(
+                "\n<a class=\"playground\" href=\"{}\" target=\"_blank\" rel=\"noopener\">Run</a>",
+                escape(url)
+            );
+        }
+        if self.title.is_some() || run.is_some() {
+            out += "\n</figure>";
+        }
+        if let Some(output) = &self.output {
+            out += &format!(
+                "\n<pre class=\"output\"><code>{}</code></pre>",
+                escape(output)
+            );
+        }
+        Ok(out)
+    }
+}
+
+/// Rustdoc's hidden lines in Rust code: a line starting with `# `, or just
+/// `#`, is compiled but not shown, and one starting with `##` is shown with a
+/// single `#`. Returns the line as compiled and whether it is hidden.
+pub fn rust_line(line: &str) -> (Cow<'_, str>, bool) {
+    let trimmed = line.trim_start();
+    if trimmed == "#" {
+        return (Cow::Borrowed(""), true);
+    }
+    if let Some(rest) = trimmed.strip_prefix("# ") {
+        return (Cow::Borrowed(rest), true);
+    }
+    if trimmed.starts_with("##") {
+        let indent = &line[..line.len() - trimmed.len()];
+        return (Cow::Owned(format!("{indent}{}", &trimmed[1..])), false);
+    }
+    (Cow::Borrowed(line), false)
+}
+
+/// The marker and name of an `ANCHOR: name` or `ANCHOR_END: name` line, in
+/// whatever comment syntax the file uses.
+fn anchor(line: &str) -> Option<(&'static str, &str)> {
+    ["ANCHOR_END", "ANCHOR"].into_iter().find_map(|marker| {
+        let (before, after) = line.split_once(&format!("{marker}:"))?;
+        let is_comment = before
+            .trim()
+            .chars()
+            .all(|c| !c.is_alphanumeric() && !c.is_whitespace());
+        let name = after.split_whitespace().next()?;
+        is_comment.then_some((marker, name))
+    })
+}
+
+/// Splits the attributes after the language in an info string into names and
+/// values, which may be quoted: `title="src/main.rs" linenos`.
+fn attributes(meta: &str) -> Result<Vec<(String, Option<String>)>, String> {
+    let mut attributes = Vec::new();
+    let mut rest = meta.trim_start();
+    while !rest.is_empty() {
+        let name_end = rest
+            .find(|c: char| c.is_whitespace() || c == '=')
+            .unwrap_or(rest.len());
+        let name = &rest[..name_end];
+        if name.is_empty() {
+            return Err(format!("expected an attribute name, found `{rest}`"));
+        }
+        rest = &rest[name_end..];
+        let value = match rest.strip_prefix('=') {
+            Some(after) => match after.chars().next() {
+                Some(quote @ ('"' | '\'')) => {
+                    let value = &after[1..];
+                    let end = value.find(quote).ok_or_else(|| {
+                        format!("the value of `{name}` is missing its closing {quote}")
+                    })?;
+                    rest = &value[end + 1..];
+                    Some(value[..end].to_string())
+                }
+                _ => {
+                    let end = after.find(char::is_whitespace).unwrap_or(after.len());
+                    rest = &after[end..];
+                    Some(after[..end].to_string())
+                }
+            },
+            None => None,
+        };
+        attributes.push((name.to_string(), value));
+        rest = rest.trim_start();
+    }
+    Ok(attributes)
+}
+
+/// Parses the range of lines to include from a file, such as `5-12`, `5-` or
+/// `7`.
+fn line_range(text: &str) -> Result<Selection, String> {
+    let (first, last) = text.split_once('-').unwrap_or((text, text));
+    let first = first
+        .trim()
+        .parse::<usize>()
+        .ok()
+        .filter(|&first| first >= 1);
+    let last = match last.trim() {
+        "" => Some(None),
+        last => last.parse::<usize>().ok().map(Some),
+    };
+    match (first, last) {
+        (Some(first), Some(last)) if last.is_none_or(|last| first <= last) => {
+            Ok(Selection::Lines(first, last))
+        }
+        _ => Err(format!(
+            "`lines` expects a line number or a range such as `5-12` or `5-`, found `{text}`"
+        )),
+    }
+}
+
+/// Parses a list of 1-based line numbers and ranges such as `1, 3-5`.
+fn line_set(text: &str) -> Result<BTreeSet<usize>, String> {
+    let mut lines = BTreeSet::new();
+    for part in text.split([',', ' ']).filter(|part| !part.is_empty()) {
+        let (first, last) = part.split_once('-').unwrap_or((part, part));
+        let range = first
+            .trim()
+            .parse::<usize>()
+            .ok()
+            .zip(last.trim().parse::<usize>().ok())
+            .filter(|&(first, last)| first >= 1 && first <= last);
+        let Some((first, last)) = range else {
+            return Err(format!(
+                "`hl_lines` expects line numbers and ranges such as `1, 3-5`, found `{part}`"
+            ));
+        };
+        lines.extend(first..=last);
+    }
+    Ok(lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn validate(markdown: &str) -> Result<(), Error> {
+        let includes = Includes {
+            root: Path::new("."),
+            dir: Path::new("."),
+        };
+        blocks(markdown, &includes).map(drop)
+    }
+
+    #[test]
+    fn valid_blocks_pass() {
+        for markdown in [
+            "```yaml\n---\nname: site\n...\n```\n",
+            "```yml\nbase: &base\n  x: 1\ncopy: *base\nversion: !!str 1.0\n```\n",
+            "```toml\na = 0\nb = 1_000\nc = 1.5e3\nd = 0x1F\n```\n",
+            "```json\n{\"a\": [1, 2.5, null]}\n```\n",
+            "```text\nnot = = checked\n```\n",
+        ] {
+            assert!(validate(markdown).is_ok(), "{markdown}");
+        }
+    }
+
+    #[test]
+    fn invalid_blocks_fail() {
+        for (markdown, message) in [
+            ("```toml\na = 01\n```\n", "invalid TOML: invalid value `01`"),
+            (
+                "```toml\na = 1__0\n```\n",
+                "invalid TOML: invalid value `1__0`",
+            ),
+            ("```toml\na = 1.\n```\n", "invalid TOML: invalid value `1.`"),
+            ("```yaml\na: *x\n```\n", "invalid YAML: unknown alias `*x`"),
+            ("```json\n{\"a\": }\n```\n", "invalid JSON:"),
+        ] {
+            let e = validate(markdown).unwrap_err();
+            assert_eq!(e.line, 2);
+            assert!(e.message.starts_with(message), "{}", e.message);
+        }
+    }
+
+    #[test]
+    fn attributes_and_values() {
+        let attribute =
+            |name: &str, value: Option<&str>| (name.to_string(), value.map(str::to_string));
+        assert_eq!(
+            attributes(r#"title="src/main.rs"  linenos hl_lines='1, 3-5' lines=2-4 no_run"#),
+            Ok(vec![
+                attribute("title", Some("src/main.rs")),
+                attribute("linenos", None),
+                attribute("hl_lines", Some("1, 3-5")),
+                attribute("lines", Some("2-4")),
+                attribute("no_run", None),
+            ])
+        );
+        assert_eq!(attributes("  "), Ok(Vec::new()));
+        assert_eq!(
+            attributes("empty=\"\""),
+            Ok(vec![attribute("empty", Some(""))])
+        );
+        assert_eq!(
+            attributes("title=\"open"),
+            Err("the value of `title` is missing its closing \"".to_string())
+        );
+        assert_eq!(
+            attributes("=value"),
+            Err("expected an attribute name, found `=value`".to_string())
+        );
+    }
+
+    #[test]
+    fn highlighted_lines() {
+        assert_eq!(line_set("1, 3-5 8"), Ok(BTreeSet::from([1, 3, 4, 5, 8])));
+        assert_eq!(line_set("2,2,1-2"), Ok(BTreeSet::from([1, 2])));
+        assert_eq!(line_set(""), Ok(BTreeSet::new()));
+        for text in ["0", "5-3", "a", "1-", "-2"] {
+            let message = line_set(text).unwrap_err();
+            assert!(message.ends_with(&format!("found `{text}`")), "{message}");
+        }
+    }
+
+    #[test]
+    fn included_line_ranges() {
+        let lines = |text| match line_range(text) {
+            Ok(Selection::Lines(first, last)) => Ok((first, last)),
+            Ok(Selection::Region(_)) => panic!("`{text}` is not a region"),
+            Err(e) => Err(e),
+        };
+        assert_eq!(lines("7"), Ok((7, Some(7))));
+        assert_eq!(lines("5-12"), Ok((5, Some(12))));
+        assert_eq!(lines(" 5 - 12 "), Ok((5, Some(12))));
+        assert_eq!(lines("5-"), Ok((5, None)));
+        for text in ["0", "0-3", "12-5", "-5", "a-b", ""] {
+            assert_eq!(
+                lines(text),
+                Err(format!(
+                    "`lines` expects a line number or a range such as `5-12` or `5-`, found `{text}`"
+                ))
+            );
+        }
+    }
+
+    #[test]
+    fn hidden_rust_lines() {
+        let line = |text| {
+            let (line, hidden) = rust_line(text);
+            (line.into_owned(), hidden)
+        };
+        assert_eq!(line("# use std::fs;"), ("use std::fs;".to_string(), true));
+        assert_eq!(line("    # let x = 1;"), ("let x = 1;".to_string(), true));
+        assert_eq!(line("#"), (String::new(), true));
+        assert!(line("  #  ").1);
+        assert_eq!(
+            line("    ## not hidden"),
+            ("    # not hidden".to_string(), false)
+        );
+        assert_eq!(
+            line("#[derive(Debug)]"),
+            ("#[derive(Debug)]".to_string(), false)
+        );
+        assert_eq!(
+            line("#![allow(unused)]"),
+            ("#![allow(unused)]".to_string(), false)
+        );
+        assert_eq!(
+            line("let s = \"# x\";"),
+            ("let s = \"# x\";".to_string(), false)
+        );
+    }
+
+    #[test]
+    fn anchors() {
+        assert_eq!(anchor("// ANCHOR: setup"), Some(("ANCHOR", "setup")));
+        assert_eq!(
+            anchor("    # ANCHOR_END: setup"),
+            Some(("ANCHOR_END", "setup"))
+        );
+        assert_eq!(anchor("<!-- ANCHOR: page -->"), Some(("ANCHOR", "page")));
+        assert_eq!(anchor("ANCHOR: bare"), Some(("ANCHOR", "bare")));
+        assert_eq!(anchor("let x = 1; // ANCHOR: inline"), None);
+        assert_eq!(anchor("// ANCHOR:"), None);
+        assert_eq!(anchor("// an ANCHOR: in prose"), None);
+        assert_eq!(anchor("fn main() {}"), None);
+    }
+
+    #[test]
+    fn partial_blocks_are_not_validated() {
+        assert!(validate("```toml,partial\na = 01\n```\n").is_ok());
+        assert!(validate("```yaml partial\n  - [\n```\n").is_ok());
+    }
+}
@@ -0,0 +1,703 @@
+//! Site configuration, read from `blog.toml` at the site root.
+//!
+//! Every key can be overridden with a `BLOG_<KEY>` environment variable or a
+//! `--set <KEY>=<VALUE>` flag, in that order of precedence. Nested keys are
+//! written `feed.limit` on the command line and `BLOG_FEED__LIMIT` in the
+//! environment. `BLOG_` variables that name no key are ignored, since other
+//! tools may use them.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::value::{Table, Value};
+use crate::{date, toml};
+
+pub const FILE_NAME: &str = "blog.toml";
+
+const ENV_PREFIX: &str = "BLOG_";
+
+/// Rust editions code blocks can be compiled with.
+pub const EDITIONS: [&str; 4] = ["2015", "2018", "2021", "2024"];
+
+#[derive(Debug)]
+pub struct Config {
+    /// Name of the site, used as the title of the index page.
+    pub title: String,
+    /// Absolute URL the site is served from, such as
+    /// `https://example.github.io/blog`. Needed for anything that has to link
+    /// back to the site from elsewhere.
+    pub base_url: Option<String>,
+    /// Path the site is served under, without a trailing slash. Root-relative
+    /// links in posts are resolved against it.
+    pub base_path: String,
+    /// Whether pages are written as `foo/index.html` and linked as `foo/`
+    /// rather than as `foo.html`.
+    pub pretty_urls: bool,
+    /// Extra `.gitignore`-style patterns for sources to leave out, applied
+    /// before those in `.blogignore`.
+    pub ignore: Vec<String>,
+    /// Default author for pages that do not name one.
+    pub author: Option<String>,
+    /// Value of the `lang` attribute on every page.
+    pub language: String,
+    pub theme: Theme,
+    pub feed: Feed,
+    pub robots: Robots,
+    pub snippets: Snippets,
+    pub docs: Docs,
+    pub dates: Dates,
+}
+
+#[derive(Debug)]
+pub struct Theme {
+    pub text: Option<String>,
+    pub background: Option<String>,
+    pub link: Option<String>,
+    pub dark_text: String,
+    pub dark_background: String,
+    pub dark_link: String,
+}
+
+#[derive(Debug)]
+pub struct Feed {
+    /// Maximum number of posts in each feed.
+    pub limit: usize,
+    /// Whether entries carry the whole post, or only its description when it
+    /// has one.
+    pub full_content: bool,
+}
+
+#[derive(Debug)]
+pub struct Snippets {
+    /// Directory made by `cargo vendor` that the dependencies of code blocks
+    /// are taken from, relative to the site root.
+    pub vendor: Option<String>,
+    /// Rust edition code blocks are compiled with, unless they name another.
+    pub edition: String,
+    /// Whether Rust code blocks get a link that runs them on the Rust
+    /// Playground.
+    pub playground: bool,
+}
+
+#[derive(Debug)]
+pub struct Docs {
+    /// Versions of the crates that paths in inline code link to on docs.rs,
+    /// for pages that don't list them in their `dependencies`.
+    pub crates: BTreeMap<String, String>,
+}
+
+#[derive(Debug)]
+pub struct Dates {
+    /// Format of dates other than ISO-8601 in front matter and on the date
+    /// line of a post, such as `%d-%m-%Y`. See [`date::check_format`].
+    pub input: Option<String>,
+    /// Format dates are shown to readers in.
+    pub format: String,
+    /// Month names, from January, in the site's language.
+    pub months: Vec<String>,
+    /// Weekday names, from Monday, in the site's language.
+    pub weekdays: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct Robots {
+    /// Paths crawlers are asked to stay out of, written to `robots.txt` as
+    /// given.
+    pub disallow: Vec<String>,
+}
+
+impl Config {
+    /// Loads `path` if it exists, then applies environment and command-line
+    /// overrides and validates the result.
+    pub fn load(path: &Path, overrides: &[(String, String)]) -> Result<Self, String> {
+        Config::load_with(path, std::env::vars(), overrides)
+    }
+
+    /// [`Config::load`] with the environment variables `env`.
+    fn load_with(
+        path: &Path,
+        env: impl IntoIterator<Item = (String, String)>,
+        overrides: &[(String, String)],
+    ) -> Result<Self, String> {
+        let mut table = if path.exists() {
+            let source =
+                std::fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+            toml::parse(&source)
+                .map_err(|e| format!("{}:{}: {}", path.display(), e.line, e.message))?
+        } else {
+            Table::new()
+        };
+        // Other tools use `BLOG_` variables too, so only those naming a key
+        // the configuration reads are taken.
+        let Value::Table(defaults) = Config::from_table(Table::new())?.to_value() else {
+            unreachable!("the configuration is a table");
+        };
+        let mut env: Vec<_> = env
+            .into_iter()
+            .filter_map(|(name, value)| {
+                let key = name
+                    .strip_prefix(ENV_PREFIX)?
+                    .to_lowercase()
+                    .replace("__", ".");
+                is_known(&defaults, &key).then_some((key, value))
+            })
+            .collect();
+        env.sort();
+        for (key, value) in env.iter().chain(overrides) {
+            set(&mut table, key, value)?;
+        }
+        Config::from_table(table).map_err(|e| format!("{}: {e}", path.display()))
+    }
+
+    fn from_table(table: Table) -> Result<Self, String> {
+        let mut root = Section::new("", table);
+        let title = root.string("title")?.unwrap_or_else(|| "Blog".to_string());
+        let base_url = root.string("base_url")?;
+        if let Some(url) = &base_url {
+            if !(url.starts_with("http://") || url.starts_with("https://")) {
+                return Err(format!("`base_url` must be an http(s) URL, found `{url}`"));
+            }
+        }
+        let base_path = root.string("base_path")?.unwrap_or_default();
+        if !base_path.is_empty() && !base_path.starts_with('/') {
+            return Err(format!(
+                "`base_path` must start with `/`, found `{base_path}`"
+            ));
+        }
+        let pretty_urls = root.boolean("pretty_urls")?.unwrap_or(false);
+        let ignore = root.strings("ignore")?.unwrap_or_default();
+        let author = root.string("author")?;
+        let language = root.string("language")?.unwrap_or_else(|| "en".to_string());
+        if language.is_empty()
+            || !language
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-')
+        {
+            return Err(format!(
+                "`language` must be a language tag such as `en`, found `{language}`"
+            ));
+        }
+
+        let mut section = root.section("theme")?;
+        let theme = Theme {
+            text: section.color("text")?,
+            background: section.color("background")?,
+            link: section.color("link")?,
+            dark_text: section
+                .color("dark_text")?
+                .unwrap_or_else(|| "#fafafa".to_string()),
+            dark_background: section
+                .color("dark_background")?
+                .unwrap_or_else(|| "#000".to_string()),
+            dark_link: section
+                .color("dark_link")?
+                .unwrap_or_else(|| "#2f81f7".to_string()),
+        };
+        section.finish()?;
+
+        let mut section = root.section("feed")?;
+        let limit = section.integer("limit")?.unwrap_or(20);
+        if limit < 1 {
+            return Err(format!("`feed.limit` must be at least 1, found {limit}"));
+        }
+        let feed = Feed {
+            limit: limit as usize,
+            full_content: section.boolean("full_content")?.unwrap_or(true),
+        };
+        section.finish()?;
+
+        let mut section = root.section("robots")?;
+        let robots = Robots {
+            disallow: section.strings("disallow")?.unwrap_or_default(),
+        };
+        section.finish()?;
+
+        let mut section = root.section("snippets")?;
+        let edition = section
+            .string("edition")?
+            .unwrap_or_else(|| "2021".to_string());
+        if !EDITIONS.contains(&edition.as_str()) {
+            return Err(format!(
+                "`snippets.edition` must be one of {}, found `{edition}`",
+                EDITIONS.join(", ")
+            ));
+        }
+        let snippets = Snippets {
+            vendor: section.string("vendor")?,
+            edition,
+            playground: section.boolean("playground")?.unwrap_or(false),
+        };
+        section.finish()?;
+
+        let mut section = root.section("docs")?;
+        let mut crates = section.section("crates")?;
+        let names: Vec<String> = crates.table.keys().cloned().collect();
+        let mut versions = BTreeMap::new();
+        for name in names {
+            versions.insert(name.clone(), crates.string(&name)?.unwrap_or_default());
+        }
+        crates.finish()?;
+        let docs = Docs { crates: versions };
+        section.finish()?;
+
+        let mut section = root.section("dates")?;
+        let names = date::names(&language);
+        let mut names_for =
+            |key: &str, count: usize, default: Option<&[&str]>| match section.strings(key)? {
+                Some(names) if names.len() != count => Err(format!(
+                    "`dates.{key}` must list {count} names, found {}",
+                    names.len()
+                )),
+                Some(names) => Ok(names),
+                None => Ok(default
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|name| name.to_string())
+                    .collect()),
+            };
+        let months = names_for("months", 12, names.as_ref().map(|names| &names.0[..]))?;
+        let weekdays = names_for("weekdays", 7, names.as_ref().map(|names| &names.1[..]))?;
+        let input = section.string("input")?;
+        let format = section
+            .string("format")?
+            .unwrap_or_else(|| "%Y-%m-%d".to_string());
+        for (key, format) in [("input", input.as_deref()), ("format", Some(&format))] {
+            let Some(format) = format else {
+                continue;
+            };
+            let used = date::check_format(format)
+                .map_err(|e| format!("`dates.{key}` is not a valid format: {e}"))?;
+            let uses = |directives: &str| used.iter().any(|c| directives.contains(*c));
+            for (directives, names, key_names) in
+                [("Bb", &months, "months"), ("Aa", &weekdays, "weekdays")]
+            {
+                if uses(directives) && names.is_empty() {
+                    return Err(format!(
+                        "`dates.{key}` uses the names of {key_names}, which are not built in \
+                         for `language` `{language}`; list them in `dates.{key_names}`"
+                    ));
+                }
+            }
+            if key == "input" && !(uses("Y") && uses("mBb") && uses("de")) {
+                return Err(format!(
+                    "`dates.input` must have a year, a month and a day, found `{format}`"
+                ));
+            }
+        }
+        let dates = Dates {
+            input,
+            format,
+            months,
+            weekdays,
+        };
+        section.finish()?;
+        root.finish()?;
+
+        Ok(Config {
+            title,
+            base_url: base_url.map(|url| url.trim_end_matches('/').to_string()),
+            base_path: base_path.trim_end_matches('/').to_string(),
+            pretty_urls,
+            ignore,
+            author,
+            language,
+            theme,
+            feed,
+            robots,
+            snippets,
+            docs,
+            dates,
+        })
+    }
+}
+
+impl Config {
+    /// The configuration as seen by templates, as `config`.
+    pub fn to_value(&self) -> Value {
+        let string = |s: &str| Value::String(s.to_string());
+        let optional = |s: &Option<String>| s.as_deref().map_or(Value::Null, string);
+        let theme = &self.theme;
+        Value::Table(Table::from([
+            ("title".into(), string(&self.title)),
+            ("base_url".into(), optional(&self.base_url)),
+            ("base_path".into(), string(&self.base_path)),
+            ("pretty_urls".into(), Value::Boolean(self.pretty_urls)),
+            (
+                "ignore".into(),
+                Value::Array(self.ignore.iter().map(|s| string(s)).collect()),
+            ),
+            ("author".into(), optional(&self.author)),
+            ("language".into(), string(&self.language)),
+            (
+                "theme".into(),
+                Value::Table(Table::from([
+                    ("text".into(), optional(&theme.text)),
+                    ("background".into(), optional(&theme.background)),
+                    ("link".into(), optional(&theme.link)),
+                    ("dark_text".into(), string(&theme.dark_text)),
+                    ("dark_background".into(), string(&theme.dark_background)),
+                    ("dark_link".into(), string(&theme.dark_link)),
+                ])),
+            ),
+            (
+                "feed".into(),
+                Value::Table(Table::from([
+                    ("limit".into(), Value::Integer(self.feed.limit as i64)),
+                    (
+                        "full_content".into(),
+                        Value::Boolean(self.feed.full_content),
+                    ),
+                ])),
+            ),
+            (
+                "robots".into(),
+                Value::Table(Table::from([(
+                    "disallow".into(),
+                    Value::Array(self.robots.disallow.iter().map(|s| string(s)).collect()),
+                )])),
+            ),
+            (
+                "snippets".into(),
+                Value::Table(Table::from([
+                    ("vendor".into(), optional(&self.snippets.vendor)),
+                    ("edition".into(), string(&self.snippets.edition)),
+                    (
+                        "playground".into(),
+                        Value::Boolean(self.snippets.playground),
+                    ),
+                ])),
+            ),
+            (
+                "docs".into(),
+                Value::Table(Table::from([(
+                    "crates".into(),
+                    Value::Table(
+                        self.docs
+                            .crates
+                            .iter()
+                            .map(|(name, version)| (name.clone(), string(version)))
+                            .collect(),
+                    ),
+                )])),
+            ),
+            (
+                "dates".into(),
+                Value::Table(Table::from([
+                    ("input".into(), optional(&self.dates.input)),
+                    ("format".into(), string(&self.dates.format)),
+                    (
+                        "months".into(),
+                        Value::Array(self.dates.months.iter().map(|s| string(s)).collect()),
+                    ),
+                    (
+                        "weekdays".into(),
+                        Value::Array(self.dates.weekdays.iter().map(|s| string(s)).collect()),
+                    ),
+                ])),
+            ),
+        ]))
+    }
+}
+
+/// Sets the dotted `key` in `table`, parsing `value` as TOML and falling back
+/// to a plain string.
+fn set(table: &mut Table, key: &str, value: &str) -> Result<(), String> {
+    let value = toml::parse(&format!("value = {value}"))
+        .ok()
+        .and_then(|mut parsed| parsed.remove("value"))
+        .unwrap_or_else(|| Value::String(value.to_string()));
+    let mut segments: Vec<&str> = key.split('.').collect();
+    let last = segments.pop().unwrap_or_default();
+    let mut table = table;
+    for segment in segments {
+        let entry = table
+            .entry(segment.to_string())
+            .or_insert_with(|| Value::Table(Table::new()));
+        table = match entry {
+            Value::Table(inner) => inner,
+            _ => {
+                return Err(format!(
+                    "cannot override `{key}`: `{segment}` is not a table"
+                ))
+            }
+        };
+    }
+    table.insert(last.to_string(), value);
+    Ok(())
+}
+
+/// Whether the dotted `key` is one of those in `defaults`, the configuration
+/// as [`Config::to_value`] gives it. Any key is known inside a table that is
+/// empty by default, such as `docs.crates`.
+fn is_known(defaults: &Table, key: &str) -> bool {
+    let mut segments = key.split('.');
+    let mut table = defaults;
+    while let Some(segment) = segments.next() {
+        match table.get(segment) {
+            Some(Value::Table(inner)) if inner.is_empty() => return true,
+            Some(Value::Table(inner)) => table = inner,
+            Some(_) => return segments.next().is_none(),
+            None => return false,
+        }
+    }
+    true
+}
+
+/// A table of keys being moved into a typed struct. Keys left over when it is
+/// finished are reported as unknown.
+struct Section {
+    prefix: String,
+    table: Table,
+}
+
+impl Section {
+    fn new(prefix: &str, table: Table) -> Self {
+        Section {
+            prefix: prefix.to_string(),
+            table,
+        }
+    }
+
+    fn key(&self, key: &str) -> String {
+        format!("{}{key}", self.prefix)
+    }
+
+    fn string(&mut self, key: &str) -> Result<Option<String>, String> {
+        match self.table.remove(key) {
+            None => Ok(None),
+            Some(Value::String(s)) => Ok(Some(s)),
+            Some(other) => Err(self.type_error(key, "a string", &other)),
+        }
+    }
+
+    fn strings(&mut self, key: &str) -> Result<Option<Vec<String>>, String> {
+        match self.table.remove(key) {
+            None => Ok(None),
+            Some(Value::Array(items)) => items
+                .into_iter()
+                .map(|item| match item {
+                    Value::String(s) => Ok(s),
+                    other => Err(self.type_error(key, "a list of strings", &other)),
+                })
+                .collect::<Result<_, _>>()
+                .map(Some),
+            Some(other) => Err(self.type_error(key, "a list of strings", &other)),
+        }
+    }
+
+    fn integer(&mut self, key: &str) -> Result<Option<i64>, String> {
+        match self.table.remove(key) {
+            None => Ok(None),
+            Some(Value::Integer(i)) => Ok(Some(i)),
+            Some(other) => Err(self.type_error(key, "an integer", &other)),
+        }
+    }
+
+    fn boolean(&mut self, key: &str) -> Result<Option<bool>, String> {
+        match self.table.remove(key) {
+            None => Ok(None),
+            Some(Value::Boolean(b)) => Ok(Some(b)),
+            Some(other) => Err(self.type_error(key, "a boolean", &other)),
+        }
+    }
+
+    /// A CSS color: a hex code, a function such as `rgb(...)` or a name.
+    fn color(&mut self, key: &str) -> Result<Option<String>, String> {
+        let color = self.string(key)?;
+        if let Some(color) = &color {
+            let valid = match color.strip_prefix('#') {
+                Some(hex) => {
+                    matches!(hex.len(), 3 | 4 | 6 | 8) && hex.chars().all(|c| c.is_ascii_hexdigit())
+                }
+                None => {
+                    !color.is_empty()
+                        && color
+                            .chars()
+                            .all(|c| c.is_ascii_alphanumeric() || " (),.%/-".contains(c))
+                }
+            };
+            if !valid {
+                return Err(format!(
+                    "`{}` is not a valid color: `{color}`",
+                    self.key(key)
+                ));
+            }
+        }
+        Ok(color)
+    }
+
+    fn section(&mut self, key: &str) -> Result<Section, String> {
+        let prefix = format!("{}.", self.key(key));
+        match self.table.remove(key) {
+            None => Ok(Section::new(&prefix, Table::new())),
+            Some(Value::Table(table)) => Ok(Section::new(&prefix, table)),
+            Some(other) => Err(self.type_error(key, "a table", &other)),
+        }
+    }
+
+    /// Fails on the first key that was never asked for.
+    fn finish(self) -> Result<(), String> {
+        match self.table.keys().next() {
+            Some(key) => Err(format!("unknown key `{}`", self.key(key))),
+            None => Ok(()),
+        }
+    }
+
+    fn type_error(&self, key: &str, expected: &str, found: &Value) -> String {
+        format!(
+            "`{}` must be {expected}, found {}",
+            self.key(key),
+            found.type_name()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::run::TempDir;
+
+    /// Loads `blog.toml` with `contents` under the environment `env`.
+    fn load(
+        contents: &str,
+        env: &[(&str, &str)],
+        overrides: &[(&str, &str)],
+    ) -> Result<Config, String> {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path.join(FILE_NAME);
+        std::fs::write(&path, contents).unwrap();
+        let env = env
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()));
+        let overrides: Vec<_> = overrides
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        Config::load_with(&path, env, &overrides).map_err(|e| {
+            // Errors start with the temporary path.
+            e.strip_prefix(&format!("{}", path.display()))
+                .unwrap_or(&e)
+                .to_string()
+        })
+    }
+
+    #[test]
+    fn defaults() {
+        let dir = TempDir::new().unwrap();
+        let config = Config::load_with(&dir.path.join(FILE_NAME), [], &[]).unwrap();
+        assert_eq!(config.title, "Blog");
+        assert_eq!(config.base_url, None);
+        assert_eq!(config.base_path, "");
+        assert!(!config.pretty_urls);
+        assert_eq!(config.language, "en");
+        assert_eq!(config.theme.dark_background, "#000");
+        assert_eq!(config.feed.limit, 20);
+        assert!(config.feed.full_content);
+        assert_eq!(config.snippets.edition, "2021");
+        assert!(!config.snippets.playground);
+        assert!(config.docs.crates.is_empty());
+        assert_eq!(config.dates.format, "%Y-%m-%d");
+        assert_eq!(config.dates.months[0], "January");
+        assert_eq!(config.dates.weekdays[6], "Sunday");
+    }
+
+    #[test]
+    fn file_values() {
+        let config = load(
+            "title = \"Notes\"\nbase_url = \"https://example.com/blog/\"\nbase_path = \"/blog/\"\n\n[feed]\nlimit = 5\n\n[docs.crates]\ntokio = \"1\"\n",
+            &[],
+            &[],
+        )
+        .unwrap();
+        assert_eq!(config.title, "Notes");
+        assert_eq!(config.base_url.as_deref(), Some("https://example.com/blog"));
+        assert_eq!(config.base_path, "/blog");
+        assert_eq!(config.feed.limit, 5);
+        assert_eq!(config.docs.crates["tokio"], "1");
+    }
+
+    #[test]
+    fn overrides_take_precedence() {
+        let file = "title = \"File\"\n[feed]\nlimit = 5\n";
+        let env = [
+            ("BLOG_TITLE", "Env"),
+            ("BLOG_FEED__LIMIT", "7"),
+            ("BLOG_FEED__FULL_CONTENT", "false"),
+            ("BLOG_TOKEN", "secret"),
+            ("HOME", "/root"),
+        ];
+        let config = load(file, &env, &[]).unwrap();
+        assert_eq!((config.title.as_str(), config.feed.limit), ("Env", 7));
+        assert!(!config.feed.full_content);
+
+        let config = load(file, &env, &[("title", "Flag"), ("title", "Last flag")]).unwrap();
+        assert_eq!((config.title.as_str(), config.feed.limit), ("Last flag", 7));
+
+        // Values are TOML when they parse as TOML, and strings otherwise.
+        let config = load(
+            "",
+            &[],
+            &[("title", "\"quoted\""), ("ignore", "[\"a\", \"b\"]")],
+        )
+        .unwrap();
+        assert_eq!(config.title, "quoted");
+        assert_eq!(config.ignore, ["a", "b"]);
+        let config = load("", &[], &[("title", "2024")]).unwrap_err();
+        assert_eq!(config, ": `title` must be a string, found integer");
+    }
+
+    #[test]
+    fn errors() {
+        let error = |file: &str| load(file, &[], &[]).unwrap_err();
+        assert_eq!(
+            error("title = 1\n"),
+            ": `title` must be a string, found integer"
+        );
+        assert_eq!(error("titel = \"x\"\n"), ": unknown key `titel`");
+        assert_eq!(error("[feed]\nmax = 1\n"), ": unknown key `feed.max`");
+        assert_eq!(
+            error("[feed]\nlimit = \"5\"\n"),
+            ": `feed.limit` must be an integer, found string"
+        );
+        assert_eq!(
+            error("[feed]\nlimit = 0\n"),
+            ": `feed.limit` must be at least 1, found 0"
+        );
+        assert_eq!(
+            error("base_url = \"example.com\"\n"),
+            ": `base_url` must be an http(s) URL, found `example.com`"
+        );
+        assert_eq!(
+            error("[snippets]\nedition = \"2019\"\n"),
+            ": `snippets.edition` must be one of 2015, 2018, 2021, 2024, found `2019`"
+        );
+        assert_eq!(
+            error("title = []]\n"),
+            ":1: expected the end of the line, found `]`"
+        );
+        assert_eq!(
+            load("feed = 1\n", &[], &[("feed.limit", "2")]).unwrap_err(),
+            "cannot override `feed.limit`: `feed` is not a table"
+        );
+    }
+
+    #[test]
+    fn known_keys() {
+        let Value::Table(defaults) = Config::from_table(Table::new()).unwrap().to_value() else {
+            panic!("the configuration is not a table");
+        };
+        for key in [
+            "title",
+            "base_url",
+            "feed.limit",
+            "dates.months",
+            "docs.crates.serde",
+        ] {
+            assert!(is_known(&defaults, key), "{key}");
+        }
+        for key in ["token", "feed.token", "title.text", "github_token"] {
+            assert!(!is_known(&defaults, key), "{key}");
+        }
+    }
+}
@@ -0,0 +1,705 @@
+//! Dates from front matter and post content, read in ISO-8601 or the
+//! configured format and written out for readers, feeds and sitemaps.
+
+use crate::config::Dates;
+use crate::html;
+
+/// A date with an optional time of day and UTC offset, as written in front
+/// matter. Dates compare by the moment they stand for, as
+/// [`timestamp`](Date::timestamp) gives it.
+#[derive(Clone, Copy, Debug)]
+pub struct Date {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+    /// Hours, minutes and seconds, if a time was given.
+    pub time: Option<(u32, u32, u32)>,
+    /// Offset from UTC in minutes, if one was given.
+    pub offset: Option<i32>,
+}
+
+impl Date {
+    /// Parses an ISO-8601 date such as `2024-01-11`, `2024-01-11T10:00:00Z` or
+    /// `2024-01-11 10:00+01:00`. Fractions of a second are ignored.
+    pub fn parse_iso(text: &str) -> Option<Date> {
+        let text = text.trim();
+        let (date, rest) = text.split_at_checked(10)?;
+        let mut parts = date.split('-');
+        let year = parse_digits(parts.next()?, 4)?;
+        let month = parse_digits(parts.next()?, 2)?;
+        let day = parse_digits(parts.next()?, 2)?;
+        Date::new(year as i32, month, day)?.with_time(rest)
+    }
+
+    /// Parses a date from front matter: ISO-8601, or else in the configured
+    /// `dates.input` format.
+    pub fn parse(text: &str, dates: &Dates) -> Option<Date> {
+        Date::parse_iso(text).or_else(|| {
+            let format = dates.input.as_deref()?;
+            Date::parse_format(text.trim(), format, &dates.months)
+        })
+    }
+
+    /// Parses a date as people write it in a post: as [`parse`](Date::parse)
+    /// does, or with the month's name in the site's language or in English,
+    /// such as `11 January 2024` or `January 11, 2024`. All-numeric dates
+    /// other than ISO-8601 are only read in the `dates.input` format, since
+    /// `11-01-2024` could be in January or in November.
+    pub fn parse_written(text: &str, dates: &Dates) -> Option<Date> {
+        if let Some(date) = Date::parse(text, dates) {
+            return Some(date);
+        }
+        let words: Vec<&str> = text
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|word| !word.is_empty())
+            .collect();
+        let [first, second, year] = words.as_slice() else {
+            return None;
+        };
+        let year = parse_digits(year, 4)? as i32;
+        let day = |word: &str| {
+            word.trim_end_matches(|c: char| c.is_alphabetic() || c == '.')
+                .parse::<u32>()
+                .ok()
+        };
+        match month_number(first, &dates.months) {
+            Some(month) => Date::new(year, month, day(second)?),
+            None => Date::new(year, month_number(second, &dates.months)?, day(first)?),
+        }
+    }
+
+    /// Parses `text` as written in `format`, followed by an optional time and
+    /// offset as in ISO-8601. See [`check_format`] for the directives.
+    fn parse_format(text: &str, format: &str, months: &[String]) -> Option<Date> {
+        let mut rest = text;
+        let (mut year, mut month, mut day) = (None, None, None);
+        let mut time: Option<(u32, u32, u32)> = None;
+        let mut offset = None;
+        let mut directives = format.chars();
+        while let Some(c) = directives.next() {
+            if c.is_whitespace() {
+                rest = rest.trim_start();
+                continue;
+            }
+            if c != '%' {
+                rest = rest.strip_prefix(c)?;
+                continue;
+            }
+            let mut directive = directives.next()?;
+            if directive == '-' {
+                directive = directives.next()?;
+            }
+            match directive {
+                'Y' => year = Some(take_digits(&mut rest, 4)? as i32),
+                'm' => month = Some(take_digits(&mut rest, 2)?),
+                'd' | 'e' => day = Some(take_digits(&mut rest, 2)?),
+                'B' | 'b' => month = Some(month_number(take_word(&mut rest), months)?),
+                'A' | 'a' => {
+                    take_word(&mut rest);
+                }
+                'H' => time.get_or_insert_default().0 = take_digits(&mut rest, 2)?,
+                'M' => time.get_or_insert_default().1 = take_digits(&mut rest, 2)?,
+                'S' => time.get_or_insert_default().2 = take_digits(&mut rest, 2)?,
+                'z' => {
+                    let len = rest.find(char::is_whitespace).unwrap_or(rest.len());
+                    offset = Some(parse_offset(&rest[..len])?);
+                    rest = &rest[len..];
+                }
+                '%' => rest = rest.strip_prefix('%')?,
+                _ => return None,
+            }
+        }
+        let mut date = Date::new(year?, month?, day?)?;
+        if let Some((hour, minute, second)) = time {
+            if hour > 23 || minute > 59 || second > 60 {
+                return None;
+            }
+            date.time = time;
+            date.offset = offset;
+            return rest.trim().is_empty().then_some(date);
+        }
+        date.with_time(rest)
+    }
+
+    /// A date without a time, if `month` and `day` exist in `year`.
+    fn new(year: i32, month: u32, day: u32) -> Option<Date> {
+        ((1..=12).contains(&month) && day > 0 && day <= days_in_month(year, month)).then_some(
+            Date {
+                year,
+                month,
+                day,
+                time: None,
+                offset: None,
+            },
+        )
+    }
+
+    /// This date with the time and offset in `rest`, what follows the date in
+    /// an ISO-8601 date and time such as `T10:00:00+01:00`.
+    fn with_time(mut self, rest: &str) -> Option<Date> {
+        let Some(rest) = rest.strip_prefix(['T', 't', ' ']) else {
+            return rest.is_empty().then_some(self);
+        };
+
+        let offset_start = rest.find(['Z', 'z', '+', '-']).unwrap_or(rest.len());
+        let (time, offset) = rest.split_at(offset_start);
+        let time = time.trim_end().split('.').next()?;
+        let mut fields = time.split(':');
+        let hour = parse_digits(fields.next()?, 2)?;
+        let minute = parse_digits(fields.next()?, 2)?;
+        let second = fields.next().map_or(Some(0), |s| parse_digits(s, 2))?;
+        if fields.next().is_some() || hour > 23 || minute > 59 || second > 60 {
+            return None;
+        }
+        self.time = Some((hour, minute, second));
+        self.offset = match offset {
+            "" => None,
+            offset => Some(parse_offset(offset)?),
+        };
+        Some(self)
+    }
+
+    /// The UTC date and time of `time`, such as a file modification time.
+    pub fn from_system_time(time: std::time::SystemTime) -> Date {
+        let seconds = match time.duration_since(std::time::UNIX_EPOCH) {
+            Ok(duration) => duration.as_secs() as i64,
+            Err(error) => -(error.duration().as_secs() as i64),
+        };
+        // From Howard Hinnant's `civil_from_days`.
+        let days = seconds.div_euclid(86_400) + 719_468;
+        let era = days.div_euclid(146_097);
+        let day_of_era = days - era * 146_097;
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let shifted_month = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+        let month = if shifted_month < 10 {
+            shifted_month + 3
+        } else {
+            shifted_month - 9
+        };
+        let year = year_of_era + era * 400 + i64::from(month <= 2);
+        let second_of_day = seconds.rem_euclid(86_400);
+        Date {
+            year: year as i32,
+            month: month as u32,
+            day: day as u32,
+            time: Some((
+                (second_of_day / 3600) as u32,
+                (second_of_day / 60 % 60) as u32,
+                (second_of_day % 60) as u32,
+            )),
+            offset: Some(0),
+        }
+    }
+
+    /// ISO-8601 with only the parts that were given, which
+    /// [`parse_iso`](Date::parse_iso) reads back.
+    pub fn iso(&self) -> String {
+        let mut out = format!("{:04}-{:02}-{:02}", self.year, self.month, self.day);
+        if let Some((hour, minute, second)) = self.time {
+            out += &format!("T{hour:02}:{minute:02}:{second:02}");
+            match self.offset {
+                Some(0) => out.push('Z'),
+                Some(offset) => out += &format_offset(offset),
+                None => {}
+            }
+        }
+        out
+    }
+
+    /// The date in the configured `dates.format`, for readers.
+    pub fn display(&self, dates: &Dates) -> String {
+        let abbreviate = |name: &str| name.chars().take(3).collect::<String>();
+        let (hour, minute, second) = self.time.unwrap_or_default();
+        let mut out = String::new();
+        let mut directives = dates.format.chars();
+        while let Some(c) = directives.next() {
+            if c != '%' {
+                out.push(c);
+                continue;
+            }
+            let (padded, directive) = match directives.next() {
+                Some('-') => (false, directives.next().unwrap_or('-')),
+                Some(directive) => (true, directive),
+                None => break,
+            };
+            let number = |n: u32| match padded {
+                true => format!("{n:02}"),
+                false => n.to_string(),
+            };
+            // Names are only known when the format asks for them, which the
+            // configuration checks.
+            let month = || {
+                let index = self.month as usize - 1;
+                dates.months.get(index).cloned().unwrap_or_default()
+            };
+            let weekday = || {
+                let index = self.weekday();
+                dates.weekdays.get(index).cloned().unwrap_or_default()
+            };
+            out += &match directive {
+                'Y' => format!("{:04}", self.year),
+                'm' => number(self.month),
+                'd' => number(self.day),
+                'e' => self.day.to_string(),
+                'B' => month(),
+                'b' => abbreviate(&month()),
+                'A' => weekday(),
+                'a' => abbreviate(&weekday()),
+                'H' => number(hour),
+                'M' => number(minute),
+                'S' => number(second),
+                'z' => self.offset.map(format_offset).unwrap_or_default(),
+                other => other.to_string(),
+            };
+        }
+        out
+    }
+
+    /// The date for readers, marked up with its ISO-8601 form for machines.
+    pub fn time_element(&self, dates: &Dates) -> String {
+        format!(
+            "<time datetime=\"{}\">{}</time>",
+            self.iso(),
+            html::escape(&self.display(dates))
+        )
+    }
+
+    /// RFC 3339, as used by Atom. Missing times are midnight and missing
+    /// offsets are UTC.
+    pub fn rfc3339(&self) -> String {
+        let (hour, minute, second) = self.time.unwrap_or_default();
+        let offset = match self.offset.unwrap_or(0) {
+            0 => "Z".to_string(),
+            offset => format_offset(offset),
+        };
+        format!(
+            "{:04}-{:02}-{:02}T{hour:02}:{minute:02}:{second:02}{offset}",
+            self.year, self.month, self.day
+        )
+    }
+
+    /// RFC 822 as updated by RFC 2822, as used by RSS.
+    pub fn rfc2822(&self) -> String {
+        const WEEKDAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+        const MONTHS: [&str; 12] = [
+            "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+        ];
+        let (hour, minute, second) = self.time.unwrap_or_default();
+        let offset = self.offset.unwrap_or(0);
+        format!(
+            "{}, {:02} {} {:04} {hour:02}:{minute:02}:{second:02} {}{:02}{:02}",
+            WEEKDAYS[self.days_since_epoch().rem_euclid(7) as usize],
+            self.day,
+            MONTHS[self.month as usize - 1],
+            self.year,
+            if offset < 0 { '-' } else { '+' },
+            offset.abs() / 60,
+            offset.abs() % 60
+        )
+    }
+
+    /// Seconds since 1970-01-01 UTC. Missing times are midnight and missing
+    /// offsets are UTC.
+    pub fn timestamp(&self) -> i64 {
+        let (hour, minute, second) = self.time.unwrap_or_default();
+        self.days_since_epoch() * 86_400 + i64::from(hour * 3600 + minute * 60 + second)
+            - i64::from(self.offset.unwrap_or(0)) * 60
+    }
+
+    /// The day of the week, from 0 for Monday.
+    fn weekday(&self) -> usize {
+        // 1970-01-01 was a Thursday.
+        (self.days_since_epoch() + 3).rem_euclid(7) as usize
+    }
+
+    /// Days from 1970-01-01 to this date, ignoring the time of day.
+    fn days_since_epoch(&self) -> i64 {
+        // From Howard Hinnant's `days_from_civil`.
+        let year = i64::from(self.year) - i64::from(self.month <= 2);
+        let era = year.div_euclid(400);
+        let year_of_era = year - era * 400;
+        let month = i64::from(self.month);
+        let day_of_year =
+            (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + i64::from(self.day) - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        era * 146_097 + day_of_era - 719_468
+    }
+}
+
+/// Checks a `dates` format and returns its directives: `%Y` for the year,
+/// `%m` and `%d` for the month and day (`%-m` and `%-d` without a leading
+/// zero, `%e` for the day without one either), `%B` and `%b` for the month's
+/// name and its first three letters, `%A` and `%a` the same for the weekday,
+/// `%H`, `%M` and `%S` for the time, `%z` for the offset from UTC and `%%`
+/// for `%`.
+pub fn check_format(format: &str) -> Result<Vec<char>, String> {
+    let mut used = Vec::new();
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            continue;
+        }
+        let directive = match chars.next() {
+            Some('-') => chars.next().filter(|c| "mdHMS".contains(*c)),
+            other => other,
+        };
+        match directive {
+            Some(directive) if "YmdeBbAaHMSz%".contains(directive) => used.push(directive),
+            Some(directive) => return Err(format!("unknown directive `%{directive}`")),
+            None => return Err("`%` must be followed by a directive".to_string()),
+        }
+    }
+    Ok(used)
+}
+
+/// Month and weekday names, from January and from Monday, in the languages
+/// dates can be shown in without listing them in the configuration.
+pub fn names(language: &str) -> Option<([&'static str; 12], [&'static str; 7])> {
+    let primary = language.split('-').next().unwrap_or_default();
+    NAMES
+        .iter()
+        .find(|(code, _, _)| primary.eq_ignore_ascii_case(code))
+        .map(|(_, months, weekdays)| (*months, *weekdays))
+}
+
+type Names = (&'static str, [&'static str; 12], [&'static str; 7]);
+
+const NAMES: [Names; 7] = [
+    (
+        "en",
+        [
+            "January",
+            "February",
+            "March",
+            "April",
+            "May",
+            "June",
+            "July",
+            "August",
+            "September",
+            "October",
+            "November",
+            "December",
+        ],
+        [
+            "Monday",
+            "Tuesday",
+            "Wednesday",
+            "Thursday",
+            "Friday",
+            "Saturday",
+            "Sunday",
+        ],
+    ),
+    (
+        "de",
+        [
+            "Januar",
+            "Februar",
+            "März",
+            "April",
+            "Mai",
+            "Juni",
+            "Juli",
+            "August",
+            "September",
+            "Oktober",
+            "November",
+            "Dezember",
+        ],
+        [
+            "Montag",
+            "Dienstag",
+            "Mittwoch",
+            "Donnerstag",
+            "Freitag",
+            "Samstag",
+            "Sonntag",
+        ],
+    ),
+    (
+        "es",
+        [
+            "enero",
+            "febrero",
+            "marzo",
+            "abril",
+            "mayo",
+            "junio",
+            "julio",
+            "agosto",
+            "septiembre",
+            "octubre",
+            "noviembre",
+            "diciembre",
+        ],
+        [
+            "lunes",
+            "martes",
+            "miércoles",
+            "jueves",
+            "viernes",
+            "sábado",
+            "domingo",
+        ],
+    ),
+    (
+        "fr",
+        [
+            "janvier",
+            "février",
+            "mars",
+            "avril",
+            "mai",
+            "juin",
+            "juillet",
+            "août",
+            "septembre",
+            "octobre",
+            "novembre",
+            "décembre",
+        ],
+        [
+            "lundi", "mardi", "mercredi", "jeudi", "vendredi", "samedi", "dimanche",
+        ],
+    ),
+    (
+        "it",
+        [
+            "gennaio",
+            "febbraio",
+            "marzo",
+            "aprile",
+            "maggio",
+            "giugno",
+            "luglio",
+            "agosto",
+            "settembre",
+            "ottobre",
+            "novembre",
+            "dicembre",
+        ],
+        [
+            "lunedì",
+            "martedì",
+            "mercoledì",
+            "giovedì",
+            "venerdì",
+            "sabato",
+            "domenica",
+        ],
+    ),
+    (
+        "nl",
+        [
+            "januari",
+            "februari",
+            "maart",
+            "april",
+            "mei",
+            "juni",
+            "juli",
+            "augustus",
+            "september",
+            "oktober",
+            "november",
+            "december",
+        ],
+        [
+            "maandag",
+            "dinsdag",
+            "woensdag",
+            "donderdag",
+            "vrijdag",
+            "zaterdag",
+            "zondag",
+        ],
+    ),
+    (
+        "pt",
+        [
+            "janeiro",
+            "fevereiro",
+            "março",
+            "abril",
+            "maio",
+            "junho",
+            "julho",
+            "agosto",
+            "setembro",
+            "outubro",
+            "novembro",
+            "dezembro",
+        ],
+        [
+            "segunda-feira",
+            "terça-feira",
+            "quarta-feira",
+            "quinta-feira",
+            "sexta-feira",
+            "sábado",
+            "domingo",
+        ],
+    ),
+];
+
+/// The month `word` names in `months` or in English, in full or by its first
+/// three letters or more, in any case.
+fn month_number(word: &str, months: &[String]) -> Option<u32> {
+    let word = word.trim_end_matches('.').to_lowercase();
+    let matches = |name: &str| {
+        let name = name.to_lowercase();
+        name == word || (word.chars().count() >= 3 && name.starts_with(&word))
+    };
+    let english = NAMES[0].1.iter().copied();
+    let position = months
+        .iter()
+        .map(String::as_str)
+        .position(matches)
+        .or_else(|| english.clone().position(matches))?;
+    Some(position as u32 + 1)
+}
+
+/// Takes up to `max` digits off the front of `rest`.
+fn take_digits(rest: &mut &str, max: usize) -> Option<u32> {
+    let len = rest
+        .bytes()
+        .take(max)
+        .take_while(u8::is_ascii_digit)
+        .count();
+    let value = rest[..len].parse().ok()?;
+    *rest = &rest[len..];
+    Some(value)
+}
+
+/// Takes a word, such as the name of a month, off the front of `rest`.
+fn take_word<'a>(rest: &mut &'a str) -> &'a str {
+    let len = rest
+        .find(|c: char| !c.is_alphabetic() && c != '.')
+        .unwrap_or(rest.len());
+    let (word, after) = rest.split_at(len);
+    *rest = after;
+    word
+}
+
+/// An offset from UTC such as `Z`, `+01:00` or `-0500`, in minutes.
+fn parse_offset(text: &str) -> Option<i32> {
+    if matches!(text, "Z" | "z") {
+        return Some(0);
+    }
+    let sign = match text.chars().next()? {
+        '+' => 1,
+        '-' => -1,
+        _ => return None,
+    };
+    let rest = &text[1..];
+    let (hours, minutes) = rest.split_once(':').or_else(|| {
+        rest.split_at_checked(2)
+            .filter(|(_, minutes)| minutes.len() == 2)
+    })?;
+    let hours = parse_digits(hours, 2)? as i32;
+    let minutes = parse_digits(minutes, 2)? as i32;
+    Some(sign * (hours * 60 + minutes))
+}
+
+fn format_offset(offset: i32) -> String {
+    format!(
+        "{}{:02}:{:02}",
+        if offset < 0 { '-' } else { '+' },
+        offset.abs() / 60,
+        offset.abs() % 60
+    )
+}
+
+impl PartialEq for Date {
+    fn eq(&self, other: &Self) -> bool {
+        self.timestamp() == other.timestamp()
+    }
+}
+
+impl Eq for Date {}
+
+impl PartialOrd for Date {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Date {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.timestamp().cmp(&other.timestamp())
+    }
+}
+
+fn parse_digits(text: &str, len: usize) -> Option<u32> {
+    if text.len() == len && text.bytes().all(|b| b.is_ascii_digit()) {
+        text.parse().ok()
+    } else {
+        None
+    }
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dates(format: &str, names: bool) -> Dates {
+        let (months, weekdays) = match names {
+            true => names_for("en"),
+            false => (Vec::new(), Vec::new()),
+        };
+        Dates {
+            input: None,
+            format: format.to_string(),
+            months,
+            weekdays,
+        }
+    }
+
+    fn names_for(language: &str) -> (Vec<String>, Vec<String>) {
+        let (months, weekdays) = names(language).unwrap();
+        (
+            months.iter().map(|name| name.to_string()).collect(),
+            weekdays.iter().map(|name| name.to_string()).collect(),
+        )
+    }
+
+    #[test]
+    fn display_numeric_formats_without_names() {
+        let date = Date::parse_iso("2024-01-11").unwrap();
+        assert_eq!(date.display(&dates("%Y-%m-%d", false)), "2024-01-11");
+        assert_eq!(date.display(&dates("%-d/%-m/%Y", false)), "11/1/2024");
+    }
+
+    #[test]
+    fn display_names() {
+        let date = Date::parse_iso("2024-01-11T09:05:00+01:00").unwrap();
+        assert_eq!(
+            date.display(&dates("%A %-d %B %Y, %H:%M %z", true)),
+            "Thursday 11 January 2024, 09:05 +01:00"
+        );
+        assert_eq!(date.display(&dates("%a %b %e", true)), "Thu Jan 11");
+    }
+
+    #[test]
+    fn order_by_moment() {
+        let date = |text| Date::parse_iso(text).unwrap();
+        // 10:00 in Paris is before 10:00 in London.
+        assert!(date("2024-01-11T10:00:00+01:00") < date("2024-01-11T10:00:00Z"));
+        assert!(date("2024-01-11") < date("2024-01-11T08:00:00Z"));
+        assert!(date("2024-01-11") > date("2024-01-10T23:00:00Z"));
+        assert_eq!(date("2024-01-11"), date("2024-01-11T01:00:00+01:00"));
+    }
+}
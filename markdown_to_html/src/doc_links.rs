@@ -0,0 +1,757 @@
+//! Links from Rust paths in inline code to their documentation, the way
+//! rustdoc resolves intra-doc links: `` `std::fs::read_dir` `` links to
+//! doc.rust-lang.org, `` `octocrab::Octocrab` `` to docs.rs, and the
+//! shorthand `` [`Vec`] `` to the standard library item of that name.
+//!
+//! Nothing is looked up online. Crates outside the standard library are linked
+//! at the version given in the page's `dependencies` or in `docs.crates`. The
+//! kind of an item, which its URL depends on, comes from a rustdoc
+//! disambiguator such as `fn@`, a `()` or `!` suffix, the standard library
+//! items listed here or the naming conventions. Paths whose kind is still
+//! unknown link to a search of the crate's documentation.
+
+use std::collections::BTreeMap;
+
+use markdown::mdast::Node;
+
+use crate::html::{escape, unescape};
+use crate::value::{Table, Value};
+
+/// Crates documented on doc.rust-lang.org.
+const STD_CRATES: [&str; 5] = ["std", "core", "alloc", "proc_macro", "test"];
+
+/// Standard library items whose kind can't be told from their path, and that
+/// the shorthand links to by name.
+const STD_ITEMS: &[(&str, Kind)] = &[
+    ("borrow::Cow", Kind::Enum),
+    ("borrow::ToOwned", Kind::Trait),
+    ("boxed::Box", Kind::Struct),
+    ("cell::Cell", Kind::Struct),
+    ("cell::RefCell", Kind::Struct),
+    ("clone::Clone", Kind::Trait),
+    ("cmp::Eq", Kind::Trait),
+    ("cmp::Ord", Kind::Trait),
+    ("cmp::Ordering", Kind::Enum),
+    ("cmp::PartialEq", Kind::Trait),
+    ("cmp::PartialOrd", Kind::Trait),
+    ("collections::BTreeMap", Kind::Struct),
+    ("collections::BTreeSet", Kind::Struct),
+    ("collections::BinaryHeap", Kind::Struct),
+    ("collections::HashMap", Kind::Struct),
+    ("collections::HashSet", Kind::Struct),
+    ("collections::VecDeque", Kind::Struct),
+    ("convert::AsMut", Kind::Trait),
+    ("convert::AsRef", Kind::Trait),
+    ("convert::From", Kind::Trait),
+    ("convert::Into", Kind::Trait),
+    ("convert::TryFrom", Kind::Trait),
+    ("convert::TryInto", Kind::Trait),
+    ("default::Default", Kind::Trait),
+    ("error::Error", Kind::Trait),
+    ("ffi::OsStr", Kind::Struct),
+    ("ffi::OsString", Kind::Struct),
+    ("fmt::Debug", Kind::Trait),
+    ("fmt::Display", Kind::Trait),
+    ("fmt::Formatter", Kind::Struct),
+    ("fs::File", Kind::Struct),
+    ("future::Future", Kind::Trait),
+    ("hash::Hash", Kind::Trait),
+    ("io::BufRead", Kind::Trait),
+    ("io::BufReader", Kind::Struct),
+    ("io::Read", Kind::Trait),
+    ("io::Write", Kind::Trait),
+    ("iter::Extend", Kind::Trait),
+    ("iter::FromIterator", Kind::Trait),
+    ("iter::IntoIterator", Kind::Trait),
+    ("iter::Iterator", Kind::Trait),
+    ("marker::Copy", Kind::Trait),
+    ("marker::PhantomData", Kind::Struct),
+    ("marker::Send", Kind::Trait),
+    ("marker::Sized", Kind::Trait),
+    ("marker::Sync", Kind::Trait),
+    ("net::TcpListener", Kind::Struct),
+    ("net::TcpStream", Kind::Struct),
+    ("ops::Deref", Kind::Trait),
+    ("ops::DerefMut", Kind::Trait),
+    ("ops::Drop", Kind::Trait),
+    ("ops::Fn", Kind::Trait),
+    ("ops::FnMut", Kind::Trait),
+    ("ops::FnOnce", Kind::Trait),
+    ("option::Option", Kind::Enum),
+    ("path::Path", Kind::Struct),
+    ("path::PathBuf", Kind::Struct),
+    ("pin::Pin", Kind::Struct),
+    ("process::Command", Kind::Struct),
+    ("rc::Rc", Kind::Struct),
+    ("result::Result", Kind::Enum),
+    ("str::FromStr", Kind::Trait),
+    ("string::String", Kind::Struct),
+    ("string::ToString", Kind::Trait),
+    ("sync::Arc", Kind::Struct),
+    ("sync::Mutex", Kind::Struct),
+    ("sync::RwLock", Kind::Struct),
+    ("thread::JoinHandle", Kind::Struct),
+    ("time::Duration", Kind::Struct),
+    ("time::Instant", Kind::Struct),
+    ("time::SystemTime", Kind::Struct),
+    ("vec::Vec", Kind::Struct),
+];
+
+/// Variants in the prelude, which the shorthand links to by name.
+const PRELUDE_VARIANTS: [&str; 4] = [
+    "option::Option::Some",
+    "option::Option::None",
+    "result::Result::Ok",
+    "result::Result::Err",
+];
+
+/// Modules of the standard library, so that paths into them can be told
+/// apart from functions.
+const STD_MODULES: &[&str] = &[
+    "alloc",
+    "any",
+    "array",
+    "ascii",
+    "backtrace",
+    "borrow",
+    "boxed",
+    "cell",
+    "char",
+    "clone",
+    "cmp",
+    "collections",
+    "collections::btree_map",
+    "collections::btree_set",
+    "collections::hash_map",
+    "collections::hash_set",
+    "collections::vec_deque",
+    "convert",
+    "default",
+    "env",
+    "error",
+    "f32",
+    "f32::consts",
+    "f64",
+    "f64::consts",
+    "ffi",
+    "fmt",
+    "fs",
+    "future",
+    "hash",
+    "hint",
+    "io",
+    "io::prelude",
+    "iter",
+    "marker",
+    "mem",
+    "net",
+    "num",
+    "ops",
+    "option",
+    "os",
+    "os::unix",
+    "os::windows",
+    "panic",
+    "path",
+    "pin",
+    "prelude",
+    "primitive",
+    "process",
+    "ptr",
+    "rc",
+    "result",
+    "slice",
+    "str",
+    "string",
+    "sync",
+    "sync::atomic",
+    "sync::mpsc",
+    "task",
+    "thread",
+    "time",
+    "vec",
+];
+
+/// Macros exported from the root of `std`, linked to by name even outside
+/// the shorthand since the `!` leaves no doubt.
+const STD_MACROS: &[&str] = &[
+    "assert",
+    "assert_eq",
+    "assert_ne",
+    "concat",
+    "dbg",
+    "debug_assert",
+    "debug_assert_eq",
+    "debug_assert_ne",
+    "env",
+    "eprint",
+    "eprintln",
+    "format",
+    "include_str",
+    "matches",
+    "panic",
+    "print",
+    "println",
+    "stringify",
+    "todo",
+    "unimplemented",
+    "unreachable",
+    "vec",
+    "write",
+    "writeln",
+];
+
+const PRIMITIVES: &[&str] = &[
+    "bool",
+    "char",
+    "f32",
+    "f64",
+    "i8",
+    "i16",
+    "i32",
+    "i64",
+    "i128",
+    "isize",
+    "str",
+    "u8",
+    "u16",
+    "u32",
+    "u64",
+    "u128",
+    "usize",
+    "array",
+    "slice",
+    "tuple",
+    "unit",
+    "never",
+    "pointer",
+    "reference",
+    "fn",
+];
+
+/// An inline code span that could not be linked although it names an item, with
+/// the 1-based line it is on in the markdown, when that is known.
+pub struct Warning {
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+/// The crates a page can link into, by the name they have in paths.
+pub struct Crates(BTreeMap<String, Crate>);
+
+struct Crate {
+    /// URL of the documentation's root module, ending in `/`.
+    base: String,
+    /// Whether it is part of the standard library, whose modules and common
+    /// items are known here.
+    std: bool,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    Module,
+    Struct,
+    Enum,
+    Trait,
+    Union,
+    Type,
+    Function,
+    Macro,
+    Constant,
+    Static,
+    Primitive,
+}
+
+impl Kind {
+    /// The kind named by a rustdoc disambiguator, as in `struct@Foo`.
+    fn from_disambiguator(prefix: &str) -> Option<Kind> {
+        Some(match prefix {
+            "mod" | "module" => Kind::Module,
+            "struct" => Kind::Struct,
+            "enum" => Kind::Enum,
+            "trait" => Kind::Trait,
+            "union" => Kind::Union,
+            "type" => Kind::Type,
+            "fn" | "function" | "method" => Kind::Function,
+            "macro" => Kind::Macro,
+            "const" | "constant" => Kind::Constant,
+            "static" => Kind::Static,
+            "prim" | "primitive" => Kind::Primitive,
+            _ => return None,
+        })
+    }
+
+    /// How rustdoc starts the file names of items of this kind.
+    fn file_prefix(self) -> &'static str {
+        match self {
+            Kind::Module => "index",
+            Kind::Struct => "struct",
+            Kind::Enum => "enum",
+            Kind::Trait => "trait",
+            Kind::Union => "union",
+            Kind::Type => "type",
+            Kind::Function => "fn",
+            Kind::Macro => "macro",
+            Kind::Constant => "constant",
+            Kind::Static => "static",
+            Kind::Primitive => "primitive",
+        }
+    }
+}
+
+impl Crates {
+    /// The standard library, the crates in `docs.crates` and the page's
+    /// `dependencies`, which take precedence.
+    pub fn new(configured: &BTreeMap<String, String>, dependencies: &Table) -> Self {
+        let mut crates = BTreeMap::new();
+        for name in STD_CRATES {
+            crates.insert(
+                name.to_string(),
+                Crate {
+                    base: format!("https://doc.rust-lang.org/{name}/"),
+                    std: true,
+                },
+            );
+        }
+        for (name, version) in configured {
+            crates.insert(name.replace('-', "_"), Crate::docs_rs(name, version));
+        }
+        for (name, spec) in dependencies {
+            // `foo = { package = "bar" }` is used as `foo` but published as
+            // `bar`.
+            let (package, version) = match spec {
+                Value::String(version) => (name.as_str(), version.as_str()),
+                Value::Table(table) => {
+                    let string = |key: &str| match table.get(key) {
+                        Some(Value::String(s)) => Some(s.as_str()),
+                        _ => None,
+                    };
+                    (
+                        string("package").unwrap_or(name),
+                        string("version").unwrap_or("latest"),
+                    )
+                }
+                _ => continue,
+            };
+            crates.insert(name.replace('-', "_"), Crate::docs_rs(package, version));
+        }
+        Crates(crates)
+    }
+
+    /// The URL `text` links to, `None` if it is not a path to link, or why a
+    /// path that should be linked could not be.
+    fn resolve(&self, text: &str, shorthand: bool) -> Result<Option<String>, String> {
+        let Some(path) = Path::parse(text) else {
+            return Ok(None);
+        };
+        let (first, rest) = path.segments.split_first().unwrap_or((&"", &[]));
+        if let Some(krate) = self.0.get(*first) {
+            return Ok(Some(krate.url(rest, path.kind)));
+        }
+        if rest.is_empty() && path.kind == Some(Kind::Macro) && STD_MACROS.contains(first) {
+            return Ok(Some(self.0["std"].url(&path.segments, path.kind)));
+        }
+        // Bare names are only taken for items when written as links, so that
+        // `String` in running text stays as it is.
+        if shorthand || !rest.is_empty() {
+            if let Some(item) = std_item(first) {
+                let mut segments: Vec<&str> = item.split("::").collect();
+                segments.extend(rest);
+                return Ok(Some(self.0["std"].url(&segments, path.kind)));
+            }
+            if PRIMITIVES.contains(first) {
+                let url = format!("https://doc.rust-lang.org/std/primitive.{first}.html");
+                return Ok(Some(match rest {
+                    [] => url,
+                    [name] => {
+                        let kind = path.kind.or_else(|| convention(name));
+                        format!("{url}#{}.{name}", member(name, kind))
+                    }
+                    _ => return Err(format!("unresolved link to `{text}`")),
+                }));
+            }
+        }
+        let crate_like = first
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+            && !["self", "super", "crate"].contains(first);
+        match (shorthand, rest.is_empty(), crate_like) {
+            (_, false, true) => Err(format!(
+                "unresolved link to `{text}`: `{first}` is not in the page's `dependencies` or in `docs.crates`"
+            )),
+            (true, _, _) => Err(format!(
+                "unresolved link to `{text}`: no such item in the standard library"
+            )),
+            (false, _, _) => Ok(None),
+        }
+    }
+}
+
+impl Crate {
+    fn docs_rs(package: &str, version: &str) -> Self {
+        let version = match version.trim_start_matches(['^', '=', '~']) {
+            "" | "*" => "latest",
+            version => version,
+        };
+        Crate {
+            base: format!(
+                "https://docs.rs/{package}/{version}/{}/",
+                package.replace('-', "_")
+            ),
+            std: false,
+        }
+    }
+
+    /// The URL of the item at `path` within the crate.
+    fn url(&self, path: &[&str], kind: Option<Kind>) -> String {
+        let Some((name, parents)) = path.split_last() else {
+            return self.base.clone();
+        };
+        let known = |path: &[&str]| match self.std {
+            true => std_kind(path),
+            false => None,
+        };
+        let kind = kind.or_else(|| known(path)).or_else(|| convention(name));
+        // Methods, variants and associated items are anchors on the page of
+        // their type.
+        if parents.last().is_some_and(|parent| is_type_name(parent)) {
+            return match known(parents) {
+                Some(parent_kind) => format!(
+                    "{}#{}.{name}",
+                    self.item_url(parents, parent_kind),
+                    member(name, kind)
+                ),
+                None => self.search_url(path),
+            };
+        }
+        match kind {
+            Some(kind) => self.item_url(path, kind),
+            None => self.search_url(path),
+        }
+    }
+
+    fn item_url(&self, path: &[&str], kind: Kind) -> String {
+        let Some((name, modules)) = path.split_last() else {
+            return self.base.clone();
+        };
+        let dir: String = modules.iter().map(|module| format!("{module}/")).collect();
+        match kind {
+            Kind::Module => format!("{}{dir}{name}/index.html", self.base),
+            // Primitives are documented at the root of `std` and `core`.
+            Kind::Primitive => format!("{}primitive.{name}.html", self.base),
+            _ => format!("{}{dir}{}.{name}.html", self.base, kind.file_prefix()),
+        }
+    }
+
+    fn search_url(&self, path: &[&str]) -> String {
+        format!("{}?search={}", self.base, path.join("::"))
+    }
+}
+
+/// A path as written in inline code, such as `std::fs::read_dir`,
+/// `fn@foo::bar`, `Vec<T>` or `println!`.
+struct Path<'a> {
+    kind: Option<Kind>,
+    segments: Vec<&'a str>,
+}
+
+impl<'a> Path<'a> {
+    fn parse(text: &'a str) -> Option<Self> {
+        let (mut kind, mut text) = match text.split_once('@') {
+            Some((prefix, rest)) => (Some(Kind::from_disambiguator(prefix)?), rest),
+            None => (None, text),
+        };
+        if let Some(rest) = text.strip_suffix("()") {
+            text = rest;
+            kind = kind.or(Some(Kind::Function));
+        }
+        if let Some(rest) = text.strip_suffix('!') {
+            text = rest;
+            kind = Some(Kind::Macro);
+        }
+        // Generic arguments don't change what is linked to.
+        if text.ends_with('>') {
+            text = &text[..text.find('<')?];
+        }
+        let segments: Vec<&str> = text.split("::").collect();
+        segments
+            .iter()
+            .all(|segment| is_identifier(segment))
+            .then_some(Path { kind, segments })
+    }
+}
+
+fn is_identifier(text: &str) -> bool {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(c) if c.is_alphabetic() || c == '_' => {
+            text != "_" && chars.all(|c| c.is_alphanumeric() || c == '_')
+        }
+        _ => false,
+    }
+}
+
+fn is_type_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_uppercase()) && convention(name).is_none()
+}
+
+/// The kind the naming conventions give away: `SCREAMING_CASE` is for
+/// constants.
+fn convention(name: &str) -> Option<Kind> {
+    let screaming = name.chars().any(|c| c.is_ascii_uppercase())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_');
+    (screaming && name.len() > 1).then_some(Kind::Constant)
+}
+
+/// The anchor rustdoc gives `name` on the page of its type.
+fn member(name: &str, kind: Option<Kind>) -> &'static str {
+    match kind {
+        Some(Kind::Constant) => "associatedconstant",
+        Some(Kind::Type) => "associatedtype",
+        Some(Kind::Function) => "method",
+        _ if name.starts_with(|c: char| c.is_ascii_uppercase()) => "variant",
+        _ => "method",
+    }
+}
+
+/// The path within `std` of the item the shorthand names `name`.
+fn std_item(name: &str) -> Option<&'static str> {
+    STD_ITEMS
+        .iter()
+        .map(|(path, _)| *path)
+        .chain(PRELUDE_VARIANTS)
+        .find(|path| path.rsplit("::").next() == Some(name))
+}
+
+/// The kind of the item at `path` within `std`: one of the known items or
+/// modules, or a function directly in a known module.
+fn std_kind(path: &[&str]) -> Option<Kind> {
+    let joined = path.join("::");
+    if let Some((_, kind)) = STD_ITEMS.iter().find(|(item, _)| *item == joined) {
+        return Some(*kind);
+    }
+    if STD_MODULES.contains(&joined.as_str()) {
+        return Some(Kind::Module);
+    }
+    let (name, parents) = path.split_last()?;
+    let in_module = !parents.is_empty() && STD_MODULES.contains(&parents.join("::").as_str());
+    (in_module && name.starts_with(|c: char| c.is_ascii_lowercase())).then_some(Kind::Function)
+}
+
+/// Links the inline code in `html`, converted from `markdown`, that names a
+/// Rust item. Code in code blocks or already in a link is left alone.
+pub fn link(markdown: &str, html: &str, crates: &Crates) -> (String, Vec<Warning>) {
+    const START: &str = "<code>";
+    const END: &str = "</code>";
+    // The lines of inline code spans, matched to the HTML by their text.
+    let mut spans = Vec::new();
+    if let Ok(tree) = markdown::to_mdast(markdown, &markdown::ParseOptions::gfm()) {
+        collect(&tree, &mut spans);
+    }
+    let mut warnings = Vec::new();
+    let mut out = String::with_capacity(html.len());
+    let mut rest = html;
+    let mut link_depth = 0;
+    let mut pre_depth = 0;
+    while let Some(start) = rest.find(START) {
+        let Some(len) = rest[start..].find(END) else {
+            break;
+        };
+        let before = &rest[..start];
+        link_depth += before.matches("<a ").count();
+        link_depth -= before.matches("</a>").count().min(link_depth);
+        pre_depth += before.matches("<pre").count();
+        pre_depth -= before.matches("</pre>").count().min(pre_depth);
+        out.push_str(before);
+        let element = &rest[start..start + len + END.len()];
+        let content = &element[START.len()..len];
+        rest = &rest[start + len + END.len()..];
+        if link_depth > 0 || pre_depth > 0 {
+            out.push_str(element);
+            continue;
+        }
+        let text = unescape(content);
+        let line = spans
+            .iter()
+            .position(|(value, _)| *value == text)
+            .map(|i| spans.remove(i).1);
+        let shorthand = out.ends_with('[') && rest.starts_with(']');
+        match crates.resolve(&text, shorthand) {
+            Ok(Some(url)) => {
+                if shorthand {
+                    out.pop();
+                    rest = &rest[1..];
+                }
+                out += &format!("<a href=\"{}\">{element}</a>", escape(&url));
+            }
+            Ok(None) => out.push_str(element),
+            Err(message) => {
+                warnings.push(Warning { line, message });
+                out.push_str(element);
+            }
+        }
+    }
+    out.push_str(rest);
+    (out, warnings)
+}
+
+fn collect(node: &Node, spans: &mut Vec<(String, usize)>) {
+    if let Node::InlineCode(code) = node {
+        let line = code
+            .position
+            .as_ref()
+            .map_or(1, |position| position.start.line);
+        spans.push((code.value.clone(), line));
+    }
+    for child in node.children().into_iter().flatten() {
+        collect(child, spans);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The disambiguator-style name of the parsed kind and the segments.
+    fn parse(text: &str) -> Option<(Option<&'static str>, Vec<&str>)> {
+        Path::parse(text).map(|path| (path.kind.map(Kind::file_prefix), path.segments))
+    }
+
+    fn crates() -> Crates {
+        let configured = BTreeMap::from([("serde".to_string(), "1.0".to_string())]);
+        let dependencies = Table::from([
+            ("octocrab".to_string(), Value::String("0.38".to_string())),
+            (
+                "my-crate".to_string(),
+                Value::Table(Table::from([
+                    (
+                        "package".to_string(),
+                        Value::String("real-crate".to_string()),
+                    ),
+                    ("version".to_string(), Value::String("^2".to_string())),
+                ])),
+            ),
+            ("serde".to_string(), Value::String("*".to_string())),
+        ]);
+        Crates::new(&configured, &dependencies)
+    }
+
+    #[test]
+    fn paths() {
+        assert_eq!(
+            parse("std::fs::read_dir"),
+            Some((None, vec!["std", "fs", "read_dir"]))
+        );
+        assert_eq!(parse("Vec<T>"), Some((None, vec!["Vec"])));
+        assert_eq!(parse("HashMap<K, V>::new"), None);
+        assert_eq!(parse("foo::bar()"), Some((Some("fn"), vec!["foo", "bar"])));
+        assert_eq!(parse("fn@foo::bar"), Some((Some("fn"), vec!["foo", "bar"])));
+        assert_eq!(parse("struct@Foo"), Some((Some("struct"), vec!["Foo"])));
+        assert_eq!(parse("prim@str"), Some((Some("primitive"), vec!["str"])));
+        assert_eq!(parse("println!"), Some((Some("macro"), vec!["println"])));
+        assert_eq!(parse("widget@Foo"), None);
+        for text in ["", "a b", "::std", "std::", "1st", "_", "x + y", "a.b()"] {
+            assert_eq!(parse(text), None, "{text}");
+        }
+    }
+
+    #[test]
+    fn standard_library() {
+        let crates = crates();
+        let resolve = |text, shorthand| crates.resolve(text, shorthand);
+        let std = "https://doc.rust-lang.org/std";
+        assert_eq!(
+            resolve("std::fs::read_dir", false),
+            Ok(Some(format!("{std}/fs/fn.read_dir.html")))
+        );
+        assert_eq!(
+            resolve("std::fs", false),
+            Ok(Some(format!("{std}/fs/index.html")))
+        );
+        assert_eq!(
+            resolve("core::mem::swap", false),
+            Ok(Some(
+                "https://doc.rust-lang.org/core/mem/fn.swap.html".to_string()
+            ))
+        );
+        assert_eq!(
+            resolve("Vec", true),
+            Ok(Some(format!("{std}/vec/struct.Vec.html")))
+        );
+        assert_eq!(resolve("Vec", false), Ok(None));
+        assert_eq!(
+            resolve("Vec::new", false),
+            Ok(Some(format!("{std}/vec/struct.Vec.html#method.new")))
+        );
+        assert_eq!(
+            resolve("Option::Some", false),
+            Ok(Some(format!("{std}/option/enum.Option.html#variant.Some")))
+        );
+        assert_eq!(
+            resolve("println!", false),
+            Ok(Some(format!("{std}/macro.println.html")))
+        );
+        assert_eq!(
+            resolve("str", true),
+            Ok(Some(format!("{std}/primitive.str.html")))
+        );
+        assert_eq!(
+            resolve("str::len", false),
+            Ok(Some(format!("{std}/primitive.str.html#method.len")))
+        );
+        assert_eq!(
+            resolve("i32::MAX", false),
+            Ok(Some(format!(
+                "{std}/primitive.i32.html#associatedconstant.MAX"
+            )))
+        );
+        assert_eq!(
+            resolve("Nope", true),
+            Err("unresolved link to `Nope`: no such item in the standard library".to_string())
+        );
+    }
+
+    #[test]
+    fn other_crates() {
+        let crates = crates();
+        let resolve = |text, shorthand| crates.resolve(text, shorthand);
+        assert_eq!(
+            resolve("octocrab::Octocrab", false),
+            Ok(Some(
+                "https://docs.rs/octocrab/0.38/octocrab/?search=Octocrab".to_string()
+            ))
+        );
+        assert_eq!(
+            resolve("struct@octocrab::Octocrab", false),
+            Ok(Some(
+                "https://docs.rs/octocrab/0.38/octocrab/struct.Octocrab.html".to_string()
+            ))
+        );
+        assert_eq!(
+            resolve("my_crate::run()", false),
+            Ok(Some(
+                "https://docs.rs/real-crate/2/real_crate/fn.run.html".to_string()
+            ))
+        );
+        // The page's dependencies come before `docs.crates`.
+        assert_eq!(
+            resolve("mod@serde::de", false),
+            Ok(Some(
+                "https://docs.rs/serde/latest/serde/de/index.html".to_string()
+            ))
+        );
+        assert_eq!(
+            resolve("octocrab", false),
+            Ok(Some("https://docs.rs/octocrab/0.38/octocrab/".to_string()))
+        );
+        assert_eq!(
+            resolve("tokio::spawn", false),
+            Err("unresolved link to `tokio::spawn`: `tokio` is not in the page's `dependencies` or in `docs.crates`".to_string())
+        );
+        for text in ["value", "x.len()", "self::foo", "Some text", "a + b"] {
+            assert_eq!(resolve(text, false), Ok(None), "{text}");
+        }
+    }
+}
@@ -0,0 +1,187 @@
+//! The downloadable archive of a post's examples: every code block with a
+//! `file` attribute, written to that path in a Cargo project named after the
+//! post.
+//!
+//! Blocks that include a file contribute all of it rather than the part
+//! shown, blocks naming the same file are joined in order and Rust code keeps
+//! its hidden lines. Paths are taken from the directory of the shallowest
+//! `Cargo.toml` among them, and without one a manifest is added with the
+//! post's `dependencies`.
+
+use std::collections::BTreeMap;
+use std::path::{Component, Path};
+
+use crate::code::{self, CodeBlock};
+use crate::value::{Table, Value};
+use crate::{toml, zip};
+
+/// Builds the archive of the project `name` from `blocks`, or returns `None`
+/// when none of them has a `file`. Included files are named by their path
+/// from `root`, the site root.
+pub fn archive(
+    name: &str,
+    blocks: &[CodeBlock],
+    root: &Path,
+    dependencies: &Table,
+    edition: &str,
+) -> Result<Option<Vec<u8>>, code::Error> {
+    let mut files: BTreeMap<String, String> = BTreeMap::new();
+    for block in blocks {
+        let Some(file) = &block.file else {
+            continue;
+        };
+        let error = |message: String| code::Error {
+            line: block.line,
+            message,
+        };
+        let (path, contents) = match &block.included {
+            Some(included) => {
+                let relative = included.strip_prefix(root).unwrap_or(included);
+                let contents = std::fs::read_to_string(included)
+                    .map_err(|e| error(format!("cannot include `{file}`: {e}")))?;
+                (project_path(relative), contents)
+            }
+            None => {
+                let contents = match block.language.as_deref() {
+                    Some("rust") => block
+                        .code
+                        .lines()
+                        .map(|line| code::rust_line(line).0)
+                        .collect::<Vec<_>>()
+                        .join("\n"),
+                    _ => block.code.clone(),
+                };
+                (
+                    project_path(Path::new(file.trim_start_matches('/'))),
+                    contents,
+                )
+            }
+        };
+        let path = path.ok_or_else(|| {
+            error(format!(
+                "`{file}` is outside the site, so it can't be part of the examples"
+            ))
+        })?;
+        match files.get_mut(&path) {
+            Some(existing) if block.included.is_none() => {
+                existing.push('\n');
+                *existing += &contents;
+            }
+            Some(_) => {}
+            None => {
+                files.insert(path, contents);
+            }
+        }
+    }
+    if files.is_empty() {
+        return Ok(None);
+    }
+
+    let base = files
+        .keys()
+        .filter_map(|path| match path.rsplit_once('/') {
+            Some((dir, "Cargo.toml")) => Some(format!("{dir}/")),
+            None if path == "Cargo.toml" => Some(String::new()),
+            _ => None,
+        })
+        .min_by_key(|dir| dir.matches('/').count());
+    let mut files: BTreeMap<String, String> = match &base {
+        Some(base) => files
+            .into_iter()
+            .map(|(path, contents)| match path.strip_prefix(base.as_str()) {
+                Some(inside) => (inside.to_string(), contents),
+                None => (path, contents),
+            })
+            .collect(),
+        None => files,
+    };
+    if base.is_none() {
+        files.insert(
+            "Cargo.toml".to_string(),
+            manifest(name, dependencies, edition),
+        );
+    }
+    // Files end in a newline, which blocks leave out.
+    let entries: Vec<(String, String)> = files
+        .into_iter()
+        .map(|(path, contents)| {
+            let contents = format!("{}\n", contents.trim_end_matches('\n'));
+            (format!("{name}/{path}"), contents)
+        })
+        .collect();
+    Ok(Some(zip::archive(entries.iter().map(
+        |(path, contents)| (path.as_str(), contents.as_bytes()),
+    ))))
+}
+
+/// `path` with `/` separators, or `None` if it leads out of the site root.
+fn project_path(path: &Path) -> Option<String> {
+    let mut parts = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => parts.push(part.to_string_lossy()),
+            Component::CurDir => {}
+            Component::ParentDir if parts.pop().is_some() => {}
+            _ => return None,
+        }
+    }
+    Some(parts.join("/"))
+}
+
+/// A `Cargo.toml` for a project with no manifest of its own.
+fn manifest(name: &str, dependencies: &Table, edition: &str) -> String {
+    let package: String = name
+        .chars()
+        .map(|c| match c.is_ascii_alphanumeric() || c == '-' {
+            true => c.to_ascii_lowercase(),
+            false => '_',
+        })
+        .collect();
+    let mut manifest = format!(
+        "[package]\nname = {}\nversion = \"0.1.0\"\nedition = \"{edition}\"\n\n[dependencies]\n",
+        toml::to_inline_string(&Value::String(package))
+    );
+    for (name, spec) in dependencies {
+        manifest += &format!(
+            "{} = {}\n",
+            toml::key_string(name),
+            toml::to_inline_string(spec)
+        );
+    }
+    manifest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn project_paths() {
+        let path = |path: &str| project_path(Path::new(path));
+        assert_eq!(path("src/main.rs"), Some("src/main.rs".to_string()));
+        assert_eq!(path("./src/../Cargo.toml"), Some("Cargo.toml".to_string()));
+        assert_eq!(path("a/./b/../c"), Some("a/c".to_string()));
+        assert_eq!(path(""), Some(String::new()));
+        assert_eq!(path("../secret"), None);
+        assert_eq!(path("src/../../secret"), None);
+        assert_eq!(path("/etc/passwd"), None);
+    }
+
+    #[test]
+    fn manifests() {
+        let dependencies = Table::from([
+            ("serde".to_string(), Value::String("1".to_string())),
+            (
+                "tokio-util".to_string(),
+                Value::Table(Table::from([(
+                    "version".to_string(),
+                    Value::String("0.7".to_string()),
+                )])),
+            ),
+        ]);
+        assert_eq!(
+            manifest("My Post!", &dependencies, "2021"),
+            "[package]\nname = \"my_post_\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n[dependencies]\nserde = \"1\"\ntokio-util = { version = \"0.7\" }\n"
+        );
+    }
+}
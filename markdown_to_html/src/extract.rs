@@ -0,0 +1,176 @@
+//! Page metadata read from the markdown itself, for pages whose front matter
+//! leaves it out.
+//!
+//! A post usually starts with its title as the first `#` heading, often
+//! followed by the date on a line of its own, and then a first paragraph that
+//! says what the post is about. These stand in for the front matter's
+//! `title`, `date` and `description`. Paragraphs of nothing but links, such
+//! as one back to the home page, are passed over.
+
+use markdown::mdast::Node;
+
+use crate::config::Dates;
+use crate::date::Date;
+
+/// What the markdown says about the page. Lines are 1-based, in the
+/// markdown body.
+#[derive(Debug, Default)]
+pub struct Extracted {
+    /// The text of the first level 1 heading, and its line.
+    pub title: Option<(String, usize)>,
+    /// The first paragraph after the title, when it is nothing but a date.
+    pub date: Option<DateLine>,
+    /// The text of the first paragraph after the title and date that is more
+    /// than links.
+    pub description: Option<String>,
+}
+
+/// A paragraph that gives the date a post was published.
+#[derive(Debug)]
+pub struct DateLine {
+    pub date: Date,
+    /// The paragraph's text, as written.
+    pub text: String,
+    /// The paragraph's markdown.
+    pub markdown: String,
+    pub line: usize,
+}
+
+/// Reads the title, date and description from `markdown`, with dates read as
+/// `dates` configures.
+pub fn extract(markdown: &str, dates: &Dates) -> Extracted {
+    let mut extracted = Extracted::default();
+    let Ok(Node::Root(root)) = markdown::to_mdast(markdown, &markdown::ParseOptions::gfm()) else {
+        return extracted;
+    };
+    // Anything before the title, such as a link back home, is not part of
+    // the post.
+    let start = root
+        .children
+        .iter()
+        .position(|node| matches!(node, Node::Heading(heading) if heading.depth == 1));
+    if let Some(index) = start {
+        let heading = &root.children[index];
+        extracted.title =
+            Some((text(heading), line(heading))).filter(|(title, _)| !title.is_empty());
+    }
+    let mut first = true;
+    for node in &root.children[start.map_or(0, |index| index + 1)..] {
+        if !matches!(node, Node::Paragraph(_)) || is_navigation(node) {
+            continue;
+        }
+        let text = text(node);
+        if text.is_empty() {
+            continue;
+        }
+        if first {
+            first = false;
+            if let Some(date) = Date::parse_written(&text, dates) {
+                extracted.date = Some(DateLine {
+                    date,
+                    text,
+                    markdown: node
+                        .position()
+                        .and_then(|position| {
+                            markdown.get(position.start.offset..position.end.offset)
+                        })
+                        .unwrap_or_default()
+                        .to_string(),
+                    line: line(node),
+                });
+                continue;
+            }
+        }
+        extracted.description = Some(text);
+        break;
+    }
+    extracted
+}
+
+/// Whether the paragraph `node` is only links, with nothing but punctuation
+/// between them.
+fn is_navigation(node: &Node) -> bool {
+    let children = node.children().map(Vec::as_slice).unwrap_or_default();
+    children
+        .iter()
+        .any(|child| matches!(child, Node::Link(_) | Node::LinkReference(_)))
+        && children.iter().all(|child| match child {
+            Node::Link(_) | Node::LinkReference(_) | Node::Break(_) => true,
+            Node::Text(text) => !text.value.chars().any(char::is_alphanumeric),
+            _ => false,
+        })
+}
+
+/// The text of `node` on one line, without its markup.
+fn text(node: &Node) -> String {
+    let mut out = String::new();
+    push_text(node, &mut out);
+    out.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn push_text(node: &Node, out: &mut String) {
+    match node.children() {
+        Some(children) => children.iter().for_each(|child| push_text(child, out)),
+        None if matches!(node, Node::Break(_)) => out.push(' '),
+        None => out.push_str(&node.to_string()),
+    }
+}
+
+fn line(node: &Node) -> usize {
+    node.position().map_or(1, |position| position.start.line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dates() -> Dates {
+        Dates {
+            input: Some("%d-%m-%Y".to_string()),
+            format: "%Y-%m-%d".to_string(),
+            months: Vec::new(),
+            weekdays: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn title_date_and_description() {
+        let extracted = extract(
+            "[< home](/)\n\n# A *new* post\n\n_02-03-2024_\n\n```\ncode\n```\n\nWhat it is about,\nin [two](/) lines.\n\nMore.\n",
+            &dates(),
+        );
+        assert_eq!(extracted.title, Some(("A new post".to_string(), 3)));
+        let date = extracted.date.unwrap();
+        assert_eq!(date.date.iso(), "2024-03-02");
+        assert_eq!(
+            (date.text.as_str(), date.markdown.as_str(), date.line),
+            ("02-03-2024", "_02-03-2024_", 5)
+        );
+        assert_eq!(
+            extracted.description.as_deref(),
+            Some("What it is about, in two lines.")
+        );
+    }
+
+    #[test]
+    fn without_a_title_or_date() {
+        let extracted = extract(
+            "[< home](/) | [tags](/tags)\n\n## Section\n\nThe first paragraph.\n",
+            &dates(),
+        );
+        assert_eq!(extracted.title, None);
+        assert!(extracted.date.is_none());
+        assert_eq!(
+            extracted.description.as_deref(),
+            Some("The first paragraph.")
+        );
+    }
+
+    #[test]
+    fn links_in_text_are_a_description() {
+        let extracted = extract("# Title\n\nSee [the docs](/docs).\n", &dates());
+        assert_eq!(extracted.description.as_deref(), Some("See the docs."));
+        let extracted = extract("# Title\n\n[< home](/)\n", &dates());
+        assert_eq!(extracted.description, None);
+    }
+}
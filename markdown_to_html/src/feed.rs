@@ -0,0 +1,458 @@
+//! Atom (`feed.xml`), RSS 2.0 (`rss.xml`) and JSON Feed 1.1 (`feed.json`)
+//! feeds of the latest posts.
+
+use crate::config::Config;
+use crate::date::Date;
+use crate::html::{self, escape};
+use crate::json;
+use crate::post::Post;
+use crate::value::{Table, Value};
+
+pub const ATOM_FILE_NAME: &str = "feed.xml";
+pub const RSS_FILE_NAME: &str = "rss.xml";
+pub const JSON_FILE_NAME: &str = "feed.json";
+
+/// The newest posts, with everything the feed formats have in common worked
+/// out.
+pub struct Feed<'a> {
+    config: &'a Config,
+    base_url: &'a str,
+    entries: Vec<Entry<'a>>,
+}
+
+struct Entry<'a> {
+    post: &'a Post,
+    url: String,
+    published: Date,
+    updated: Date,
+    /// The rendered post with every link made absolute.
+    content: String,
+    image: Option<String>,
+}
+
+impl Entry<'_> {
+    /// The content for the XML feeds, which leave it out when configured to
+    /// carry only summaries and the post has one.
+    fn xml_content<'e>(&'e self, config: &Config) -> Option<&'e str> {
+        (config.feed.full_content || self.post.description.is_none()).then_some(&self.content)
+    }
+}
+
+impl<'a> Feed<'a> {
+    pub fn new(config: &'a Config, base_url: &'a str, posts: &'a [Post]) -> Self {
+        let mut entries: Vec<Entry> = posts
+            .iter()
+            .map(|post| {
+                let url = format!("{base_url}/{}", post.url);
+                let content = html::absolute_urls(&post.content, &url);
+                let image = post
+                    .image
+                    .as_deref()
+                    .map(|image| html::absolute_url(image, &url));
+                Entry {
+                    post,
+                    url,
+                    published: post.date,
+                    updated: post.updated.unwrap_or(post.date),
+                    content,
+                    image,
+                }
+            })
+            .collect();
+        entries.sort_by(|a, b| {
+            b.published
+                .cmp(&a.published)
+                .then_with(|| a.post.title.cmp(&b.post.title))
+        });
+        entries.truncate(config.feed.limit);
+        Feed {
+            config,
+            base_url,
+            entries,
+        }
+    }
+
+    /// When the feed last changed: the latest update of any of its posts.
+    fn updated(&self) -> Option<Date> {
+        self.entries.iter().map(|entry| entry.updated).max()
+    }
+
+    pub fn atom(&self) -> String {
+        let config = self.config;
+        let base_url = self.base_url;
+        let mut out = format!(
+            r#"<?xml version="1.0" encoding="utf-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom" xml:lang="{}">
+  <title>{}</title>
+  <link href="{base_url}/{ATOM_FILE_NAME}" rel="self" type="application/atom+xml" />
+  <link href="{base_url}/" rel="alternate" type="text/html" />
+  <id>{base_url}/</id>
+"#,
+            escape(&config.language),
+            escape(&config.title),
+        );
+        if let Some(updated) = self.updated() {
+            out += &format!("  <updated>{}</updated>\n", updated.rfc3339());
+        }
+        // Atom requires an author for every entry, which entries without one
+        // take from the feed. A site with no author is by its own name.
+        let author = config.author.as_ref().unwrap_or(&config.title);
+        out += &format!("  <author><name>{}</name></author>\n", escape(author));
+        for entry in &self.entries {
+            let post = entry.post;
+            out += &format!(
+                r#"  <entry>
+    <title>{}</title>
+    <link href="{}" rel="alternate" type="text/html" />
+    <id>{}</id>
+    <published>{}</published>
+    <updated>{}</updated>
+"#,
+                escape(&post.title),
+                escape(&entry.url),
+                escape(&entry.url),
+                entry.published.rfc3339(),
+                entry.updated.rfc3339(),
+            );
+            if let Some(author) = post.author.as_ref().or(config.author.as_ref()) {
+                out += &format!("    <author><name>{}</name></author>\n", escape(author));
+            }
+            for tag in &post.tags {
+                out += &format!("    <category term=\"{}\" />\n", escape(tag));
+            }
+            if let Some(description) = &post.description {
+                out += &format!("    <summary>{}</summary>\n", escape(description.trim()));
+            }
+            if let Some(content) = entry.xml_content(config) {
+                out += &format!("    <content type=\"html\">{}</content>\n", escape(content));
+            }
+            out += "  </entry>\n";
+        }
+        out += "</feed>\n";
+        out
+    }
+
+    pub fn rss(&self) -> String {
+        let config = self.config;
+        let base_url = self.base_url;
+        let mut out = format!(
+            r#"<?xml version="1.0" encoding="utf-8"?>
+<rss version="2.0" xmlns:atom="http://www.w3.org/2005/Atom" xmlns:content="http://purl.org/rss/1.0/modules/content/" xmlns:dc="http://purl.org/dc/elements/1.1/">
+  <channel>
+    <title>{}</title>
+    <link>{base_url}/</link>
+    <description>{}</description>
+    <language>{}</language>
+    <atom:link href="{base_url}/{RSS_FILE_NAME}" rel="self" type="application/rss+xml" />
+"#,
+            escape(&config.title),
+            escape(&config.title),
+            escape(&config.language),
+        );
+        if let Some(updated) = self.updated() {
+            out += &format!("    <lastBuildDate>{}</lastBuildDate>\n", updated.rfc2822());
+        }
+        for entry in &self.entries {
+            let post = entry.post;
+            out += &format!(
+                r#"    <item>
+      <title>{}</title>
+      <link>{}</link>
+      <guid isPermaLink="true">{}</guid>
+      <pubDate>{}</pubDate>
+"#,
+                escape(&post.title),
+                escape(&entry.url),
+                escape(&entry.url),
+                entry.published.rfc2822(),
+            );
+            // RSS wants an email address in `<author>`, so names go in
+            // `<dc:creator>` instead.
+            if let Some(author) = post.author.as_ref().or(config.author.as_ref()) {
+                out += &format!("      <dc:creator>{}</dc:creator>\n", escape(author));
+            }
+            for tag in &post.tags {
+                out += &format!("      <category>{}</category>\n", escape(tag));
+            }
+            match (&post.description, entry.xml_content(config)) {
+                (Some(description), _) => {
+                    out += &format!(
+                        "      <description>{}</description>\n",
+                        escape(description.trim())
+                    );
+                }
+                (None, Some(content)) => {
+                    out += &format!("      <description>{}</description>\n", escape(content));
+                }
+                (None, None) => {}
+            }
+            if let Some(content) = entry.xml_content(config) {
+                out += &format!(
+                    "      <content:encoded>{}</content:encoded>\n",
+                    escape(content)
+                );
+            }
+            out += "    </item>\n";
+        }
+        out += "  </channel>\n</rss>\n";
+        out
+    }
+
+    pub fn json(&self) -> String {
+        let config = self.config;
+        let base_url = self.base_url;
+        let string = |s: &str| Value::String(s.to_string());
+        let authors = |author: Option<&String>| {
+            let author = Table::from([("name".into(), string(author?))]);
+            Some(("authors".into(), Value::Array(vec![Value::Table(author)])))
+        };
+        let items = self
+            .entries
+            .iter()
+            .map(|entry| {
+                let post = entry.post;
+                let mut item = Table::from([
+                    ("id".into(), string(&entry.url)),
+                    ("url".into(), string(&entry.url)),
+                    ("title".into(), string(&post.title)),
+                    ("content_html".into(), string(&entry.content)),
+                    ("date_published".into(), string(&entry.published.rfc3339())),
+                    ("date_modified".into(), string(&entry.updated.rfc3339())),
+                ]);
+                if let Some(description) = &post.description {
+                    item.insert("summary".into(), string(description.trim()));
+                }
+                if let Some(image) = &entry.image {
+                    item.insert("image".into(), string(image));
+                }
+                if !post.tags.is_empty() {
+                    let tags = post.tags.iter().map(|tag| string(tag)).collect();
+                    item.insert("tags".into(), Value::Array(tags));
+                }
+                item.extend(authors(post.author.as_ref()));
+                Value::Table(item)
+            })
+            .collect();
+        let mut feed = Table::from([
+            ("version".into(), string("https://jsonfeed.org/version/1.1")),
+            ("title".into(), string(&config.title)),
+            ("home_page_url".into(), string(&format!("{base_url}/"))),
+            (
+                "feed_url".into(),
+                string(&format!("{base_url}/{JSON_FILE_NAME}")),
+            ),
+            ("language".into(), string(&config.language)),
+            ("items".into(), Value::Array(items)),
+        ]);
+        feed.extend(authors(config.author.as_ref()));
+        json::to_string_pretty(&Value::Table(feed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::run::TempDir;
+
+    /// The default configuration with `overrides`.
+    fn load_config(overrides: &[(&str, &str)]) -> Config {
+        let dir = TempDir::new().unwrap();
+        let overrides: Vec<_> = overrides
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        Config::load(&dir.path.join("blog.toml"), &overrides).unwrap()
+    }
+
+    fn post(title: &str, date: &str, url: &str) -> Post {
+        Post {
+            title: title.into(),
+            date: Date::parse_iso(date).unwrap(),
+            updated: None,
+            description: None,
+            author: None,
+            tags: Vec::new(),
+            image: None,
+            url: url.into(),
+            content: String::new(),
+        }
+    }
+
+    fn posts() -> Vec<Post> {
+        vec![
+            post("Old", "2023-01-01", "old.html"),
+            Post {
+                updated: Date::parse_iso("2024-03-01T12:00:00+01:00"),
+                description: Some(" Fish & chips. ".into()),
+                author: Some("Bob".into()),
+                tags: vec!["food".into(), "<uk>".into()],
+                image: Some("cover.png".into()),
+                content: r#"<p><a href="../other.html">x</a> <img src="/img/a.png"> <a href="https://elsewhere.org/">y</a></p>"#.into(),
+                ..post("Tom & Jerry", "2024-02-01", "posts/tom.html")
+            },
+            post("Middle", "2023-06-01", "middle.html"),
+        ]
+    }
+
+    const BASE_URL: &str = "https://example.com/blog";
+
+    #[test]
+    fn atom() {
+        let config = load_config(&[("title", "Cats & Dogs"), ("author", "Ann")]);
+        let posts = posts();
+        let atom = Feed::new(&config, BASE_URL, &posts).atom();
+        assert!(atom.starts_with(
+            r#"<?xml version="1.0" encoding="utf-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom" xml:lang="en">
+  <title>Cats &amp; Dogs</title>
+  <link href="https://example.com/blog/feed.xml" rel="self" type="application/atom+xml" />
+  <link href="https://example.com/blog/" rel="alternate" type="text/html" />
+  <id>https://example.com/blog/</id>
+  <updated>2024-03-01T12:00:00+01:00</updated>
+  <author><name>Ann</name></author>
+  <entry>
+    <title>Tom &amp; Jerry</title>
+    <link href="https://example.com/blog/posts/tom.html" rel="alternate" type="text/html" />
+    <id>https://example.com/blog/posts/tom.html</id>
+    <published>2024-02-01T00:00:00Z</published>
+    <updated>2024-03-01T12:00:00+01:00</updated>
+    <author><name>Bob</name></author>
+    <category term="food" />
+    <category term="&lt;uk&gt;" />
+    <summary>Fish &amp; chips.</summary>
+    <content type="html">&lt;p&gt;&lt;a href=&quot;https://example.com/blog/posts/../other.html&quot;&gt;x&lt;/a&gt; &lt;img src=&quot;https://example.com/img/a.png&quot;&gt; &lt;a href=&quot;https://elsewhere.org/&quot;&gt;y&lt;/a&gt;&lt;/p&gt;</content>
+  </entry>
+  <entry>
+    <title>Middle</title>
+"#
+        ));
+        assert!(atom.ends_with(
+            r#"    <title>Old</title>
+    <link href="https://example.com/blog/old.html" rel="alternate" type="text/html" />
+    <id>https://example.com/blog/old.html</id>
+    <published>2023-01-01T00:00:00Z</published>
+    <updated>2023-01-01T00:00:00Z</updated>
+    <author><name>Ann</name></author>
+    <content type="html"></content>
+  </entry>
+</feed>
+"#
+        ));
+
+        // Without an author, the feed is by the site.
+        let config = load_config(&[("title", "Site")]);
+        let atom = Feed::new(&config, BASE_URL, &posts).atom();
+        assert!(atom.contains("  <author><name>Site</name></author>\n  <entry>"));
+        assert_eq!(atom.matches("<author>").count(), 2);
+    }
+
+    #[test]
+    fn rss() {
+        let config = load_config(&[("author", "Ann"), ("feed.full_content", "false")]);
+        let posts = posts();
+        let rss = Feed::new(&config, BASE_URL, &posts).rss();
+        assert!(rss.contains(
+            r#"    <atom:link href="https://example.com/blog/rss.xml" rel="self" type="application/rss+xml" />
+    <lastBuildDate>Fri, 01 Mar 2024 12:00:00 +0100</lastBuildDate>
+    <item>
+      <title>Tom &amp; Jerry</title>
+      <link>https://example.com/blog/posts/tom.html</link>
+      <guid isPermaLink="true">https://example.com/blog/posts/tom.html</guid>
+      <pubDate>Thu, 01 Feb 2024 00:00:00 +0000</pubDate>
+      <dc:creator>Bob</dc:creator>
+      <category>food</category>
+      <category>&lt;uk&gt;</category>
+      <description>Fish &amp; chips.</description>
+    </item>
+    <item>
+      <title>Middle</title>
+"#
+        ));
+        // Posts without a description carry their content either way.
+        assert!(rss.ends_with(
+            r#"      <dc:creator>Ann</dc:creator>
+      <description></description>
+      <content:encoded></content:encoded>
+    </item>
+  </channel>
+</rss>
+"#
+        ));
+    }
+
+    #[test]
+    fn limit() {
+        let config = load_config(&[("feed.limit", "2")]);
+        let posts = posts();
+        let atom = Feed::new(&config, BASE_URL, &posts).atom();
+        let titles: Vec<_> = atom
+            .match_indices("<title>")
+            .map(|(i, _)| &atom[i + 7..i + 7 + atom[i + 7..].find('<').unwrap()])
+            .collect();
+        assert_eq!(titles, ["Blog", "Tom &amp; Jerry", "Middle"]);
+    }
+
+    #[test]
+    fn json_feed() {
+        let config = load_config(&[("title", "Cats & Dogs"), ("author", "Ann")]);
+        let posts = posts();
+        let feed = json::parse(&Feed::new(&config, BASE_URL, &posts).json()).unwrap();
+        let string = |s: &str| Value::String(s.into());
+        let authors = |name: &str| {
+            Value::Array(vec![Value::Table(Table::from([(
+                "name".into(),
+                string(name),
+            )]))])
+        };
+        let Value::Table(feed) = feed else {
+            panic!("expected a table");
+        };
+        assert_eq!(feed["version"], string("https://jsonfeed.org/version/1.1"));
+        assert_eq!(feed["title"], string("Cats & Dogs"));
+        assert_eq!(feed["home_page_url"], string("https://example.com/blog/"));
+        assert_eq!(
+            feed["feed_url"],
+            string("https://example.com/blog/feed.json")
+        );
+        assert_eq!(feed["authors"], authors("Ann"));
+        let Value::Array(items) = &feed["items"] else {
+            panic!("expected an array");
+        };
+        let item = |i: usize| match &items[i] {
+            Value::Table(item) => item,
+            other => panic!("expected a table, found {other:?}"),
+        };
+        assert_eq!(items.len(), 3);
+        assert_eq!(item(0)["title"], string("Tom & Jerry"));
+        assert_eq!(
+            item(0)["id"],
+            string("https://example.com/blog/posts/tom.html")
+        );
+        assert_eq!(item(0)["url"], item(0)["id"]);
+        assert_eq!(item(0)["date_published"], string("2024-02-01T00:00:00Z"));
+        assert_eq!(
+            item(0)["date_modified"],
+            string("2024-03-01T12:00:00+01:00")
+        );
+        assert_eq!(item(0)["summary"], string("Fish & chips."));
+        assert_eq!(
+            item(0)["tags"],
+            Value::Array(vec![string("food"), string("<uk>")])
+        );
+        assert_eq!(item(0)["authors"], authors("Bob"));
+        assert_eq!(
+            item(0)["image"],
+            string("https://example.com/blog/posts/cover.png")
+        );
+        assert!(matches!(
+            &item(0)["content_html"],
+            Value::String(html) if html.contains(r#"src="https://example.com/img/a.png""#)
+        ));
+        // Items without an author of their own have the feed's.
+        assert_eq!(item(1)["title"], string("Middle"));
+        assert!(!item(1).contains_key("authors"));
+        assert!(!item(1).contains_key("summary"));
+        assert_eq!(item(2)["title"], string("Old"));
+    }
+}
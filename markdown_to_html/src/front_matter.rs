@@ -0,0 +1,271 @@
+//! Page front matter: a `+++` TOML or `---` YAML block at the top of a post.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::config::Dates;
+use crate::date::Date;
+use crate::value::{Table, Value};
+use crate::{toml, yaml};
+
+/// Metadata about a page, taken from its front matter.
+#[derive(Clone, Debug, Default)]
+pub struct PageMeta {
+    pub title: Option<String>,
+    pub date: Option<Date>,
+    pub updated: Option<Date>,
+    pub description: Option<String>,
+    pub tags: Vec<String>,
+    pub draft: bool,
+    /// Keeps the page out of search engines and the sitemap.
+    pub noindex: bool,
+    pub author: Option<String>,
+    pub slug: Option<String>,
+    /// Image representing the page in feeds, relative to the page or the
+    /// site root.
+    pub image: Option<String>,
+    /// Name of the template to render the page with, without `.html`.
+    pub layout: Option<String>,
+    /// Crates the page's Rust code blocks use, written like the
+    /// `[dependencies]` of a `Cargo.toml`, for `check-snippets` and for
+    /// linking paths in inline code to the crates' documentation.
+    pub dependencies: Table,
+    /// Any keys not listed above, for use by templates.
+    pub extra: Table,
+}
+
+/// A front matter block that could not be parsed, with the file and 1-based
+/// line it was found on.
+#[derive(Debug)]
+pub struct Error {
+    pub path: PathBuf,
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.path.display(), self.line, self.message)
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Splits the front matter off `source`, returning the page metadata and the
+/// remaining markdown body. Files without front matter get default metadata.
+/// Dates are read as `dates` configures.
+pub fn parse<'a>(
+    path: &Path,
+    source: &'a str,
+    dates: &Dates,
+) -> Result<(PageMeta, &'a str), Error> {
+    let error = |line, message: String| Error {
+        path: path.to_path_buf(),
+        line,
+        message,
+    };
+    let source = source.strip_prefix('\u{feff}').unwrap_or(source);
+    let Some((fence, after_fence)) = ["+++", "---"].into_iter().find_map(|fence| {
+        let rest = source.strip_prefix(fence)?;
+        let (line, rest) = rest.split_once('\n').unwrap_or((rest, ""));
+        line.trim().is_empty().then_some((fence, rest))
+    }) else {
+        return Ok((PageMeta::default(), source));
+    };
+
+    let mut block_len = None;
+    let mut offset = 0;
+    for line in after_fence.split_inclusive('\n') {
+        if line.trim_end() == fence {
+            block_len = Some(offset);
+            offset += line.len();
+            break;
+        }
+        offset += line.len();
+    }
+    let Some(block_len) = block_len else {
+        return Err(error(
+            1,
+            format!("front matter is missing its closing `{fence}`"),
+        ));
+    };
+    let block = &after_fence[..block_len];
+    let body = &after_fence[offset..];
+
+    // The block starts on the second line of the file.
+    let table = if fence == "+++" {
+        toml::parse(block).map_err(|e| error(e.line + 1, e.message))?
+    } else {
+        match yaml::parse(block).map_err(|e| error(e.line + 1, e.message))? {
+            Value::Table(table) => table,
+            Value::Null => Table::new(),
+            other => {
+                return Err(error(
+                    2,
+                    format!(
+                        "front matter must be a mapping, found {}",
+                        other.type_name()
+                    ),
+                ))
+            }
+        }
+    };
+    let meta = PageMeta::from_table(table, dates)
+        .map_err(|(key, message)| error(key_line(block, &key) + 1, message))?;
+    Ok((meta, body))
+}
+
+impl PageMeta {
+    /// Moves the known keys out of `table` into typed fields. Errors name the
+    /// offending key.
+    fn from_table(mut table: Table, dates: &Dates) -> Result<Self, (String, String)> {
+        let mut string = |key: &str| match table.remove(key) {
+            None | Some(Value::Null) => Ok(None),
+            Some(Value::String(s)) => Ok(Some(s)),
+            Some(other) => Err(type_error(key, "a string", &other)),
+        };
+        let title = string("title")?;
+        let date = string("date")?;
+        let updated = string("updated")?;
+        let date = parse_date("date", date, dates)?;
+        let updated = parse_date("updated", updated, dates)?;
+        let description = string("description")?;
+        let author = string("author")?;
+        let slug = string("slug")?;
+        let image = string("image")?;
+        let layout = string("layout")?;
+        let draft = match table.remove("draft") {
+            None | Some(Value::Null) => false,
+            Some(Value::Boolean(draft)) => draft,
+            Some(other) => return Err(type_error("draft", "a boolean", &other)),
+        };
+        let noindex = match table.remove("noindex") {
+            None | Some(Value::Null) => false,
+            Some(Value::Boolean(noindex)) => noindex,
+            Some(other) => return Err(type_error("noindex", "a boolean", &other)),
+        };
+        let tags = match table.remove("tags") {
+            None | Some(Value::Null) => Vec::new(),
+            Some(Value::String(tag)) => vec![tag],
+            Some(Value::Array(items)) => items
+                .into_iter()
+                .map(|item| match item {
+                    Value::String(tag) => Ok(tag),
+                    other => Err(type_error("tags", "a list of strings", &other)),
+                })
+                .collect::<Result<_, _>>()?,
+            Some(other) => return Err(type_error("tags", "a list of strings", &other)),
+        };
+        let dependencies = match table.remove("dependencies") {
+            None | Some(Value::Null) => Table::new(),
+            Some(Value::Table(dependencies)) => {
+                let invalid = dependencies
+                    .iter()
+                    .find(|(_, spec)| !matches!(spec, Value::String(_) | Value::Table(_)));
+                if let Some((name, spec)) = invalid {
+                    return Err((
+                        "dependencies".to_string(),
+                        format!(
+                            "`dependencies.{name}` must be a version string or a table, found {}",
+                            spec.type_name()
+                        ),
+                    ));
+                }
+                dependencies
+            }
+            Some(other) => return Err(type_error("dependencies", "a table", &other)),
+        };
+        Ok(PageMeta {
+            title,
+            date,
+            updated,
+            description,
+            tags,
+            draft,
+            noindex,
+            author,
+            slug,
+            image,
+            layout,
+            dependencies,
+            extra: table,
+        })
+    }
+}
+
+impl PageMeta {
+    /// The metadata as seen by templates. Extra keys sit alongside the known
+    /// ones, which take precedence.
+    pub fn to_value(&self) -> Table {
+        let optional = |s: &Option<String>| s.clone().map_or(Value::Null, Value::String);
+        let date = |date: &Option<Date>| date.map_or(Value::Null, |date| Value::String(date.iso()));
+        let mut table = self.extra.clone();
+        table.extend([
+            ("title".into(), optional(&self.title)),
+            ("date".into(), date(&self.date)),
+            ("updated".into(), date(&self.updated)),
+            ("description".into(), optional(&self.description)),
+            (
+                "tags".into(),
+                Value::Array(self.tags.iter().cloned().map(Value::String).collect()),
+            ),
+            ("draft".into(), Value::Boolean(self.draft)),
+            ("noindex".into(), Value::Boolean(self.noindex)),
+            ("author".into(), optional(&self.author)),
+            ("slug".into(), optional(&self.slug)),
+            ("image".into(), optional(&self.image)),
+            ("layout".into(), optional(&self.layout)),
+            (
+                "dependencies".into(),
+                Value::Table(self.dependencies.clone()),
+            ),
+            ("extra".into(), Value::Table(self.extra.clone())),
+        ]);
+        table
+    }
+}
+
+/// Parses the date `text` given for `key`, if any.
+fn parse_date(
+    key: &str,
+    text: Option<String>,
+    dates: &Dates,
+) -> Result<Option<Date>, (String, String)> {
+    let Some(text) = text else {
+        return Ok(None);
+    };
+    match Date::parse(&text, dates) {
+        Some(date) => Ok(Some(date)),
+        None => Err((
+            key.to_string(),
+            match &dates.input {
+                Some(input) => format!(
+                    "`{key}` must be an ISO-8601 date such as `2024-01-11` or in the \
+                     `dates.input` format `{input}`, found `{text}`"
+                ),
+                None => format!(
+                    "`{key}` must be an ISO-8601 date such as `2024-01-11`, with an optional \
+                     time, found `{text}`"
+                ),
+            },
+        )),
+    }
+}
+
+fn type_error(key: &str, expected: &str, found: &Value) -> (String, String) {
+    (
+        key.to_string(),
+        format!("`{key}` must be {expected}, found {}", found.type_name()),
+    )
+}
+
+/// The 1-based line within `block` where top-level `key` is defined.
+fn key_line(block: &str, key: &str) -> usize {
+    block
+        .lines()
+        .position(|line| {
+            line.strip_prefix(key)
+                .is_some_and(|rest| rest.trim_start().starts_with(['=', ':']))
+        })
+        .map_or(1, |index| index + 1)
+}
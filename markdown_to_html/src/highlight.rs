@@ -0,0 +1,836 @@
+//! Syntax highlighting for code blocks, done while building.
+//!
+//! Code in the languages the posts use is split into tokens by small
+//! hand-written lexers, and each token is wrapped in a `<span>` with an `hl-`
+//! class. The colors for those classes are in the stylesheet of `base.html`,
+//! with a light and a dark variant, so pages need no script and follow the
+//! reader's color scheme like the rest of the page.
+
+use crate::html::escape;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Token {
+    Comment,
+    Keyword,
+    String,
+    Number,
+    Constant,
+    Type,
+    Function,
+    Macro,
+    Attribute,
+    Variable,
+    Property,
+    Lifetime,
+    /// The `$ ` before a command in a terminal session.
+    Prompt,
+}
+
+impl Token {
+    fn class(self) -> &'static str {
+        match self {
+            Token::Comment => "hl-comment",
+            Token::Keyword => "hl-keyword",
+            Token::String => "hl-string",
+            Token::Number => "hl-number",
+            Token::Constant => "hl-constant",
+            Token::Type => "hl-type",
+            Token::Function => "hl-function",
+            Token::Macro => "hl-macro",
+            Token::Attribute => "hl-attribute",
+            Token::Variable => "hl-variable",
+            Token::Property => "hl-property",
+            Token::Lifetime => "hl-lifetime",
+            Token::Prompt => "hl-prompt",
+        }
+    }
+}
+
+/// Highlights `code` written in `language` into HTML, or returns `None` if
+/// the language is not supported.
+pub fn highlight(language: &str, code: &str) -> Option<String> {
+    let lex: fn(&mut Lexer) = match language.to_ascii_lowercase().as_str() {
+        "rust" | "rs" => rust,
+        "toml" => toml,
+        "yaml" | "yml" => yaml,
+        "sh" | "bash" | "shell" | "zsh" => shell,
+        "console" | "shell-session" => console,
+        "json" => json,
+        _ => return None,
+    };
+    let mut lexer = Lexer::new(code);
+    lex(&mut lexer);
+    Some(lexer.out)
+}
+
+struct Lexer<'a> {
+    src: &'a str,
+    pos: usize,
+    out: String,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(src: &'a str) -> Self {
+        Lexer {
+            src,
+            pos: 0,
+            out: String::with_capacity(src.len() * 2),
+        }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.src[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    /// Writes the next `len` bytes as `token`, or as plain text if `None`.
+    /// Spans are closed at the end of every line, so that each line of the
+    /// output stands on its own.
+    fn emit(&mut self, token: Option<Token>, len: usize) {
+        let text = &self.src[self.pos..self.pos + len];
+        self.pos += len;
+        let Some(token) = token else {
+            self.out.push_str(&escape(text));
+            return;
+        };
+        for (i, line) in text.split('\n').enumerate() {
+            if i > 0 {
+                self.out.push('\n');
+            }
+            if !line.is_empty() {
+                self.out += &format!("<span class=\"{}\">{}</span>", token.class(), escape(line));
+            }
+        }
+    }
+
+    /// Writes the next character as plain text.
+    fn skip(&mut self) {
+        let len = self.peek().map_or(0, char::len_utf8);
+        self.emit(None, len);
+    }
+
+    /// Highlights the next `len` bytes with another lexer.
+    fn nested(&mut self, len: usize, lex: fn(&mut Lexer)) {
+        let mut nested = Lexer::new(&self.src[self.pos..self.pos + len]);
+        lex(&mut nested);
+        self.out += &nested.out;
+        self.pos += len;
+    }
+
+    /// Whether the next character starts a word rather than continuing one.
+    fn at_word_start(&self) -> bool {
+        self.src[..self.pos]
+            .chars()
+            .next_back()
+            .is_none_or(char::is_whitespace)
+    }
+}
+
+const RUST_KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern",
+    "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref",
+    "return", "self", "static", "struct", "super", "trait", "type", "unsafe", "use", "where",
+    "while", "yield",
+];
+
+const RUST_PRIMITIVES: &[&str] = &[
+    "bool", "char", "str", "u8", "u16", "u32", "u64", "u128", "usize", "i8", "i16", "i32", "i64",
+    "i128", "isize", "f32", "f64",
+];
+
+fn rust(lexer: &mut Lexer) {
+    let mut after_fn = false;
+    while let Some(c) = lexer.peek() {
+        let rest = lexer.rest();
+        if rest.starts_with("//") {
+            lexer.emit(Some(Token::Comment), line_len(rest));
+        } else if rest.starts_with("/*") {
+            lexer.emit(Some(Token::Comment), block_comment_len(rest));
+        } else if rest.starts_with("#[") || rest.starts_with("#![") {
+            lexer.emit(Some(Token::Attribute), attribute_len(rest));
+        } else if let Some(len) = rust_string_len(rest) {
+            lexer.emit(Some(Token::String), len);
+        } else if c == '\'' {
+            match char_len(rest) {
+                Some(len) => lexer.emit(Some(Token::String), len),
+                None if rest[1..].starts_with(is_ident_start) => {
+                    let len = 1 + word_len(&rest[1..], is_ident_part);
+                    lexer.emit(Some(Token::Lifetime), len);
+                }
+                None => lexer.skip(),
+            }
+        } else if c.is_ascii_digit() {
+            lexer.emit(Some(Token::Number), number_len(rest));
+        } else if is_ident_start(c) {
+            let len = word_len(rest, is_ident_part);
+            let word = &rest[..len];
+            let after = &rest[len..];
+            if after.starts_with('!') && !after.starts_with("!=") && !RUST_KEYWORDS.contains(&word)
+            {
+                lexer.emit(Some(Token::Macro), len + 1);
+            } else {
+                let token = if RUST_KEYWORDS.contains(&word) {
+                    Some(Token::Keyword)
+                } else if word == "true" || word == "false" {
+                    Some(Token::Constant)
+                } else if after_fn {
+                    Some(Token::Function)
+                } else if RUST_PRIMITIVES.contains(&word) {
+                    Some(Token::Type)
+                } else if c.is_uppercase() {
+                    let constant = len > 1
+                        && word
+                            .chars()
+                            .all(|c| c.is_uppercase() || c.is_ascii_digit() || c == '_');
+                    Some(if constant {
+                        Token::Constant
+                    } else {
+                        Token::Type
+                    })
+                } else if after.starts_with('(') || after.starts_with("::<") {
+                    Some(Token::Function)
+                } else {
+                    None
+                };
+                lexer.emit(token, len);
+            }
+            after_fn = word == "fn";
+        } else {
+            lexer.skip();
+        }
+    }
+}
+
+/// Length of the string literal at the start of `rest`, with any `b`, `c` or
+/// `r` prefix, or `None` if there is none.
+fn rust_string_len(rest: &str) -> Option<usize> {
+    for prefix in ["br", "cr", "b", "c", "r", ""] {
+        let Some(after) = rest.strip_prefix(prefix) else {
+            continue;
+        };
+        if prefix.ends_with('r') {
+            let hashes = after.len() - after.trim_start_matches('#').len();
+            if after[hashes..].starts_with('"') {
+                let closing = format!("\"{}", "#".repeat(hashes));
+                let body = &after[hashes + 1..];
+                let len = body
+                    .find(&closing)
+                    .map_or(body.len(), |end| end + closing.len());
+                return Some(prefix.len() + hashes + 1 + len);
+            }
+        } else if after.starts_with('"') {
+            return Some(prefix.len() + quoted_len(after, "\"", true, true));
+        } else if prefix == "b" && after.starts_with('\'') {
+            if let Some(len) = char_len(after) {
+                return Some(1 + len);
+            }
+        }
+    }
+    None
+}
+
+/// Length of the character literal at the start of `rest`, or `None` if the
+/// quote starts a lifetime instead.
+fn char_len(rest: &str) -> Option<usize> {
+    let first = rest[1..].chars().next()?;
+    if first == '\\' {
+        let escaped = rest[2..].chars().next()?;
+        let start = 2 + escaped.len_utf8();
+        let end = rest[start..].find(['\'', '\n'])?;
+        return rest[start + end..]
+            .starts_with('\'')
+            .then_some(start + end + 1);
+    }
+    let len = 1 + first.len_utf8();
+    rest[len..].starts_with('\'').then_some(len + 1)
+}
+
+/// Length of the block comment at the start of `rest`, which may contain
+/// other block comments.
+fn block_comment_len(rest: &str) -> usize {
+    let mut depth = 0;
+    let mut i = 0;
+    while i < rest.len() {
+        if rest[i..].starts_with("/*") {
+            depth += 1;
+            i += 2;
+        } else if rest[i..].starts_with("*/") {
+            depth -= 1;
+            i += 2;
+            if depth == 0 {
+                return i;
+            }
+        } else {
+            i += rest[i..].chars().next().map_or(1, char::len_utf8);
+        }
+    }
+    rest.len()
+}
+
+/// Length of the `#[...]` or `#![...]` attribute at the start of `rest`.
+fn attribute_len(rest: &str) -> usize {
+    let mut depth = 0;
+    for (i, c) in rest.char_indices() {
+        match c {
+            '[' => depth += 1,
+            ']' => {
+                depth -= 1;
+                if depth == 0 {
+                    return i + 1;
+                }
+            }
+            '\n' => return i,
+            _ => {}
+        }
+    }
+    rest.len()
+}
+
+fn is_ident_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_'
+}
+
+fn is_ident_part(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn toml(lexer: &mut Lexer) {
+    // The `[` and `{` the current value is nested in. Keys are expected at
+    // the start of a line outside of them and after `{` or `,` in an inline
+    // table.
+    let mut nesting = Vec::new();
+    let mut expect_key = true;
+    while let Some(c) = lexer.peek() {
+        let rest = lexer.rest();
+        if c == '\n' {
+            expect_key |= nesting.is_empty();
+            lexer.skip();
+        } else if c.is_whitespace() {
+            lexer.skip();
+        } else if c == '#' {
+            lexer.emit(Some(Token::Comment), line_len(rest));
+        } else if expect_key && nesting.is_empty() && c == '[' {
+            let header = &rest[..line_len(rest)];
+            let len = match header.find(']') {
+                Some(end) if header[end + 1..].starts_with(']') => end + 2,
+                Some(end) => end + 1,
+                None => header.len(),
+            };
+            lexer.emit(Some(Token::Type), len);
+            expect_key = false;
+        } else if expect_key && (c == '"' || c == '\'' || is_bare_key(c)) {
+            lexer.emit(Some(Token::Property), toml_key_len(rest));
+            expect_key = false;
+        } else if rest.starts_with("\"\"\"") {
+            lexer.emit(Some(Token::String), quoted_len(rest, "\"\"\"", true, true));
+        } else if rest.starts_with("'''") {
+            lexer.emit(Some(Token::String), quoted_len(rest, "'''", false, true));
+        } else if c == '"' {
+            lexer.emit(Some(Token::String), quoted_len(rest, "\"", true, false));
+        } else if c == '\'' {
+            lexer.emit(Some(Token::String), quoted_len(rest, "'", false, false));
+        } else if c == '[' || c == '{' {
+            nesting.push(c);
+            expect_key = c == '{';
+            lexer.skip();
+        } else if c == ']' || c == '}' {
+            nesting.pop();
+            lexer.skip();
+        } else if c == ',' {
+            expect_key = nesting.last() == Some(&'{');
+            lexer.skip();
+        } else if c.is_ascii_digit() || (matches!(c, '+' | '-') && rest[1..].starts_with(is_digit))
+        {
+            let len = 1 + word_len(&rest[1..], |c| {
+                c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | ':' | '+' | '-')
+            });
+            lexer.emit(Some(Token::Number), len);
+        } else if c.is_ascii_alphabetic() {
+            let len = word_len(rest, is_bare_key);
+            let constant = matches!(&rest[..len], "true" | "false" | "inf" | "nan");
+            lexer.emit(constant.then_some(Token::Constant), len);
+        } else {
+            lexer.skip();
+        }
+    }
+}
+
+/// Length of the possibly dotted key at the start of `rest`.
+fn toml_key_len(rest: &str) -> usize {
+    let mut len = 0;
+    loop {
+        let part = &rest[len..];
+        len += match part.chars().next() {
+            Some('"') => quoted_len(part, "\"", true, false),
+            Some('\'') => quoted_len(part, "'", false, false),
+            _ => word_len(part, is_bare_key),
+        };
+        let after = rest[len..].trim_start_matches([' ', '\t']);
+        match after.strip_prefix('.') {
+            Some(next) => len = rest.len() - next.trim_start_matches([' ', '\t']).len(),
+            None => return len,
+        }
+    }
+}
+
+fn is_bare_key(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '-'
+}
+
+fn yaml(lexer: &mut Lexer) {
+    // The indentation of the key whose value is the block scalar being read.
+    let mut block_scalar = None;
+    while !lexer.rest().is_empty() {
+        let rest = lexer.rest();
+        let line = &rest[..line_len(rest)];
+        let indent = line.len() - line.trim_start_matches(' ').len();
+        match block_scalar {
+            Some(parent) if line.trim().is_empty() || indent > parent => {
+                lexer.emit(None, indent);
+                lexer.emit(Some(Token::String), line.len() - indent);
+            }
+            _ => {
+                let key_indent = line.len() - line.trim_start_matches([' ', '-']).len();
+                block_scalar = yaml_line(lexer, line.len()).then_some(key_indent);
+            }
+        }
+        if lexer.peek() == Some('\n') {
+            lexer.skip();
+        }
+    }
+}
+
+/// Highlights the next `len` bytes, one line of YAML. Returns whether the line
+/// ends with the indicator of a block scalar, whose content is on the more
+/// indented lines that follow.
+fn yaml_line(lexer: &mut Lexer, len: usize) -> bool {
+    let end = lexer.pos + len;
+    let line_start = lexer.pos;
+    // The `[` and `{` of the flow collections the current value is in.
+    let mut nesting = Vec::new();
+    let mut expect_key = true;
+    let mut block_scalar = false;
+    while lexer.pos < end {
+        let rest = &lexer.src[lexer.pos..end];
+        let c = rest.chars().next().unwrap_or_default();
+        let flow = !nesting.is_empty();
+        if c == ' ' || c == '\t' {
+            lexer.skip();
+        } else if c == '#' && lexer.at_word_start() {
+            lexer.emit(Some(Token::Comment), rest.len());
+        } else if lexer.pos == line_start
+            && (rest.starts_with("---") || rest.starts_with("..."))
+            && rest[3..].trim().is_empty()
+        {
+            lexer.emit(Some(Token::Keyword), 3);
+        } else if matches!(c, '-' | '?') && !flow && (rest.len() == 1 || rest[1..].starts_with(' '))
+        {
+            lexer.skip();
+        } else if let Some(len) = expect_key.then(|| yaml_key_len(rest, flow)).flatten() {
+            lexer.emit(Some(Token::Property), len);
+            expect_key = false;
+        } else if c == '"' {
+            lexer.emit(Some(Token::String), quoted_len(rest, "\"", true, false));
+        } else if c == '\'' {
+            lexer.emit(Some(Token::String), quoted_len(rest, "'", false, false));
+        } else if matches!(c, '&' | '*' | '!') {
+            let token = if c == '!' {
+                Token::Type
+            } else {
+                Token::Variable
+            };
+            let len = 1 + word_len(&rest[1..], |c| {
+                !c.is_whitespace() && !matches!(c, ',' | '[' | ']' | '{' | '}')
+            });
+            lexer.emit(Some(token), len);
+        } else if matches!(c, '|' | '>') && !flow && is_block_indicator(&rest[1..]) {
+            lexer.emit(Some(Token::Keyword), 1);
+            block_scalar = true;
+        } else if c == '[' || c == '{' {
+            nesting.push(c);
+            expect_key = c == '{';
+            lexer.skip();
+        } else if c == ']' || c == '}' {
+            nesting.pop();
+            lexer.skip();
+        } else if c == ',' {
+            expect_key = nesting.last() == Some(&'{');
+            lexer.skip();
+        } else if c == ':' {
+            expect_key = false;
+            lexer.skip();
+        } else {
+            let len = yaml_scalar_len(rest, flow);
+            let scalar = &rest[..len];
+            let token = if matches!(
+                scalar.trim_end(),
+                "true"
+                    | "True"
+                    | "TRUE"
+                    | "false"
+                    | "False"
+                    | "FALSE"
+                    | "null"
+                    | "Null"
+                    | "NULL"
+                    | "~"
+                    | "yes"
+                    | "no"
+                    | "on"
+                    | "off"
+            ) {
+                Some(Token::Constant)
+            } else if scalar
+                .starts_with(|c: char| c.is_ascii_digit() || matches!(c, '-' | '+' | '.'))
+                && scalar.trim_end().parse::<f64>().is_ok()
+            {
+                Some(Token::Number)
+            } else {
+                None
+            };
+            lexer.emit(token, len);
+        }
+    }
+    block_scalar
+}
+
+/// Whether `rest`, which follows a `|` or `>`, makes it the start of a block
+/// scalar: only chomping and indentation indicators and a comment may follow.
+fn is_block_indicator(rest: &str) -> bool {
+    let rest = rest.trim_start_matches(|c: char| matches!(c, '-' | '+') || c.is_ascii_digit());
+    rest.trim().is_empty() || rest.starts_with([' ', '\t']) && rest.trim_start().starts_with('#')
+}
+
+/// Length of the key at the start of `rest` if it is followed by `:` and a
+/// space or the end of the line.
+fn yaml_key_len(rest: &str, flow: bool) -> Option<usize> {
+    let len = match rest.chars().next()? {
+        '"' => quoted_len(rest, "\"", true, false),
+        '\'' => quoted_len(rest, "'", false, false),
+        _ => yaml_scalar_len(rest, flow),
+    };
+    let key = rest[..len].trim_end();
+    let after = &rest[key.len()..];
+    let is_key =
+        after.starts_with(':') && (after.len() == 1 || after[1..].starts_with([' ', '\t']));
+    (is_key && !key.is_empty()).then_some(key.len())
+}
+
+/// Length of the plain scalar at the start of `rest`, which ends at a comment,
+/// at `: ` and, in a flow collection, at `,`, `]` or `}`.
+fn yaml_scalar_len(rest: &str, flow: bool) -> usize {
+    let mut previous = ' ';
+    for (i, c) in rest.char_indices() {
+        let after = &rest[i + c.len_utf8()..];
+        let ends = (c == '#' && previous.is_whitespace() && i > 0)
+            || (c == ':' && (after.is_empty() || after.starts_with([' ', '\t'])))
+            || (flow && matches!(c, ',' | ']' | '}'));
+        if ends {
+            return i;
+        }
+        previous = c;
+    }
+    rest.len()
+}
+
+const SHELL_KEYWORDS: &[&str] = &[
+    "if", "then", "else", "elif", "fi", "for", "while", "until", "do", "done", "case", "esac",
+    "in", "function", "select", "time",
+];
+
+fn shell(lexer: &mut Lexer) {
+    // Whether the next word is in command position, where it names a command
+    // or is a keyword or an assignment.
+    let mut command = true;
+    // Whether an `in` would be the keyword of a `for`, `case` or `select`.
+    let mut expect_in = false;
+    while let Some(c) = lexer.peek() {
+        let rest = lexer.rest();
+        match c {
+            '\n' => {
+                expect_in = false;
+                command |= !lexer.src[..lexer.pos].ends_with('\\');
+                lexer.skip();
+            }
+            ' ' | '\t' => lexer.skip(),
+            '#' if lexer.at_word_start() => lexer.emit(Some(Token::Comment), line_len(rest)),
+            '\'' => {
+                lexer.emit(Some(Token::String), quoted_len(rest, "'", false, true));
+                command = false;
+            }
+            '"' => {
+                shell_double_quoted(lexer);
+                command = false;
+            }
+            '$' => {
+                let len = variable_len(rest);
+                lexer.emit((len > 1).then_some(Token::Variable), len);
+                command = false;
+            }
+            ';' | '|' | '&' | '(' | '`' => {
+                lexer.skip();
+                command = true;
+                expect_in = false;
+            }
+            ')' | '<' | '>' => lexer.skip(),
+            _ => {
+                let len = word_len(rest, |c| !c.is_whitespace() && !";|&()<>\"'$`".contains(c));
+                let word = &rest[..len];
+                if expect_in && word == "in" {
+                    lexer.emit(Some(Token::Keyword), len);
+                    command = false;
+                    expect_in = false;
+                } else if !command {
+                    lexer.emit(None, len);
+                } else if SHELL_KEYWORDS.contains(&word) {
+                    lexer.emit(Some(Token::Keyword), len);
+                    command = !matches!(word, "for" | "case" | "select" | "function" | "in");
+                    expect_in = matches!(word, "for" | "case" | "select");
+                } else if let Some(name) = word
+                    .split_once('=')
+                    .map(|(name, _)| name)
+                    .filter(|name| !name.is_empty() && name.chars().all(is_ident_part))
+                {
+                    lexer.emit(Some(Token::Variable), name.len());
+                    lexer.emit(None, len - name.len());
+                } else {
+                    lexer.emit(Some(Token::Function), len);
+                    command = false;
+                }
+            }
+        }
+    }
+}
+
+/// Highlights the double-quoted string at the start of the rest, with the
+/// variables expanded in it.
+fn shell_double_quoted(lexer: &mut Lexer) {
+    let len = quoted_len(lexer.rest(), "\"", true, true);
+    let text = &lexer.rest()[..len];
+    let mut i = 0;
+    let mut emitted = 0;
+    while i < len {
+        match text.as_bytes()[i] {
+            b'\\' => i += 2,
+            b'$' if variable_len(&text[i..]) > 1 => {
+                lexer.emit(Some(Token::String), i - emitted);
+                let variable = variable_len(&text[i..]).min(len - 1 - i);
+                lexer.emit(Some(Token::Variable), variable);
+                i += variable;
+                emitted = i;
+            }
+            _ => i += 1,
+        }
+    }
+    lexer.emit(Some(Token::String), len - emitted);
+}
+
+/// Length of the expansion at the start of `rest`, such as `$HOME` or
+/// `${HOME}`, or 1 if the `$` does not start one.
+fn variable_len(rest: &str) -> usize {
+    let after = &rest[1..];
+    match after.chars().next() {
+        Some('{') => after.find('}').map_or(1, |end| end + 2),
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+            1 + word_len(after, |c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        Some(c) if c.is_ascii_digit() || "@*#?$!-".contains(c) => 2,
+        _ => 1,
+    }
+}
+
+/// A terminal session: commands after a `$ ` prompt, and their output.
+fn console(lexer: &mut Lexer) {
+    while !lexer.rest().is_empty() {
+        let rest = lexer.rest();
+        let line = &rest[..line_len(rest)];
+        match line.strip_prefix("$ ") {
+            Some(command) => {
+                lexer.emit(Some(Token::Prompt), 2);
+                lexer.nested(command.len(), shell);
+            }
+            None => lexer.emit(None, line.len()),
+        }
+        if lexer.peek() == Some('\n') {
+            lexer.skip();
+        }
+    }
+}
+
+fn json(lexer: &mut Lexer) {
+    while let Some(c) = lexer.peek() {
+        let rest = lexer.rest();
+        if c == '"' {
+            let len = quoted_len(rest, "\"", true, false);
+            let key = rest[len..].trim_start().starts_with(':');
+            let token = if key { Token::Property } else { Token::String };
+            lexer.emit(Some(token), len);
+        } else if c.is_ascii_digit() || (c == '-' && rest[1..].starts_with(is_digit)) {
+            lexer.emit(Some(Token::Number), 1 + number_len(&rest[1..]));
+        } else if c.is_ascii_alphabetic() {
+            let len = word_len(rest, |c| c.is_ascii_alphabetic());
+            let constant = matches!(&rest[..len], "true" | "false" | "null");
+            lexer.emit(constant.then_some(Token::Constant), len);
+        } else {
+            lexer.skip();
+        }
+    }
+}
+
+fn line_len(rest: &str) -> usize {
+    rest.find('\n').unwrap_or(rest.len())
+}
+
+fn word_len(rest: &str, part: impl Fn(char) -> bool) -> usize {
+    rest.find(|c| !part(c)).unwrap_or(rest.len())
+}
+
+fn is_digit(c: char) -> bool {
+    c.is_ascii_digit()
+}
+
+/// Length of the string that `quote` starts at the start of `rest`, up to
+/// and including the closing quote. Unclosed strings run to the end of the
+/// code, or of the line unless they may span lines.
+fn quoted_len(rest: &str, quote: &str, escapes: bool, multiline: bool) -> usize {
+    let mut i = quote.len();
+    while let Some(c) = rest[i..].chars().next() {
+        if rest[i..].starts_with(quote) {
+            return i + quote.len();
+        }
+        if c == '\n' && !multiline {
+            return i;
+        }
+        i += c.len_utf8();
+        if escapes && c == '\\' {
+            i += rest[i..].chars().next().map_or(0, char::len_utf8);
+        }
+    }
+    rest.len()
+}
+
+/// Length of the number at the start of `rest`, with its suffix.
+fn number_len(rest: &str) -> usize {
+    let bytes = rest.as_bytes();
+    let mut len = 0;
+    while let Some(&byte) = bytes.get(len) {
+        let exponent_sign = matches!(byte, b'+' | b'-')
+            && len > 0
+            && matches!(bytes[len - 1], b'e' | b'E')
+            && !rest.starts_with("0x");
+        let fraction = byte == b'.' && bytes.get(len + 1).is_some_and(u8::is_ascii_digit);
+        if byte.is_ascii_alphanumeric() || byte == b'_' || exponent_sign || fraction {
+            len += 1;
+        } else {
+            break;
+        }
+    }
+    len
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The highlighted code with each span written `[class text]`, which is
+    /// easier to read in assertions than the HTML.
+    fn spans(language: &str, code: &str) -> String {
+        let html = highlight(language, code).unwrap();
+        let mut out = String::new();
+        let mut rest = html.as_str();
+        while let Some(start) = rest.find("<span class=\"hl-") {
+            out += &rest[..start];
+            let after = &rest[start + "<span class=\"hl-".len()..];
+            let (class, after) = after.split_once("\">").unwrap();
+            let (text, after) = after.split_once("</span>").unwrap();
+            out += &format!("[{class} {text}]");
+            rest = after;
+        }
+        out + rest
+    }
+
+    #[test]
+    fn unsupported_languages() {
+        assert_eq!(highlight("cobol", "DISPLAY 'HI'."), None);
+        assert!(highlight("Rust", "fn").is_some());
+    }
+
+    #[test]
+    fn rust() {
+        assert_eq!(
+            spans("rust", "#[derive(Debug)]\npub fn main() -> Result<(), E> {\n    let x: u8 = 0x1F_u8; // hex\n    println!(\"{x}\\n\");\n}"),
+            "[attribute #[derive(Debug)]]\n[keyword pub] [keyword fn] [function main]() -&gt; [type Result]&lt;(), [type E]&gt; {\n    [keyword let] x: [type u8] = [number 0x1F_u8]; [comment // hex]\n    [macro println!]([string &quot;{x}\\n&quot;]);\n}"
+        );
+        assert_eq!(
+            spans("rust", "fn f<'a>(s: &'a str) -> char { 'x' }"),
+            "[keyword fn] [function f]&lt;[lifetime &#39;a]&gt;(s: &amp;[lifetime &#39;a] [type str]) -&gt; [type char] { [string &#39;x&#39;] }"
+        );
+        assert_eq!(
+            spans("rust", "r#\"a \"b\"\"# b'\\n' MAX_LEN true 1.5e-3 x.len() /* a /* b */ c */"),
+            "[string r#&quot;a &quot;b&quot;&quot;#] [string b&#39;\\n&#39;] [constant MAX_LEN] [constant true] [number 1.5e-3] x.[function len]() [comment /* a /* b */ c */]"
+        );
+        assert_eq!(
+            spans("rust", "/* one\ntwo */ if a != b {}"),
+            "[comment /* one]\n[comment two */] [keyword if] a != b {}"
+        );
+    }
+
+    #[test]
+    fn toml() {
+        assert_eq!(
+            spans("toml", "# site\n[package]\nname = \"blog\" # name\n\"quoted key\".x = 'raw'\n[[bin]]\nflags = [true, -1_000, 1979-05-27T07:32:00Z, inf]\ndeps = { serde = { version = \"1\" }, b = 2 }"),
+            "[comment # site]\n[type [package]]\n[property name] = [string &quot;blog&quot;] [comment # name]\n[property &quot;quoted key&quot;.x] = [string &#39;raw&#39;]\n[type [[bin]]]\n[property flags] = [[constant true], [number -1_000], [number 1979-05-27T07:32:00Z], [constant inf]]\n[property deps] = { [property serde] = { [property version] = [string &quot;1&quot;] }, [property b] = [number 2] }"
+        );
+        assert_eq!(
+            spans("toml", "text = \"\"\"\none\n\"\"\""),
+            "[property text] = [string &quot;&quot;&quot;]\n[string one]\n[string &quot;&quot;&quot;]"
+        );
+    }
+
+    #[test]
+    fn yaml() {
+        assert_eq!(
+            spans("yaml", "---\nname: Build # CI\non: [push, pull_request]\njobs:\n  - key: &anchor 1.5\n    other: *anchor\n    tag: !!str yes\n    \"quoted\": 'single'\n    flow: {a: null, b: ~}\n..."),
+            "[keyword ---]\n[property name]: Build [comment # CI]\n[property on]: [push, pull_request]\n[property jobs]:\n  - [property key]: [variable &amp;anchor] [number 1.5]\n    [property other]: [variable *anchor]\n    [property tag]: [type !!str] [constant yes]\n    [property &quot;quoted&quot;]: [string &#39;single&#39;]\n    [property flow]: {[property a]: [constant null], [property b]: [constant ~]}\n[keyword ...]"
+        );
+        assert_eq!(
+            spans("yaml", "run: |\n  cargo test\n\n  # not a comment\nnext: a#b"),
+            "[property run]: [keyword |]\n  [string cargo test]\n\n  [string # not a comment]\n[property next]: a#b"
+        );
+    }
+
+    #[test]
+    fn shell() {
+        assert_eq!(
+            spans("sh", "# build\nRUST_LOG=debug cargo run --release | tee \"$HOME/${LOG}.txt\" 'out'\nif [ -n \"$1\" ]; then echo $? ; fi"),
+            "[comment # build]\n[variable RUST_LOG]=debug [function cargo] run --release | [function tee] [string &quot;][variable $HOME][string /][variable ${LOG}][string .txt&quot;] [string &#39;out&#39;]\n[keyword if] [function [] -n [string &quot;][variable $1][string &quot;] ]; [keyword then] [function echo] [variable $?] ; [keyword fi]"
+        );
+        assert_eq!(
+            spans("bash", "for f in *.md; do\n  wc -l \\\n    $f # count\ndone"),
+            "[keyword for] f [keyword in] *.md; [keyword do]\n  [function wc] -l \\\n    [variable $f] [comment # count]\n[keyword done]"
+        );
+        assert_eq!(
+            spans("sh", "case $x in\nesac\necho in"),
+            "[keyword case] [variable $x] [keyword in]\n[keyword esac]\n[function echo] in"
+        );
+    }
+
+    #[test]
+    fn console() {
+        assert_eq!(
+            spans("console", "$ cargo build\n   Compiling blog v0.1.0\n$ echo \"$PATH\"\n$PATH"),
+            "[prompt $ ][function cargo] build\n   Compiling blog v0.1.0\n[prompt $ ][function echo] [string &quot;][variable $PATH][string &quot;]\n$PATH"
+        );
+    }
+
+    #[test]
+    fn json() {
+        assert_eq!(
+            spans("json", "{\"name\": \"blog\", \"tags\": [\"a\\\"b\"], \"n\": -1.5e3, \"ok\": true, \"none\": null}"),
+            "{[property &quot;name&quot;]: [string &quot;blog&quot;], [property &quot;tags&quot;]: [[string &quot;a\\&quot;b&quot;]], [property &quot;n&quot;]: [number -1.5e3], [property &quot;ok&quot;]: [constant true], [property &quot;none&quot;]: [constant null]}"
+        );
+    }
+}
@@ -0,0 +1,113 @@
+//! Helpers for writing HTML by hand.
+
+/// Escapes text for use in HTML element content and quoted attributes.
+pub fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Undoes the entity escaping the markdown renderer applies to text and
+/// attributes.
+pub fn unescape(text: &str) -> String {
+    text.replace("&quot;", "\"")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+/// Prefixes the root-relative `href` and `src` URLs in `html` with
+/// `base_path`, so that `/` in a post points at the root of the site.
+pub fn prefix_root_urls(html: &str, base_path: &str) -> String {
+    if base_path.is_empty() {
+        return html.to_string();
+    }
+    rewrite_urls(html, |url| {
+        (url.starts_with('/') && !url.starts_with("//")).then(|| format!("{base_path}{url}"))
+    })
+}
+
+/// Prefixes the relative `href` and `src` URLs in `html` with `prefix`, for a
+/// page written somewhere other than next to its source.
+pub fn relocate_relative_urls(html: &str, prefix: &str) -> String {
+    if prefix.is_empty() {
+        return html.to_string();
+    }
+    rewrite_urls(html, |url| {
+        is_relative(url).then(|| format!("{prefix}{url}"))
+    })
+}
+
+/// Whether `url` is relative to the page it appears on, as opposed to
+/// absolute, root-relative or a fragment.
+pub fn is_relative(url: &str) -> bool {
+    !url.is_empty()
+        && !url.starts_with(['/', '#', '?'])
+        && !url
+            .split(['/', '?', '#'])
+            .next()
+            .unwrap_or_default()
+            .contains(':')
+}
+
+/// Resolves the relative `href` and `src` URLs in `html` against the absolute
+/// URL of the page it appears on, for use outside the site such as in feeds.
+pub fn absolute_urls(html: &str, page_url: &str) -> String {
+    rewrite_urls(html, |url| Some(absolute_url(url, page_url)))
+}
+
+/// Resolves `url` against the absolute URL of the page it appears on.
+pub fn absolute_url(url: &str, page_url: &str) -> String {
+    let origin_end = page_url
+        .find("://")
+        .and_then(|scheme| page_url[scheme + 3..].find('/').map(|i| scheme + 3 + i))
+        .unwrap_or(page_url.len());
+    let directory = &page_url[..page_url.rfind('/').map_or(page_url.len(), |i| i + 1)];
+    if url.starts_with("//") || url.contains("://") || url.starts_with("mailto:") {
+        url.to_string()
+    } else if url.starts_with('/') {
+        format!("{}{url}", &page_url[..origin_end])
+    } else if url.starts_with('#') {
+        format!("{page_url}{url}")
+    } else {
+        format!("{directory}{url}")
+    }
+}
+
+/// The `src` of the first image in `html`.
+pub fn first_image(html: &str) -> Option<&str> {
+    let start = html.find("<img ")?;
+    let rest = &html[start..];
+    let rest = &rest[rest.find("src=\"")? + 5..];
+    Some(&rest[..rest.find('"')?])
+}
+
+/// Replaces the values of `href` and `src` attributes for which `rewrite`
+/// returns a new URL.
+fn rewrite_urls(html: &str, rewrite: impl Fn(&str) -> Option<String>) -> String {
+    let mut out = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(start) = ["href=\"", "src=\""]
+        .iter()
+        .filter_map(|attribute| Some(rest.find(attribute)? + attribute.len()))
+        .min()
+    {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = rest.find('"').unwrap_or(rest.len());
+        let url = &rest[..end];
+        out.push_str(&rewrite(url).unwrap_or_else(|| url.to_string()));
+        rest = &rest[end..];
+    }
+    out.push_str(rest);
+    out
+}
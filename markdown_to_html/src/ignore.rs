@@ -0,0 +1,366 @@
+//! Rules for leaving sources out of the site, written like `.gitignore`.
+//!
+//! Rules come from the `ignore` list in the configuration and then from
+//! `.blogignore` at the site root, after the defaults that hide names
+//! starting with `_` or `.`. The last rule that matches a path decides, so a
+//! later `!pattern` brings back something an earlier rule ignored. Ignoring a
+//! directory ignores everything in it.
+
+use std::fmt;
+use std::path::Path;
+
+pub const FILE_NAME: &str = ".blogignore";
+
+const DEFAULTS: [&str; 2] = ["_*", ".*"];
+
+pub struct Rule {
+    /// The rule as written, for messages.
+    pattern: String,
+    /// Where the rule was written, such as `.blogignore:3`.
+    origin: String,
+    glob: Vec<char>,
+    negated: bool,
+    /// Whether the pattern ends in `/` and so only matches directories.
+    directory_only: bool,
+    /// Whether the pattern contains a `/` and so matches the whole path from
+    /// the site root rather than just the name.
+    anchored: bool,
+}
+
+impl Rule {
+    fn new(pattern: &str, origin: String) -> Option<Rule> {
+        let pattern = trim_trailing_spaces(pattern);
+        if pattern.is_empty() || pattern.starts_with('#') {
+            return None;
+        }
+        let (negated, glob) = match pattern.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, pattern),
+        };
+        let (directory_only, glob) = match glob.strip_suffix('/') {
+            Some(rest) => (true, rest),
+            None => (false, glob),
+        };
+        let anchored = glob.contains('/');
+        let glob = glob.strip_prefix('/').unwrap_or(glob);
+        Some(Rule {
+            pattern: pattern.to_string(),
+            origin,
+            glob: glob.chars().collect(),
+            negated,
+            directory_only,
+            anchored,
+        })
+    }
+
+    fn matches(&self, path: &str, is_dir: bool) -> bool {
+        if self.directory_only && !is_dir {
+            return false;
+        }
+        let text: Vec<char> = match self.anchored {
+            true => path.chars().collect(),
+            false => path.rsplit('/').next().unwrap_or(path).chars().collect(),
+        };
+        glob_match(&self.glob, &text)
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "`{}` ({})", self.pattern, self.origin)
+    }
+}
+
+pub struct Ignore {
+    rules: Vec<Rule>,
+}
+
+impl Ignore {
+    /// The default rules, then `patterns` from the configuration, then
+    /// `.blogignore` in `source` if there is one.
+    pub fn load(source: &Path, patterns: &[String]) -> Result<Ignore, String> {
+        let mut rules: Vec<Rule> = DEFAULTS
+            .iter()
+            .filter_map(|pattern| Rule::new(pattern, "default".to_string()))
+            .collect();
+        rules.extend(
+            patterns
+                .iter()
+                .filter_map(|pattern| Rule::new(pattern, "config".to_string())),
+        );
+        let path = source.join(FILE_NAME);
+        if path.exists() {
+            let text =
+                std::fs::read_to_string(&path).map_err(|e| format!("{}: {e}", path.display()))?;
+            rules.extend(
+                text.lines()
+                    .enumerate()
+                    .filter_map(|(i, line)| Rule::new(line, format!("{FILE_NAME}:{}", i + 1))),
+            );
+        }
+        Ok(Ignore { rules })
+    }
+
+    /// The rule that ignores `relative`, a path from the site root, or `None`
+    /// if it is not ignored.
+    pub fn matched(&self, relative: &Path, is_dir: bool) -> Option<&Rule> {
+        let path = relative
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| rule.matches(&path, is_dir))
+            .filter(|rule| !rule.negated)
+    }
+
+    /// The rule that ignores the file `relative` or one of the directories it
+    /// is in, for files that are found through links rather than by walking
+    /// the sources.
+    pub fn matched_file(&self, relative: &Path) -> Option<&Rule> {
+        let mut ancestors: Vec<&Path> = relative.ancestors().skip(1).collect();
+        ancestors.reverse();
+        ancestors
+            .into_iter()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .find_map(|dir| self.matched(dir, true))
+            .or_else(|| self.matched(relative, false))
+    }
+}
+
+/// Drops trailing spaces unless they are escaped with a backslash.
+fn trim_trailing_spaces(line: &str) -> &str {
+    let mut end = line.len();
+    while line[..end].ends_with(' ') && !line[..end - 1].ends_with('\\') {
+        end -= 1;
+    }
+    &line[..end]
+}
+
+/// Matches `text` against a glob where `*` and `?` stay within one path
+/// segment, `**` crosses segments and `[...]` is a character class.
+fn glob_match(glob: &[char], text: &[char]) -> bool {
+    match glob {
+        [] => text.is_empty(),
+        ['*', '*', '/', rest @ ..] => (0..=text.len())
+            .filter(|&i| i == 0 || text[i - 1] == '/')
+            .any(|i| glob_match(rest, &text[i..])),
+        ['*', '*', rest @ ..] => (0..=text.len()).any(|i| glob_match(rest, &text[i..])),
+        ['*', rest @ ..] => {
+            let segment_end = text.iter().position(|&c| c == '/').unwrap_or(text.len());
+            (0..=segment_end).any(|i| glob_match(rest, &text[i..]))
+        }
+        ['?', rest @ ..] => {
+            matches!(text.first(), Some(&c) if c != '/') && glob_match(rest, &text[1..])
+        }
+        ['[', class @ ..] => match (text.first(), class_match(class, text.first().copied())) {
+            (Some(_), Some((true, rest))) => glob_match(rest, &text[1..]),
+            (_, Some((false, _))) | (None, Some(_)) => false,
+            // An unclosed `[` is an ordinary character.
+            (_, None) => text.first() == Some(&'[') && glob_match(class, &text[1..]),
+        },
+        ['\\', c, rest @ ..] => text.first() == Some(c) && glob_match(rest, &text[1..]),
+        [c, rest @ ..] => text.first() == Some(c) && glob_match(rest, &text[1..]),
+    }
+}
+
+/// Matches `c` against the character class that starts `class`, just after
+/// its `[`. Returns whether it matched and the rest of the glob, or `None` if
+/// the class is never closed.
+fn class_match(class: &[char], c: Option<char>) -> Option<(bool, &[char])> {
+    let (negated, mut rest) = match class {
+        ['!' | '^', rest @ ..] => (true, rest),
+        _ => (false, class),
+    };
+    let mut matched = false;
+    let mut first = true;
+    loop {
+        match rest {
+            [']', after @ ..] if !first => {
+                let matched = matched != negated && c.is_some_and(|c| c != '/');
+                return Some((matched, after));
+            }
+            [low, '-', high, after @ ..] if *high != ']' => {
+                matched |= c.is_some_and(|c| (*low..=*high).contains(&c));
+                rest = after;
+            }
+            [single, after @ ..] => {
+                matched |= c == Some(*single);
+                rest = after;
+            }
+            [] => return None,
+        }
+        first = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::run::TempDir;
+
+    fn glob(glob: &str, text: &str) -> bool {
+        let glob: Vec<char> = glob.chars().collect();
+        let text: Vec<char> = text.chars().collect();
+        glob_match(&glob, &text)
+    }
+
+    #[test]
+    fn globs() {
+        let cases = [
+            ("README.md", "README.md", true),
+            ("README.md", "README.mdx", false),
+            ("*.md", "notes.md", true),
+            ("*.md", "a/notes.md", false),
+            ("*", "", true),
+            ("a*b", "ab", true),
+            ("a*b", "a/b", false),
+            ("?.md", "a.md", true),
+            ("?.md", "ab.md", false),
+            ("a?b", "a/b", false),
+            ("**/notes.md", "notes.md", true),
+            ("**/notes.md", "a/b/notes.md", true),
+            ("**/notes.md", "a/bnotes.md", false),
+            ("a/**", "a/b/c.md", true),
+            ("a/**/c.md", "a/c.md", true),
+            ("a/**/c.md", "a/b/d/c.md", true),
+            ("a**", "ab/c", true),
+            ("\\*.md", "*.md", true),
+            ("\\*.md", "a.md", false),
+            ("[a-c].md", "b.md", true),
+            ("[a-c].md", "d.md", false),
+            ("[!x].md", "y.md", true),
+            ("[!x].md", "x.md", false),
+            ("[^x].md", "x.md", false),
+            ("[]x].md", "].md", true),
+            ("[a-].md", "-.md", true),
+            ("a[/]b", "a/b", false),
+            ("[!x]", "/", false),
+            ("[ab", "[ab", true),
+            ("[ab", "a", false),
+        ];
+        for (pattern, text, expected) in cases {
+            assert_eq!(glob(pattern, text), expected, "`{pattern}` on `{text}`");
+        }
+    }
+
+    #[test]
+    fn classes() {
+        let class: Vec<char> = "a-cx]rest".chars().collect();
+        let rest: Vec<char> = "rest".chars().collect();
+        assert_eq!(class_match(&class, Some('b')), Some((true, &rest[..])));
+        assert_eq!(class_match(&class, Some('x')), Some((true, &rest[..])));
+        assert_eq!(class_match(&class, Some('d')), Some((false, &rest[..])));
+        assert_eq!(class_match(&class, None), Some((false, &rest[..])));
+        let negated: Vec<char> = "!a-c]".chars().collect();
+        assert_eq!(class_match(&negated, Some('d')), Some((true, &[][..])));
+        assert_eq!(class_match(&negated, None), Some((false, &[][..])));
+        let unclosed: Vec<char> = "abc".chars().collect();
+        assert_eq!(class_match(&unclosed, Some('a')), None);
+    }
+
+    /// The rule ignoring each of `paths`, a directory when it ends in `/`, or
+    /// `None` where none does.
+    fn matched(ignore: &Ignore, paths: &[&str]) -> Vec<Option<String>> {
+        paths
+            .iter()
+            .map(|path| {
+                let (path, is_dir) = match path.strip_suffix('/') {
+                    Some(path) => (path, true),
+                    None => (*path, false),
+                };
+                ignore
+                    .matched(Path::new(path), is_dir)
+                    .map(|rule| rule.pattern.clone())
+            })
+            .collect()
+    }
+
+    fn load(patterns: &[&str], blogignore: &str) -> Ignore {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path.join(FILE_NAME), blogignore).unwrap();
+        let patterns: Vec<String> = patterns.iter().map(|p| p.to_string()).collect();
+        Ignore::load(&dir.path, &patterns).unwrap()
+    }
+
+    #[test]
+    fn rules() {
+        let ignore = load(
+            &["*.md", "!posts/*.md"],
+            "# Comment\n\nposts/secret.md\n/TODO.md\nbuild/\ndocs/**/draft-*.md\n!_keep.md\nspaced.md  \n",
+        );
+        let some = |pattern: &str| Some(pattern.to_string());
+        assert_eq!(
+            matched(
+                &ignore,
+                &[
+                    "notes.md",
+                    "a/notes.md",
+                    "posts/a.md",
+                    "posts/secret.md",
+                    "posts/deeper/a.md",
+                    "TODO.md",
+                    "a/TODO.md",
+                    "build/",
+                    "a/build/",
+                    "build",
+                    "docs/draft-1.md",
+                    "docs/x/y/draft-2.md",
+                    "_private/",
+                    "_keep.md",
+                    ".git/",
+                    "img/",
+                    "spaced.md",
+                ]
+            ),
+            [
+                some("*.md"),
+                some("*.md"),
+                // A later negation brings a file back, and a later rule
+                // ignores it again.
+                None,
+                some("posts/secret.md"),
+                some("*.md"),
+                some("/TODO.md"),
+                some("*.md"),
+                some("build/"),
+                some("build/"),
+                // Only directories match a trailing `/`.
+                None,
+                some("docs/**/draft-*.md"),
+                some("docs/**/draft-*.md"),
+                some("_*"),
+                None,
+                some(".*"),
+                None,
+                some("spaced.md"),
+            ]
+        );
+
+        let ignore = load(&[], "");
+        let rule = ignore.matched(Path::new("_drafts"), true).unwrap();
+        assert_eq!(rule.to_string(), "`_*` (default)");
+        let ignore = load(&[], "\n*.txt\n");
+        let rule = ignore.matched(Path::new("a.txt"), false).unwrap();
+        assert_eq!(rule.to_string(), "`*.txt` (.blogignore:2)");
+    }
+
+    #[test]
+    fn files_in_ignored_directories() {
+        let ignore = load(&["!_private/*.png"], "");
+        let matched = |path: &str| {
+            ignore
+                .matched_file(Path::new(path))
+                .map(|rule| rule.pattern.clone())
+        };
+        assert_eq!(matched("img/a.png"), None);
+        // Ignoring a directory ignores what is in it, even when a rule would
+        // bring back the file on its own.
+        assert_eq!(matched("_private/x.png"), Some("_*".to_string()));
+        assert_eq!(matched("a/.hidden/b/x.png"), Some(".*".to_string()));
+        assert_eq!(matched("a/_x.png"), Some("_*".to_string()));
+    }
+}
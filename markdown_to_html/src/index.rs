@@ -0,0 +1,140 @@
+//! The list of posts shown on the index page.
+
+use crate::config::Dates;
+use crate::post::Post;
+use crate::value::{Table, Value};
+
+/// Groups `posts` by year for the index template, newest first, as a list of
+/// `{ year, posts: [{ title, date, date_text, url }] }`, where `date` is
+/// ISO-8601 and `date_text` is formatted as `dates` configures.
+pub fn years(posts: &[Post], dates: &Dates) -> Value {
+    let mut sorted: Vec<&Post> = posts.iter().collect();
+    sorted.sort_by(|a, b| b.date.cmp(&a.date).then_with(|| a.title.cmp(&b.title)));
+
+    let mut years: Vec<(String, Vec<Value>)> = Vec::new();
+    for post in sorted {
+        let year = format!("{:04}", post.date.year);
+        if years.last().is_none_or(|(current, _)| *current != year) {
+            years.push((year, Vec::new()));
+        }
+        let entry = Table::from([
+            ("title".into(), Value::String(post.title.clone())),
+            ("date".into(), Value::String(post.date.iso())),
+            ("date_text".into(), Value::String(post.date.display(dates))),
+            ("url".into(), Value::String(format!("./{}", post.url))),
+        ]);
+        years.last_mut().unwrap().1.push(Value::Table(entry));
+    }
+    Value::Array(
+        years
+            .into_iter()
+            .map(|(year, posts)| {
+                Value::Table(Table::from([
+                    ("year".into(), Value::String(year)),
+                    ("posts".into(), Value::Array(posts)),
+                ]))
+            })
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::date::Date;
+
+    fn post(title: &str, date: &str) -> Post {
+        Post {
+            title: title.into(),
+            date: Date::parse_iso(date).unwrap(),
+            updated: None,
+            description: None,
+            author: None,
+            tags: Vec::new(),
+            image: None,
+            url: format!("{}.html", title.to_lowercase()),
+            content: String::new(),
+        }
+    }
+
+    /// The years and the titles listed under each.
+    fn titles(years: &Value) -> Vec<(String, Vec<String>)> {
+        let string = |value: &Value| match value {
+            Value::String(s) => s.clone(),
+            other => panic!("expected a string, found {other:?}"),
+        };
+        let Value::Array(years) = years else {
+            panic!("expected an array");
+        };
+        years
+            .iter()
+            .map(|group| {
+                let Value::Table(group) = group else {
+                    panic!("expected a table");
+                };
+                let Value::Array(posts) = &group["posts"] else {
+                    panic!("expected an array");
+                };
+                let posts = posts
+                    .iter()
+                    .map(|post| match post {
+                        Value::Table(post) => string(&post["title"]),
+                        other => panic!("expected a table, found {other:?}"),
+                    })
+                    .collect();
+                (string(&group["year"]), posts)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn newest_first_by_year() {
+        let dates = Dates {
+            input: None,
+            format: "%-d/%-m/%Y".into(),
+            months: Vec::new(),
+            weekdays: Vec::new(),
+        };
+        let posts = [
+            post("Spring", "2023-05-01"),
+            post("Later", "2024-01-02T10:00:00Z"),
+            post("Winter", "2023-12-31"),
+            post("Also spring", "2023-05-01"),
+            post("Earliest", "2023-01-02T09:00:00Z"),
+            post("Early", "2023-01-02T10:00:00Z"),
+            post("Ancient", "0999-01-01"),
+        ];
+        let years = years(&posts, &dates);
+        assert_eq!(
+            titles(&years),
+            [
+                ("2024".to_string(), vec!["Later".to_string()]),
+                (
+                    "2023".to_string(),
+                    ["Winter", "Also spring", "Spring", "Early", "Earliest"]
+                        .map(String::from)
+                        .to_vec()
+                ),
+                ("0999".to_string(), vec!["Ancient".to_string()]),
+            ]
+        );
+        let Value::Array(groups) = &years else {
+            unreachable!()
+        };
+        let Value::Table(group) = &groups[0] else {
+            unreachable!()
+        };
+        let Value::Array(entries) = &group["posts"] else {
+            unreachable!()
+        };
+        let Value::Table(entry) = &entries[0] else {
+            unreachable!()
+        };
+        let string = |s: &str| Value::String(s.into());
+        assert_eq!(entry["url"], string("./later.html"));
+        assert_eq!(entry["date"], string("2024-01-02T10:00:00Z"));
+        assert_eq!(entry["date_text"], string("2/1/2024"));
+        assert!(titles(&super::years(&[], &dates)).is_empty());
+    }
+}
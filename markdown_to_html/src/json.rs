@@ -0,0 +1,421 @@
+//! Reading and writing JSON.
+
+use crate::value::{ParseError, Table, Value};
+
+/// Parses a JSON document. Numbers without a fraction or exponent that fit
+/// in an `i64` become integers and all others floats.
+pub fn parse(source: &str) -> Result<Value, ParseError> {
+    let mut parser = Parser {
+        src: source,
+        pos: 0,
+        line: 1,
+    };
+    parser.skip_whitespace();
+    let value = parser.parse_value()?;
+    parser.skip_whitespace();
+    match parser.peek() {
+        None => Ok(value),
+        Some(c) => parser.error(format!("expected the end of the document, found `{c}`")),
+    }
+}
+
+/// Serializes `value` as indented JSON. Table keys come out sorted, and
+/// non-finite floats, which JSON cannot represent, become `null`.
+pub fn to_string_pretty(value: &Value) -> String {
+    let mut out = String::new();
+    write_value(value, 0, &mut out);
+    out.push('\n');
+    out
+}
+
+fn write_value(value: &Value, indent: usize, out: &mut String) {
+    match value {
+        Value::Null => out.push_str("null"),
+        Value::Boolean(b) => out.push_str(if *b { "true" } else { "false" }),
+        Value::Integer(i) => out.push_str(&i.to_string()),
+        Value::Float(f) if f.is_finite() => out.push_str(&format!("{f:?}")),
+        Value::Float(_) => out.push_str("null"),
+        Value::String(s) => write_string(s, out),
+        Value::Array(items) if items.is_empty() => out.push_str("[]"),
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                out.push_str(if i == 0 { "\n" } else { ",\n" });
+                push_indent(indent + 1, out);
+                write_value(item, indent + 1, out);
+            }
+            out.push('\n');
+            push_indent(indent, out);
+            out.push(']');
+        }
+        Value::Table(table) if table.is_empty() => out.push_str("{}"),
+        Value::Table(table) => {
+            out.push('{');
+            for (i, (key, item)) in table.iter().enumerate() {
+                out.push_str(if i == 0 { "\n" } else { ",\n" });
+                push_indent(indent + 1, out);
+                write_string(key, out);
+                out.push_str(": ");
+                write_value(item, indent + 1, out);
+            }
+            out.push('\n');
+            push_indent(indent, out);
+            out.push('}');
+        }
+    }
+}
+
+fn push_indent(indent: usize, out: &mut String) {
+    out.extend(std::iter::repeat_n("  ", indent));
+}
+
+fn write_string(s: &str, out: &mut String) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if u32::from(c) < 0x20 => out.push_str(&format!("\\u{:04x}", u32::from(c))),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+struct Parser<'a> {
+    src: &'a str,
+    pos: usize,
+    line: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.src[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        if c == '\n' {
+            self.line += 1;
+        }
+        Some(c)
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.bump();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn error<T>(&self, message: impl Into<String>) -> Result<T, ParseError> {
+        Err(ParseError {
+            line: self.line,
+            message: message.into(),
+        })
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t' | '\n' | '\r')) {
+            self.bump();
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Value, ParseError> {
+        match self.peek() {
+            Some('{') => self.parse_object(),
+            Some('[') => self.parse_array(),
+            Some('"') => Ok(Value::String(self.parse_string()?)),
+            Some('-' | '0'..='9') => self.parse_number(),
+            Some(c) if c.is_ascii_alphabetic() => {
+                let len = self
+                    .rest()
+                    .find(|c: char| !c.is_ascii_alphanumeric())
+                    .unwrap_or(self.rest().len());
+                let word = &self.rest()[..len];
+                let value = match word {
+                    "true" => Value::Boolean(true),
+                    "false" => Value::Boolean(false),
+                    "null" => Value::Null,
+                    _ => return self.error(format!("unexpected `{word}`")),
+                };
+                self.pos += len;
+                Ok(value)
+            }
+            Some(c) => self.error(format!("expected a value, found `{c}`")),
+            None => self.error("expected a value, found the end of the document"),
+        }
+    }
+
+    fn parse_number(&mut self) -> Result<Value, ParseError> {
+        let start = self.pos;
+        self.eat('-');
+        let integer_start = self.pos;
+        while matches!(self.peek(), Some('0'..='9')) {
+            self.bump();
+        }
+        let integer = &self.src[integer_start..self.pos];
+        if integer.is_empty() || (integer.len() > 1 && integer.starts_with('0')) {
+            return self.error(format!("invalid number `{}`", &self.src[start..self.pos]));
+        }
+        let mut float = false;
+        if self.eat('.') {
+            float = true;
+            if !self.digits() {
+                return self.error("expected digits after `.`");
+            }
+        }
+        if self.eat('e') || self.eat('E') {
+            float = true;
+            let _ = self.eat('+') || self.eat('-');
+            if !self.digits() {
+                return self.error("expected digits in exponent");
+            }
+        }
+        let text = &self.src[start..self.pos];
+        if !float {
+            if let Ok(integer) = text.parse() {
+                return Ok(Value::Integer(integer));
+            }
+        }
+        match text.parse() {
+            Ok(float) => Ok(Value::Float(float)),
+            Err(_) => self.error(format!("invalid number `{text}`")),
+        }
+    }
+
+    /// Skips a run of digits, returning whether there were any.
+    fn digits(&mut self) -> bool {
+        let start = self.pos;
+        while matches!(self.peek(), Some('0'..='9')) {
+            self.bump();
+        }
+        self.pos > start
+    }
+
+    fn parse_string(&mut self) -> Result<String, ParseError> {
+        self.bump();
+        let mut out = String::new();
+        loop {
+            match self.bump() {
+                Some('"') => return Ok(out),
+                Some('\\') => self.parse_escape(&mut out)?,
+                Some(c) if u32::from(c) < 0x20 => {
+                    if c == '\n' {
+                        self.line -= 1;
+                    }
+                    return self.error("unterminated string or unescaped control character");
+                }
+                Some(c) => out.push(c),
+                None => return self.error("unterminated string"),
+            }
+        }
+    }
+
+    fn parse_escape(&mut self, out: &mut String) -> Result<(), ParseError> {
+        let c = match self.bump() {
+            Some('b') => '\u{8}',
+            Some('t') => '\t',
+            Some('n') => '\n',
+            Some('f') => '\u{c}',
+            Some('r') => '\r',
+            Some('"') => '"',
+            Some('\\') => '\\',
+            Some('/') => '/',
+            Some('u') => {
+                let high = self.parse_hex()?;
+                let c = match high {
+                    // A surrogate pair, written as two escapes.
+                    0xd800..=0xdbff if self.rest().starts_with("\\u") => {
+                        self.pos += 2;
+                        let low = self.parse_hex()?;
+                        char::from_u32(
+                            0x10000 + ((high - 0xd800) << 10) + (low.wrapping_sub(0xdc00)),
+                        )
+                        .filter(|_| (0xdc00..=0xdfff).contains(&low))
+                    }
+                    _ => char::from_u32(high),
+                };
+                match c {
+                    Some(c) => c,
+                    None => return self.error("invalid unicode escape"),
+                }
+            }
+            Some(c) => return self.error(format!("invalid escape `\\{c}`")),
+            None => return self.error("unterminated string"),
+        };
+        out.push(c);
+        Ok(())
+    }
+
+    fn parse_hex(&mut self) -> Result<u32, ParseError> {
+        let rest = self.rest();
+        let hex = &rest[..rest.char_indices().nth(4).map_or(rest.len(), |(i, _)| i)];
+        match u32::from_str_radix(hex, 16) {
+            Ok(value) if hex.len() == 4 && hex.chars().all(|c| c.is_ascii_hexdigit()) => {
+                self.pos += 4;
+                Ok(value)
+            }
+            _ => self.error(format!("invalid unicode escape `\\u{hex}`")),
+        }
+    }
+
+    fn parse_array(&mut self) -> Result<Value, ParseError> {
+        self.bump();
+        let mut items = Vec::new();
+        self.skip_whitespace();
+        if self.eat(']') {
+            return Ok(Value::Array(items));
+        }
+        loop {
+            self.skip_whitespace();
+            items.push(self.parse_value()?);
+            self.skip_whitespace();
+            if self.eat(']') {
+                return Ok(Value::Array(items));
+            }
+            if !self.eat(',') {
+                return self.error("expected `,` or `]` in array");
+            }
+        }
+    }
+
+    fn parse_object(&mut self) -> Result<Value, ParseError> {
+        self.bump();
+        let mut table = Table::new();
+        self.skip_whitespace();
+        if self.eat('}') {
+            return Ok(Value::Table(table));
+        }
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some('"') {
+                return self.error("expected a string key in object");
+            }
+            let key = self.parse_string()?;
+            self.skip_whitespace();
+            if !self.eat(':') {
+                return self.error("expected `:` after key");
+            }
+            self.skip_whitespace();
+            let value = self.parse_value()?;
+            if table.insert(key.clone(), value).is_some() {
+                return self.error(format!("duplicate key `{key}`"));
+            }
+            self.skip_whitespace();
+            if self.eat('}') {
+                return Ok(Value::Table(table));
+            }
+            if !self.eat(',') {
+                return self.error("expected `,` or `}` in object");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let value = Value::Table(Table::from([
+            ("null".into(), Value::Null),
+            ("true".into(), Value::Boolean(true)),
+            ("integer".into(), Value::Integer(-42)),
+            ("big".into(), Value::Integer(i64::MAX)),
+            ("float".into(), Value::Float(1.5e-7)),
+            ("whole float".into(), Value::Float(2.0)),
+            (
+                "string".into(),
+                Value::String("\"quoted\" \\ \n\r\t \u{1} é 🦀 </script>".into()),
+            ),
+            ("empty array".into(), Value::Array(Vec::new())),
+            ("empty table".into(), Value::Table(Table::new())),
+            (
+                "nested".into(),
+                Value::Array(vec![
+                    Value::Integer(1),
+                    Value::Table(Table::from([("a".into(), Value::Array(vec![]))])),
+                ]),
+            ),
+        ]));
+        let text = to_string_pretty(&value);
+        assert_eq!(parse(&text).unwrap(), value);
+        assert!(text.contains(r#""string": "\"quoted\" \\ \n\r\t \u0001 é 🦀 </script>""#));
+        assert!(text.contains("\"whole float\": 2.0"));
+        assert_eq!(
+            to_string_pretty(&Value::Array(vec![
+                Value::Float(f64::NAN),
+                Value::Table(Table::from([("b".into(), Value::Integer(1))])),
+            ])),
+            "[\n  null,\n  {\n    \"b\": 1\n  }\n]\n"
+        );
+    }
+
+    #[test]
+    fn values() {
+        let cases = [
+            ("0", Value::Integer(0)),
+            ("-0", Value::Integer(0)),
+            ("1e2", Value::Float(100.0)),
+            ("-1.25E-1", Value::Float(-0.125)),
+            ("9223372036854775808", Value::Float(9223372036854775808.0)),
+            (r#""é🦀\/""#, Value::String("é🦀/".into())),
+            (
+                " \n[ 1 , true,null ] \n",
+                Value::Array(vec![Value::Integer(1), Value::Boolean(true), Value::Null]),
+            ),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(parse(text).unwrap(), expected, "{text}");
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let cases = [
+            (
+                "",
+                "line 1: expected a value, found the end of the document",
+            ),
+            ("[1,]", "line 1: expected a value, found `]`"),
+            ("[1 2]", "line 1: expected `,` or `]` in array"),
+            ("{\"a\": 1,\n}", "line 2: expected a string key in object"),
+            ("{a: 1}", "line 1: expected a string key in object"),
+            ("{\"a\" 1}", "line 1: expected `:` after key"),
+            ("{\"a\": 1, \"a\": 2}", "line 1: duplicate key `a`"),
+            ("{\"a\": 1", "line 1: expected `,` or `}` in object"),
+            ("01", "line 1: invalid number `01`"),
+            ("-", "line 1: invalid number `-`"),
+            ("1.", "line 1: expected digits after `.`"),
+            ("1e", "line 1: expected digits in exponent"),
+            ("nul", "line 1: unexpected `nul`"),
+            ("\"abc", "line 1: unterminated string"),
+            (
+                "\n\"a\nb\"",
+                "line 2: unterminated string or unescaped control character",
+            ),
+            (r#""\x""#, r#"line 1: invalid escape `\x`"#),
+            (r#""\u12""#, r#"line 1: invalid unicode escape `\u12"`"#),
+            (r#""\ud83eA""#, "line 1: invalid unicode escape"),
+            (
+                "{} {}",
+                "line 1: expected the end of the document, found `{`",
+            ),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(parse(text).unwrap_err().to_string(), expected, "{text}");
+        }
+    }
+}
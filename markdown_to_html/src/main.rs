@@ -1,66 +1,1466 @@
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    for entry in std::fs::read_dir(std::env::current_dir()?)? {
-        let entry = entry?;
-        if entry.file_type()?.is_file()
-            && entry.path().extension() == Some(std::ffi::OsStr::new("md"))
-        {
-            let output_path = entry.path().with_extension("html");
-            std::fs::write(
-                &output_path,
-                format!(
-                    r#"<!DOCTYPE html>
-<html lang="en">
-<head>
-    <meta charset="utf-8" />
-    <meta name="viewport" content="width=device-width, initial-scale=1.0" />
-    <meta name="apple-mobile-web-app-capable" content="yes">
-    <title>{}</title>
-    <style>
-        html, body {{
-            overflow-x: hidden;
-        }}
-        @media (prefers-color-scheme: dark) {{
-            body {{
-                color: #fafafa;
-                background-color: #000;
-            }}
-            :link, :visited, :visited:active {{
-                color: #2f81f7;
-            }}
-        }}
-        body {{
-            position: relative;
-            box-sizing: border-box;
-            -webkit-box-sizing: border-box;
-            -moz-box-sizing: border-box;
-        }}
-        pre {{
-            overflow-x: scroll;
-        }}
-        img {{
-            max-width: 100%;
-        }}
-    </style>
-</head>
-<body>
-<main>
-{}
-</main>
-</body>
-</html>"#,
-                    entry
-                        .path()
-                        .file_stem()
-                        .unwrap_or_default()
-                        .to_string_lossy(),
-                    markdown::to_html_with_options(
-                        &std::fs::read_to_string(entry.path())?,
-                        &markdown::Options::gfm(),
-                    )?,
+mod assets;
+mod cache;
+mod cli;
+mod code;
+mod config;
+mod date;
+mod doc_links;
+mod examples;
+mod extract;
+mod feed;
+mod front_matter;
+mod highlight;
+mod html;
+mod ignore;
+mod index;
+mod json;
+mod parallel;
+mod post;
+mod run;
+mod serve;
+mod sitemap;
+mod snippets;
+mod template;
+mod toml;
+mod value;
+mod yaml;
+mod zip;
+
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+use value::{Table, Value};
+
+/// File every build leaves in the output directory, so that a later build
+/// knows it may clean the directory out.
+const OUTPUT_MARKER: &str = ".blog-output";
+
+fn main() -> std::process::ExitCode {
+    match run() {
+        Ok(()) => std::process::ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("error: {error}");
+            std::process::ExitCode::FAILURE
+        }
+    }
+}
+
+fn run() -> Result<(), Box<dyn std::error::Error>> {
+    let args = cli::Args::parse(std::env::args().skip(1))?;
+    match args.command {
+        cli::Command::Build => build(&args),
+        cli::Command::Serve { port } => serve::serve(args, port),
+        cli::Command::CheckSnippets => snippets::check(&args),
+    }
+}
+
+/// Builds the site from `args.source` into `args.output`, rendering only the
+/// pages whose inputs changed since the last build.
+pub fn build(args: &cli::Args) -> Result<(), Box<dyn std::error::Error>> {
+    let config = config::Config::load(&args.config, &args.overrides)?;
+    let templates = template::Templates::load(&args.source.join("templates"))?;
+    let manifest_path = args.source.join(cache::FILE_NAME);
+    let previous = match args.output.canonicalize() {
+        Ok(output) => cache::Manifest::load(&manifest_path, &output.to_string_lossy()),
+        Err(_) => None,
+    };
+    // Until this build finishes, the output matches no manifest.
+    if manifest_path.exists() {
+        std::fs::remove_file(&manifest_path)?;
+    }
+    if previous.is_none() {
+        clean(&args.source, &args.output)?;
+    }
+    let previous = previous.unwrap_or_default();
+    let outputs_path = args.source.join(run::OUTPUTS_FILE_NAME);
+    let recorded = run::load_outputs(&outputs_path)?;
+    std::fs::create_dir_all(&args.output)?;
+    std::fs::write(args.output.join(OUTPUT_MARKER), "")?;
+    let mut manifest = cache::Manifest {
+        output: args.output.canonicalize()?.to_string_lossy().into_owned(),
+        outputs: std::collections::BTreeMap::new(),
+    };
+    let config_hash = cache::hash(json::to_string_pretty(&config.to_value()).as_bytes());
+    let templates_hash = templates.fingerprint().to_string();
+
+    let ignore = ignore::Ignore::load(&args.source, &config.ignore)?;
+    let mut sources = Vec::new();
+    let mut skip = vec![args.output.canonicalize()?, args.source.join("templates")];
+    // The registry vendored for code blocks is not part of the site.
+    skip.extend(
+        config
+            .snippets
+            .vendor
+            .iter()
+            .map(|vendor| args.source.join(vendor)),
+    );
+    find_pages(args, &args.source, &skip, &ignore, &mut sources)?;
+    sources.sort();
+
+    // Front matter decides where each page goes and whether it is built at
+    // all, so it is read up front, in order. Rendering then runs in parallel.
+    let mut pages = Vec::new();
+    let mut index_source = None;
+    let mut outputs = std::collections::BTreeMap::new();
+    // Sources left out of this build, and why, to warn about links to them.
+    let mut unpublished = BTreeMap::new();
+    let now = date::Date::from_system_time(std::time::SystemTime::now());
+    for path in sources {
+        let relative = path.strip_prefix(&args.source)?.to_path_buf();
+        // The index is written last, once every post is known.
+        if relative == Path::new("index.md") {
+            index_source = Some(path);
+            continue;
+        }
+        let source = std::fs::read_to_string(&path)?;
+        let (mut meta, body) = front_matter::parse(&path, &source, &config.dates)?;
+        let body_start = source.len() - body.len();
+        let extracted = extract::extract(body, &config.dates);
+        for (line, message) in fill_from_content(&mut meta, &extracted) {
+            let line = source[..body_start].lines().count() + line;
+            eprintln!("warning: {}:{line}: {message}", path.display());
+        }
+        let scheduled = meta
+            .date
+            .is_some_and(|date| date.timestamp() > now.timestamp());
+        if (meta.draft || scheduled) && !args.drafts {
+            let scheduled_for = || {
+                let date = meta.date.map(|date| date.iso()).unwrap_or_default();
+                format!("scheduled for {date}")
+            };
+            if args.verbose {
+                match meta.draft {
+                    true => println!("skipped {}: draft", path.display()),
+                    false => println!("skipped {}: {}", path.display(), scheduled_for()),
+                }
+            }
+            let reason = match meta.draft {
+                true => "a draft".to_string(),
+                false => scheduled_for(),
+            };
+            unpublished.insert(relative, reason);
+            continue;
+        }
+        let output_relative = output_path(&relative, meta.slug.as_deref(), config.pretty_urls);
+        if let Some(other) = outputs.insert(output_relative.clone(), path.clone()) {
+            return Err(output_conflict(&other, &path, &output_relative).into());
+        }
+        let title = meta.title.clone().unwrap_or_else(|| {
+            path.file_stem()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned()
+        });
+        let url = url_for(&output_relative, config.pretty_urls);
+        // Relative links keep pointing at the files next to the source when
+        // the page ends up in another directory.
+        let relocation = relocation(
+            output_relative.parent().unwrap_or(Path::new("")),
+            relative.parent().unwrap_or(Path::new("")),
+        );
+        pages.push(PageSource {
+            path,
+            relative,
+            source,
+            body_start,
+            meta,
+            output_relative,
+            title,
+            url,
+            relocation,
+            scheduled,
+            date_line: extracted.date,
+        });
+    }
+
+    let site = Site {
+        args,
+        config: &config,
+        templates: &templates,
+        previous: &previous,
+        recorded: &recorded,
+        unpublished: &unpublished,
+        config_hash: &config_hash,
+        templates_hash: &templates_hash,
+    };
+    let rendered = parallel::map(&pages, |page| {
+        render_source(&site, page).map_err(|error| error.to_string())
+    });
+    let mut posts = Vec::new();
+    let mut assets = std::collections::BTreeSet::new();
+    let mut run_outputs = run::Outputs::new();
+    let mut sitemap_entries = Vec::new();
+    for (page, rendered) in pages.into_iter().zip(rendered) {
+        let Rendered {
+            log,
+            warnings,
+            entry,
+            examples,
+        } = rendered?;
+        for warning in warnings {
+            eprintln!("warning: {warning}");
+        }
+        for line in log {
+            println!("{line}");
+        }
+        if let Some((key, entry)) = examples {
+            manifest.outputs.insert(key, entry);
+        }
+        let content = entry.html.clone().unwrap_or_default();
+        assets.extend(entry.assets.iter().cloned());
+        if !entry.runs.is_empty() {
+            run_outputs.insert(cache::key(&page.relative), entry.runs.clone());
+        }
+        manifest
+            .outputs
+            .insert(cache::key(&page.output_relative), entry);
+
+        let meta = page.meta;
+        if !meta.draft && !meta.noindex && !page.scheduled {
+            sitemap_entries.push(sitemap::Entry {
+                url: page.url.clone(),
+                lastmod: sitemap::lastmod(&meta),
+            });
+        }
+        if let Some(date) = meta.date {
+            posts.push(post::Post {
+                title: page.title,
+                date,
+                updated: meta.updated,
+                description: meta.description,
+                author: meta.author,
+                tags: meta.tags,
+                image: meta
+                    .image
+                    .map(|image| match image.starts_with('/') {
+                        true => format!("{}{image}", config.base_path),
+                        false if html::is_relative(&image) => {
+                            format!("{}{image}", page.relocation)
+                        }
+                        false => image,
+                    })
+                    .or_else(|| html::first_image(&content).map(str::to_string)),
+                url: page.url,
+                content,
+            });
+        }
+    }
+
+    let output_path = args.output.join("index.html");
+    if let Some(other) = outputs.get(Path::new("index.html")) {
+        return Err(format!(
+            "{}: would be written to `index.html`, which is the index page",
+            other.display()
+        )
+        .into());
+    }
+    let (path, source) = match index_source {
+        Some(path) => {
+            let source = std::fs::read_to_string(&path)?;
+            (Some(path), source)
+        }
+        None => (None, String::new()),
+    };
+    let (meta, body) = match &path {
+        Some(path) => front_matter::parse(path, &source, &config.dates)?,
+        None => Default::default(),
+    };
+    let source_key = path
+        .as_deref()
+        .and_then(|path| path.strip_prefix(&args.source).ok())
+        .map(cache::key);
+    let runs = known_runs(
+        &recorded,
+        source_key.as_deref(),
+        previous.outputs.get("index.html"),
+    );
+    let context = Context {
+        config: &config,
+        crates: doc_links::Crates::new(&config.docs.crates, &meta.dependencies),
+        includes: code::Includes {
+            root: &args.source,
+            dir: &args.source,
+        },
+        runs: &runs,
+        run: args.run,
+        date_line: None,
+        unpublished: &unpublished,
+    };
+    let converted = convert(
+        path.as_deref().unwrap_or(&output_path),
+        &source,
+        body,
+        &context,
+    )?;
+    for warning in &converted.warnings {
+        eprintln!("warning: {warning}");
+    }
+    let intro = converted.html;
+    let index_assets = local_assets(&intro, Path::new(""));
+    assets.extend(index_assets.iter().cloned());
+    let years = index::years(&posts, &config.dates);
+    // The index lists every post, so it changes whenever one of them does.
+    let mut hasher = cache::Hasher::new();
+    hasher.write(&source);
+    for block in &converted.blocks {
+        if let Some(included) = &block.included {
+            hasher.write(&std::fs::read_to_string(included).unwrap_or_default());
+        }
+    }
+    hasher.write(&json::to_string_pretty(&years));
+    if let Some(source_key) = &source_key {
+        if !converted.runs.is_empty() {
+            run_outputs.insert(source_key.clone(), converted.runs.clone());
+        }
+    }
+    let entry = cache::Output {
+        source: source_key,
+        content: Some(hasher.finish()),
+        templates: Some(templates_hash.clone()),
+        config: Some(config_hash.clone()),
+        html: None,
+        assets: index_assets,
+        includes: Vec::new(),
+        runs: converted.runs,
+    };
+    let unchanged = previous.outputs.get("index.html").is_some_and(|cached| {
+        cached.content == entry.content
+            && cached.templates == entry.templates
+            && cached.config == entry.config
+    }) && output_path.is_file();
+    if unchanged {
+        if args.verbose {
+            println!(
+                "unchanged {}",
+                path.as_deref().unwrap_or(&output_path).display()
+            );
+        }
+    } else {
+        let html = render_page(
+            &templates,
+            &config,
+            path.as_deref().unwrap_or(&output_path),
+            &meta,
+            Page {
+                layout: "index",
+                title: meta.title.as_deref().unwrap_or(&config.title),
+                url: "",
+                content: &html::prefix_root_urls(&intro, &config.base_path),
+                examples: None,
+                scheduled: false,
+            },
+            Table::from([("years".into(), years)]),
+        )?;
+        std::fs::write(&output_path, html)?;
+        match &path {
+            Some(path) => println!("{} -> {}", path.display(), output_path.display()),
+            None => println!("{}", output_path.display()),
+        }
+    }
+    manifest.outputs.insert("index.html".into(), entry);
+    if !meta.draft && !meta.noindex {
+        // The index changes whenever a post does.
+        let lastmod = posts
+            .iter()
+            .map(|post| post.updated.unwrap_or(post.date))
+            .chain(sitemap::lastmod(&meta))
+            .max();
+        sitemap_entries.push(sitemap::Entry {
+            url: String::new(),
+            lastmod,
+        });
+    }
+
+    let mut generated = Vec::new();
+    match &config.base_url {
+        Some(base_url) => {
+            let feed = feed::Feed::new(&config, base_url, &posts);
+            generated.extend([
+                (feed::ATOM_FILE_NAME, feed.atom()),
+                (feed::RSS_FILE_NAME, feed.rss()),
+                (feed::JSON_FILE_NAME, feed.json()),
+                (
+                    sitemap::SITEMAP_FILE_NAME,
+                    sitemap::sitemap(base_url, &sitemap_entries),
                 ),
-            )?;
-            println!("{} -> {}", entry.path().display(), output_path.display());
+            ]);
         }
+        None => eprintln!("warning: `base_url` is not configured, skipping feeds and sitemap"),
     }
+    generated.push((sitemap::ROBOTS_FILE_NAME, sitemap::robots(&config)));
+    for (file_name, contents) in generated {
+        let output_path = args.output.join(file_name);
+        if std::fs::read_to_string(&output_path).ok().as_ref() != Some(&contents) {
+            std::fs::write(&output_path, contents)?;
+            println!("{}", output_path.display());
+        }
+        manifest
+            .outputs
+            .insert(file_name.to_string(), cache::Output::default());
+    }
+
+    for asset in assets {
+        if let Some(entry) = copy_asset(args, &ignore, previous.outputs.get(&asset), &asset)? {
+            manifest.outputs.insert(asset, entry);
+        }
+    }
+
+    // Pages left out of this build keep their outputs for the next build that
+    // includes them.
+    let unpublished: Vec<String> = unpublished.keys().map(|path| cache::key(path)).collect();
+    for (page, runs) in &recorded {
+        if unpublished.contains(page) {
+            run_outputs.insert(page.clone(), runs.clone());
+        }
+    }
+    if run_outputs != recorded {
+        run::save_outputs(&outputs_path, &run_outputs)?;
+        println!("{}", outputs_path.display());
+    }
+
+    for stale in previous.outputs.keys() {
+        if !manifest.outputs.contains_key(stale) {
+            remove_output(&args.output, Path::new(stale))?;
+        }
+    }
+    manifest.save(&manifest_path)?;
     Ok(())
 }
+
+/// A page found in the sources, with everything about it that is known
+/// before it is rendered.
+struct PageSource {
+    path: PathBuf,
+    /// `path` relative to the site root.
+    relative: PathBuf,
+    source: String,
+    /// Where the markdown starts in `source`, after the front matter.
+    body_start: usize,
+    meta: front_matter::PageMeta,
+    /// Where the page is written, relative to the output directory.
+    output_relative: PathBuf,
+    title: String,
+    url: String,
+    /// Prefix for relative URLs in the page, from [`relocation`].
+    relocation: String,
+    scheduled: bool,
+    /// The paragraph giving the date the page was published, if it has one.
+    date_line: Option<extract::DateLine>,
+}
+
+/// What rendering any page needs, shared by the threads doing it.
+struct Site<'a> {
+    args: &'a cli::Args,
+    config: &'a config::Config,
+    templates: &'a template::Templates,
+    previous: &'a cache::Manifest,
+    recorded: &'a run::Outputs,
+    /// Sources left out of the build, with why.
+    unpublished: &'a BTreeMap<PathBuf, String>,
+    config_hash: &'a str,
+    templates_hash: &'a str,
+}
+
+/// The outcome of [`render_source`].
+struct Rendered {
+    /// The lines to log.
+    log: Vec<String>,
+    /// Problems found while converting the page that did not stop it.
+    warnings: Vec<String>,
+    /// The page's manifest entry, which carries its converted HTML.
+    entry: cache::Output,
+    /// The manifest key and entry of the page's examples archive, if it has
+    /// one.
+    examples: Option<(String, cache::Output)>,
+}
+
+/// Converts and renders one page unless the last build already did so with
+/// the same inputs.
+fn render_source(site: &Site, page: &PageSource) -> Result<Rendered, Box<dyn std::error::Error>> {
+    let mut hasher = cache::Hasher::new();
+    hasher.write(&page.source);
+    hasher.write(&page.url);
+    hasher.write(if page.scheduled { "scheduled" } else { "" });
+    let page_hash = hasher.finish();
+    let previous = site
+        .previous
+        .outputs
+        .get(&cache::key(&page.output_relative));
+    let mut entry = cache::Output {
+        source: Some(cache::key(&page.relative)),
+        content: Some(content_hash(
+            &page_hash,
+            &site.args.source,
+            previous.map_or(&[], |previous| &previous.includes),
+        )),
+        templates: Some(site.templates_hash.to_string()),
+        config: Some(site.config_hash.to_string()),
+        html: None,
+        assets: Vec::new(),
+        includes: Vec::new(),
+        runs: BTreeMap::new(),
+    };
+    let examples_relative = examples_path(&page.output_relative);
+    let examples_key = cache::key(&examples_relative);
+    let examples_path = site.args.output.join(&examples_relative);
+    let previous_examples = site
+        .previous
+        .outputs
+        .get(&examples_key)
+        .filter(|_| examples_path.is_file());
+    let cached = previous.filter(|cached| {
+        cached.content == entry.content
+            && cached.config == entry.config
+            && (previous_examples.is_some() || !site.previous.outputs.contains_key(&examples_key))
+    });
+    let mut log = Vec::new();
+    let mut warnings = Vec::new();
+    let mut examples = None;
+    let content = match cached.and_then(|cached| Some((cached.html.clone()?, cached))) {
+        Some((content, cached)) => {
+            entry.assets = cached.assets.clone();
+            entry.includes = cached.includes.clone();
+            entry.runs = cached.runs.clone();
+            examples = previous_examples.map(|previous| (examples_key, previous.clone()));
+            content
+        }
+        None => {
+            let runs = known_runs(site.recorded, Some(&cache::key(&page.relative)), previous);
+            let context = Context {
+                config: site.config,
+                crates: doc_links::Crates::new(&site.config.docs.crates, &page.meta.dependencies),
+                includes: code::Includes {
+                    root: &site.args.source,
+                    dir: page.path.parent().unwrap_or(&site.args.source),
+                },
+                runs: &runs,
+                run: site.args.run,
+                date_line: page.date_line.as_ref(),
+                unpublished: site.unpublished,
+            };
+            let body = &page.source[page.body_start..];
+            let converted = convert(&page.path, &page.source, body, &context)?;
+            warnings = converted.warnings;
+            entry.runs = converted.runs;
+            entry.includes = converted
+                .blocks
+                .iter()
+                .filter_map(|block| block.included.as_deref())
+                .map(|path| cache::key(path.strip_prefix(&site.args.source).unwrap_or(path)))
+                .collect();
+            entry.content = Some(content_hash(&page_hash, &site.args.source, &entry.includes));
+            entry.assets = local_assets(
+                &converted.html,
+                page.relative.parent().unwrap_or(Path::new("")),
+            );
+            let archive = examples::archive(
+                &examples_name(&page.output_relative),
+                &converted.blocks,
+                &site.args.source,
+                &page.meta.dependencies,
+                &site.config.snippets.edition,
+            )
+            .map_err(|e| {
+                let line = page.source[..page.body_start].lines().count() + e.line;
+                format!("{}:{line}: {}", page.path.display(), e.message)
+            })?;
+            if let Some(archive) = archive {
+                let examples_entry = cache::Output {
+                    source: entry.source.clone(),
+                    content: Some(cache::hash(&archive)),
+                    ..Default::default()
+                };
+                if previous_examples.map(|previous| &previous.content)
+                    != Some(&examples_entry.content)
+                {
+                    if let Some(parent) = examples_path.parent() {
+                        std::fs::create_dir_all(parent)?;
+                    }
+                    std::fs::write(&examples_path, archive)?;
+                    log.push(format!(
+                        "{} -> {}",
+                        page.path.display(),
+                        examples_path.display()
+                    ));
+                }
+                examples = Some((examples_key, examples_entry));
+            }
+            html::prefix_root_urls(
+                &html::relocate_relative_urls(&converted.html, &page.relocation),
+                &site.config.base_path,
+            )
+        }
+    };
+
+    let output_path = site.args.output.join(&page.output_relative);
+    let unchanged =
+        cached.is_some_and(|cached| cached.templates == entry.templates) && output_path.is_file();
+    if unchanged {
+        if site.args.verbose {
+            log.insert(0, format!("unchanged {}", page.path.display()));
+        }
+    } else {
+        let html = render_page(
+            site.templates,
+            site.config,
+            &page.path,
+            &page.meta,
+            Page {
+                layout: "page",
+                title: &page.title,
+                url: &page.url,
+                content: &content,
+                examples: examples.as_ref().map(|(key, _)| key.as_str()),
+                scheduled: page.scheduled,
+            },
+            Table::new(),
+        )?;
+        if let Some(parent) = output_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&output_path, html)?;
+        log.insert(
+            0,
+            format!("{} -> {}", page.path.display(), output_path.display()),
+        );
+    }
+    entry.html = Some(content);
+    Ok(Rendered {
+        log,
+        warnings,
+        entry,
+        examples,
+    })
+}
+
+/// Where the examples archive of the page written to `output_relative` goes:
+/// next to it, or inside its directory for a page written as `index.html`.
+fn examples_path(output_relative: &Path) -> PathBuf {
+    match output_relative.file_name() == Some(OsStr::new("index.html")) {
+        true => output_relative.with_file_name("examples.zip"),
+        false => output_relative
+            .with_file_name(format!("{}-examples.zip", examples_name(output_relative))),
+    }
+}
+
+/// The name of the project in the examples archive of the page written to
+/// `output_relative`, from the page's file or directory name.
+fn examples_name(output_relative: &Path) -> String {
+    let name = match output_relative.file_name() == Some(OsStr::new("index.html")) {
+        true => output_relative.parent().and_then(Path::file_name),
+        false => output_relative.file_stem(),
+    };
+    name.map_or_else(
+        || "examples".to_string(),
+        |name| name.to_string_lossy().into_owned(),
+    )
+}
+
+/// Hash of a page's own inputs, `page_hash`, and the current contents of the
+/// files it includes, given relative to `root`.
+fn content_hash(page_hash: &str, root: &Path, includes: &[String]) -> String {
+    let mut hasher = cache::Hasher::new();
+    hasher.write(page_hash);
+    for include in includes {
+        hasher.write(include);
+        hasher.write(&std::fs::read_to_string(root.join(include)).unwrap_or_default());
+    }
+    hasher.finish()
+}
+
+/// What converting a page needs besides its markdown.
+struct Context<'a> {
+    config: &'a config::Config,
+    crates: doc_links::Crates,
+    includes: code::Includes<'a>,
+    /// Outputs of the page's code blocks from earlier builds, by
+    /// [`run::key`].
+    runs: &'a BTreeMap<String, String>,
+    /// Whether code blocks with no output from the last build may be run.
+    run: bool,
+    /// The paragraph giving the page's date, which is shown as configured.
+    date_line: Option<&'a extract::DateLine>,
+    /// Sources left out of the build, with why, which links can't point to.
+    unpublished: &'a BTreeMap<PathBuf, String>,
+}
+
+/// A page's markdown converted by [`convert`].
+struct Converted {
+    html: String,
+    /// Rust paths in inline code that could not be linked, and links to
+    /// pages that are not built.
+    warnings: Vec<String>,
+    blocks: Vec<code::CodeBlock>,
+    /// Outputs of the code blocks that were run, by [`run::key`].
+    runs: BTreeMap<String, String>,
+}
+
+/// Outputs of code blocks run by earlier builds of the page from `source`: those
+/// in the outputs file, and those in the `previous` manifest entry, which may
+/// not have been recorded yet.
+fn known_runs(
+    recorded: &run::Outputs,
+    source: Option<&str>,
+    previous: Option<&cache::Output>,
+) -> BTreeMap<String, String> {
+    let mut runs = previous
+        .map(|previous| previous.runs.clone())
+        .unwrap_or_default();
+    if let Some(recorded) = source.and_then(|source| recorded.get(source)) {
+        runs.extend(recorded.clone());
+    }
+    runs
+}
+
+/// Converts `body`, the markdown after the front matter of `source`, to HTML
+/// with its code blocks run and rendered, its date line marked up and Rust
+/// paths in inline code linked to their documentation.
+fn convert(
+    path: &Path,
+    source: &str,
+    body: &str,
+    context: &Context,
+) -> Result<Converted, Box<dyn std::error::Error>> {
+    let mut html = markdown::to_html_with_options(body, &markdown::Options::gfm())
+        .map_err(|message| message.to_string())?;
+    let front_matter_lines = source[..source.len() - body.len()].lines().count();
+    let mut date_warning = None;
+    if let Some(date_line) = context.date_line {
+        // The date line renders on its own as it does in the page.
+        let paragraph =
+            markdown::to_html_with_options(&date_line.markdown, &markdown::Options::gfm())
+                .map_err(|message| message.to_string())?;
+        let paragraph = paragraph.trim_end();
+        match html.find(paragraph) {
+            Some(start) => html.replace_range(
+                start..start + paragraph.len(),
+                &format!(
+                    "<p>{}</p>",
+                    date_line.date.time_element(&context.config.dates)
+                ),
+            ),
+            None => {
+                date_warning = Some(format!(
+                    "{}:{}: the date `{}` could not be shown as configured",
+                    path.display(),
+                    front_matter_lines + date_line.line,
+                    date_line.text
+                ))
+            }
+        }
+    }
+    let located = |e: code::Error| {
+        let line = front_matter_lines + e.line;
+        format!("{}:{line}: {}", path.display(), e.message)
+    };
+    let edition = &context.config.snippets.edition;
+    let mut blocks = code::blocks(body, &context.includes).map_err(located)?;
+    let mut runs = BTreeMap::new();
+    for block in blocks.iter_mut().filter(|block| run::runnable(block)) {
+        let key = run::key(block, edition);
+        let output = match context.runs.get(&key) {
+            Some(output) => output.clone(),
+            None if !context.run => {
+                return Err(located(code::Error {
+                    line: block.line,
+                    message: format!(
+                        "the block asks to be run and has no output in `{}`; build with `--run` and commit that file",
+                        run::OUTPUTS_FILE_NAME
+                    ),
+                })
+                .into());
+            }
+            None => run::run(block, edition).map_err(|message| {
+                located(code::Error {
+                    line: block.line,
+                    message,
+                })
+            })?,
+        };
+        block.output = Some(output.clone());
+        runs.insert(key, output);
+    }
+    let playground = context
+        .config
+        .snippets
+        .playground
+        .then_some(edition.as_str());
+    let html = code::render(&html, &blocks, playground).map_err(located)?;
+    let (html, warnings) = doc_links::link(body, &html, &context.crates);
+    let page_dir = context
+        .includes
+        .dir
+        .strip_prefix(context.includes.root)
+        .unwrap_or(Path::new(""));
+    let unpublished = unpublished_links(&html, page_dir, context.unpublished)
+        .into_iter()
+        .map(|message| format!("{}: {message}", path.display()));
+    let warnings = date_warning
+        .into_iter()
+        .chain(unpublished)
+        .chain(warnings.into_iter().map(|warning| match warning.line {
+            Some(line) => format!(
+                "{}:{}: {}",
+                path.display(),
+                front_matter_lines + line,
+                warning.message
+            ),
+            None => format!("{}: {}", path.display(), warning.message),
+        }))
+        .collect();
+    Ok(Converted {
+        html,
+        warnings,
+        blocks,
+        runs,
+    })
+}
+
+/// The files under the site root that a page in `page_dir` links to or
+/// embeds, as manifest keys.
+fn local_assets(html: &str, page_dir: &Path) -> Vec<String> {
+    assets::local_references(html)
+        .iter()
+        .filter_map(|reference| assets::resolve(page_dir, reference))
+        .filter(|asset| !asset.as_os_str().is_empty())
+        .map(|asset| cache::key(&asset))
+        .collect()
+}
+
+/// Describes the links in `html`, on a page in `page_dir`, to sources that
+/// are not built, which are left pointing at the markdown.
+fn unpublished_links(
+    html: &str,
+    page_dir: &Path,
+    unpublished: &BTreeMap<PathBuf, String>,
+) -> Vec<String> {
+    assets::local_references(html)
+        .iter()
+        .filter(|reference| reference.ends_with(".md"))
+        .filter_map(|reference| {
+            let target = assets::resolve(page_dir, reference)?;
+            let reason = unpublished.get(&target)?;
+            Some(format!(
+                "unresolved link to `{reference}`: `{}` is {reason}, so it is not built",
+                target.display()
+            ))
+        })
+        .collect()
+}
+
+/// Collects the markdown files under `dir`, leaving out the directories in
+/// `skip` and anything `ignore` matches.
+fn find_pages(
+    args: &cli::Args,
+    dir: &Path,
+    skip: &[PathBuf],
+    ignore: &ignore::Ignore,
+    pages: &mut Vec<PathBuf>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut entries = std::fs::read_dir(dir)?.collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+        let is_page = file_type.is_file() && path.extension() == Some(OsStr::new("md"));
+        if !is_page && !file_type.is_dir() {
+            continue;
+        }
+        if let Some(rule) = ignore.matched(path.strip_prefix(&args.source)?, file_type.is_dir()) {
+            if args.verbose {
+                println!("skipped {}: matches {rule}", path.display());
+            }
+        } else if is_page {
+            pages.push(path);
+        } else if !skip.contains(&path) && !skip.contains(&path.canonicalize()?) {
+            find_pages(args, &path, skip, ignore, pages)?;
+        }
+    }
+    Ok(())
+}
+
+/// Where the page at `relative` in the sources is written, relative to the
+/// output directory. `foo/index.md` is the page bundle `foo/index.html`, and
+/// a slug replaces the file or bundle name.
+fn output_path(relative: &Path, slug: Option<&str>, pretty_urls: bool) -> PathBuf {
+    let dir = relative.parent().unwrap_or(Path::new(""));
+    let stem = relative.file_stem().unwrap_or_default().to_string_lossy();
+    if stem == "index" {
+        return match slug {
+            Some(slug) => dir.with_file_name(slug).join("index.html"),
+            None => dir.join("index.html"),
+        };
+    }
+    let name = slug.unwrap_or(&stem);
+    match pretty_urls {
+        true => dir.join(name).join("index.html"),
+        false => dir.join(format!("{name}.html")),
+    }
+}
+
+/// The URL of the page written to `output_relative`, relative to the site
+/// root. Pretty URLs leave off `index.html`.
+fn url_for(output_relative: &Path, pretty_urls: bool) -> String {
+    let url = cache::key(output_relative);
+    match url.strip_suffix("index.html") {
+        Some(directory) if pretty_urls => directory.to_string(),
+        _ => url,
+    }
+}
+
+/// The prefix that takes a relative URL from `output_dir` back to
+/// `source_dir`, both relative to the site root.
+fn relocation(output_dir: &Path, source_dir: &Path) -> String {
+    let common = output_dir
+        .components()
+        .zip(source_dir.components())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let mut prefix = "../".repeat(output_dir.components().count() - common);
+    for component in source_dir.components().skip(common) {
+        prefix += &component.as_os_str().to_string_lossy();
+        prefix += "/";
+    }
+    prefix
+}
+
+/// Fills the title, date and description that `meta` leaves out with those
+/// `extracted` from the page's markdown. Returns warnings, with their line in
+/// the markdown, for a title or date there that contradicts the front matter.
+fn fill_from_content(
+    meta: &mut front_matter::PageMeta,
+    extracted: &extract::Extracted,
+) -> Vec<(usize, String)> {
+    let mut warnings = Vec::new();
+    if let Some((heading, line)) = &extracted.title {
+        match &meta.title {
+            Some(title) if title.trim() != heading => warnings.push((
+                *line,
+                format!("the heading `{heading}` differs from the front matter's title `{title}`"),
+            )),
+            Some(_) => {}
+            None => meta.title = Some(heading.clone()),
+        }
+    }
+    if let Some(date_line) = &extracted.date {
+        let date = date_line.date;
+        match meta.date {
+            Some(front)
+                if (front.year, front.month, front.day) != (date.year, date.month, date.day) =>
+            {
+                warnings.push((
+                    date_line.line,
+                    format!(
+                        "the date `{}` is {}, which differs from the front matter's date {}",
+                        date_line.text,
+                        date.iso(),
+                        front.iso()
+                    ),
+                ))
+            }
+            Some(_) => {}
+            None => meta.date = Some(date),
+        }
+    }
+    if meta.description.is_none() {
+        meta.description = extracted.description.clone();
+    }
+    warnings
+}
+
+fn output_conflict(first: &Path, second: &Path, output: &Path) -> String {
+    format!(
+        "`{}` and `{}` would both be written to `{}`",
+        first.display(),
+        second.display(),
+        output.display()
+    )
+}
+
+/// Removes the output of earlier builds. Only an empty directory or one an
+/// earlier build left its [`OUTPUT_MARKER`] in is removed, and never one that
+/// holds the sources, so that a mistyped `--output` can't delete anything
+/// else.
+fn clean(source: &Path, output: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    if !output.exists() {
+        return Ok(());
+    }
+    if source.canonicalize()?.starts_with(output.canonicalize()?) {
+        return Err(format!(
+            "refusing to clean `{}` because it contains the source directory",
+            output.display()
+        )
+        .into());
+    }
+    let empty = std::fs::read_dir(output)?.next().is_none();
+    if !empty && !output.join(OUTPUT_MARKER).is_file() {
+        return Err(format!(
+            "refusing to clean `{}` because no earlier build wrote it; remove it or choose \
+             another output directory",
+            output.display()
+        )
+        .into());
+    }
+    std::fs::remove_dir_all(output)?;
+    Ok(())
+}
+
+/// Copies a file referenced by a page from the source directory into the
+/// output, at the same path from the site root, unless the copy from the last
+/// build is still current. References to missing files, pages and ignored
+/// files are left alone and get no manifest entry.
+fn copy_asset(
+    args: &cli::Args,
+    ignore: &ignore::Ignore,
+    previous: Option<&cache::Output>,
+    relative: &str,
+) -> Result<Option<cache::Output>, Box<dyn std::error::Error>> {
+    let from = args.source.join(relative);
+    if !from.is_file() || Path::new(relative).extension() == Some(OsStr::new("md")) {
+        return Ok(None);
+    }
+    if let Some(rule) = ignore.matched_file(Path::new(relative)) {
+        eprintln!(
+            "warning: {} is linked to but not copied, since it matches {rule}",
+            from.display()
+        );
+        return Ok(None);
+    }
+    let to = args.output.join(relative);
+    let entry = cache::Output {
+        source: Some(relative.to_string()),
+        content: Some(cache::hash(&std::fs::read(&from)?)),
+        ..Default::default()
+    };
+    if previous.is_some_and(|previous| previous.content == entry.content) && to.is_file() {
+        if args.verbose {
+            println!("unchanged {}", from.display());
+        }
+        return Ok(Some(entry));
+    }
+    if let Some(parent) = to.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::copy(&from, &to)?;
+    println!("{} -> {}", from.display(), to.display());
+    Ok(Some(entry))
+}
+
+/// Deletes an output the last build wrote and this one did not, along with
+/// any directories that leaves empty.
+fn remove_output(output: &Path, relative: &Path) -> std::io::Result<()> {
+    let path = output.join(relative);
+    if path.is_file() {
+        std::fs::remove_file(&path)?;
+        println!("removed {}", path.display());
+    }
+    let mut dir = relative.parent();
+    while let Some(current) = dir.filter(|dir| !dir.as_os_str().is_empty()) {
+        // Fails, and so stops, at the first directory that is not empty.
+        if std::fs::remove_dir(output.join(current)).is_err() {
+            break;
+        }
+        dir = current.parent();
+    }
+    Ok(())
+}
+
+/// What the generator knows about a page beyond its front matter.
+struct Page<'a> {
+    /// Template used when the front matter does not pick one.
+    layout: &'a str,
+    title: &'a str,
+    /// Path of the page relative to the site root.
+    url: &'a str,
+    content: &'a str,
+    /// Path of the page's examples archive relative to the site root, if it
+    /// has one.
+    examples: Option<&'a str>,
+    /// Whether the page has a publish date still in the future, which only
+    /// happens when building drafts.
+    scheduled: bool,
+}
+
+/// Renders a page with its layout template. `globals` are made available to
+/// the template next to `config` and `page`.
+fn render_page(
+    templates: &template::Templates,
+    config: &config::Config,
+    path: &Path,
+    meta: &front_matter::PageMeta,
+    page: Page,
+    mut globals: Table,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let layout = format!("{}.html", meta.layout.as_deref().unwrap_or(page.layout));
+    if !templates.contains(&layout) {
+        return Err(format!("{}: layout template `{layout}` not found", path.display()).into());
+    }
+    let mut page_value = meta.to_value();
+    let date_text = |date: Option<date::Date>| {
+        date.map_or(Value::Null, |date| {
+            Value::String(date.display(&config.dates))
+        })
+    };
+    page_value.extend([
+        ("date_text".into(), date_text(meta.date)),
+        ("updated_text".into(), date_text(meta.updated)),
+        ("title".into(), Value::String(page.title.to_string())),
+        ("url".into(), Value::String(page.url.to_string())),
+        ("content".into(), Value::String(page.content.to_string())),
+        (
+            "examples".into(),
+            page.examples
+                .map_or(Value::Null, |examples| Value::String(examples.to_string())),
+        ),
+        ("scheduled".into(), Value::Boolean(page.scheduled)),
+    ]);
+    globals.insert("config".into(), config.to_value());
+    globals.insert("page".into(), Value::Table(page_value));
+    Ok(templates.render(&layout, &globals)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::run::TempDir;
+
+    /// Writes `files`, pairs of a path and contents, under `root`.
+    fn write_files(root: &Path, files: &[(&str, &str)]) {
+        for (path, contents) in files {
+            let path = root.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, contents).unwrap();
+        }
+    }
+
+    #[test]
+    fn cleans_only_earlier_output() {
+        let dir = TempDir::new().unwrap();
+        let source = dir.path.join("site");
+        write_files(&source, &[("post.md", "# Post")]);
+
+        let output = dir.path.join("missing");
+        assert!(clean(&source, &output).is_ok());
+
+        let output = dir.path.join("empty");
+        std::fs::create_dir(&output).unwrap();
+        clean(&source, &output).unwrap();
+        assert!(!output.exists());
+
+        let output = dir.path.join("built");
+        write_files(
+            &output,
+            &[(OUTPUT_MARKER, ""), ("index.html", ""), ("a/b.html", "")],
+        );
+        clean(&source, &output).unwrap();
+        assert!(!output.exists());
+
+        let output = dir.path.join("project");
+        write_files(&output, &[("Cargo.toml", "")]);
+        let e = clean(&source, &output).unwrap_err();
+        assert!(e.to_string().contains("no earlier build wrote it"), "{e}");
+        assert!(output.join("Cargo.toml").exists());
+
+        write_files(&dir.path, &[(OUTPUT_MARKER, "")]);
+        let e = clean(&source, &dir.path).unwrap_err();
+        assert!(
+            e.to_string().contains("contains the source directory"),
+            "{e}"
+        );
+        assert!(source.join("post.md").exists());
+    }
+
+    #[test]
+    fn page_paths() {
+        let output = |source, slug, pretty_urls| {
+            cache::key(&output_path(Path::new(source), slug, pretty_urls))
+        };
+        assert_eq!(output("post.md", None, false), "post.html");
+        assert_eq!(output("post.md", None, true), "post/index.html");
+        assert_eq!(output("a/b/post.md", None, false), "a/b/post.html");
+        assert_eq!(output("a/b/post.md", None, true), "a/b/post/index.html");
+        assert_eq!(output("a/post.md", Some("b"), false), "a/b.html");
+        assert_eq!(output("a/post.md", Some("b"), true), "a/b/index.html");
+        // Page bundles keep their directory, which holds their assets.
+        assert_eq!(output("a/c/index.md", None, false), "a/c/index.html");
+        assert_eq!(output("a/c/index.md", None, true), "a/c/index.html");
+        assert_eq!(output("a/c/index.md", Some("b"), false), "a/b/index.html");
+        assert_eq!(output("index.md", None, true), "index.html");
+
+        let url = |output, pretty_urls| url_for(Path::new(output), pretty_urls);
+        assert_eq!(url("post.html", false), "post.html");
+        assert_eq!(url("a/post/index.html", false), "a/post/index.html");
+        assert_eq!(url("a/post/index.html", true), "a/post/");
+        assert_eq!(url("index.html", true), "");
+    }
+
+    #[test]
+    fn relocations() {
+        let prefix =
+            |output_dir, source_dir| relocation(Path::new(output_dir), Path::new(source_dir));
+        assert_eq!(prefix("a", "a"), "");
+        assert_eq!(prefix("a/post", "a"), "../");
+        assert_eq!(prefix("a/b", "a/c"), "../c/");
+        assert_eq!(prefix("a/b", "c"), "../../c/");
+        assert_eq!(prefix("", "c/d"), "c/d/");
+        assert_eq!(
+            html::relocate_relative_urls(
+                r##"<img src="cat.png"> <a href="/x">x</a> <a href="#top">t</a> <a href="https://e.org/">e</a>"##,
+                &prefix("a/post", "a")
+            ),
+            r##"<img src="../cat.png"> <a href="/x">x</a> <a href="#top">t</a> <a href="https://e.org/">e</a>"##
+        );
+    }
+
+    #[test]
+    fn links_to_unpublished_pages() {
+        let unpublished = BTreeMap::from([
+            (PathBuf::from("posts/draft.md"), "a draft".to_string()),
+            (
+                PathBuf::from("later.md"),
+                "scheduled for 2999-01-01".to_string(),
+            ),
+        ]);
+        let html = r#"<a href="draft.md">a</a> <a href="./draft.md#top">b</a>
+<a href="../later.md">c</a> <a href="/posts/draft.md">d</a>
+<a href="published.md">e</a> <a href="draft.html">f</a> <a href="https://example.com/later.md">g</a>"#;
+        assert_eq!(
+            unpublished_links(html, Path::new("posts"), &unpublished),
+            [
+                "unresolved link to `draft.md`: `posts/draft.md` is a draft, so it is not built",
+                "unresolved link to `./draft.md`: `posts/draft.md` is a draft, so it is not built",
+                "unresolved link to `../later.md`: `later.md` is scheduled for 2999-01-01, so it is not built",
+                "unresolved link to `/posts/draft.md`: `posts/draft.md` is a draft, so it is not built",
+            ]
+        );
+    }
+
+    /// Builds the site in `source` into its `dist` directory with `args`.
+    fn build_site(source: &Path, args: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+        let source = source.to_string_lossy();
+        let args = ["--source", &source]
+            .into_iter()
+            .chain(args.iter().copied())
+            .map(str::to_string);
+        build(&cli::Args::parse(args)?)
+    }
+
+    #[test]
+    fn sitemap_lists_indexed_pages() {
+        let dir = TempDir::new().unwrap();
+        write_files(
+            &dir.path,
+            &[
+                ("blog.toml", "base_url = \"https://example.com\"\n"),
+                ("a.md", "+++\ndate = 2024-01-02\n+++\n# A\n"),
+                (
+                    "b.md",
+                    "+++\ndate = 2023-05-01\nupdated = 2024-02-03\n+++\n# B\n",
+                ),
+                ("about.md", "# About\n"),
+                ("hidden.md", "+++\nnoindex = true\n+++\n# Hidden\n"),
+                (
+                    "draft.md",
+                    "+++\ndate = 2025-01-01\ndraft = true\n+++\n# Draft\n",
+                ),
+                ("later.md", "+++\ndate = 2999-01-01\n+++\n# Later\n"),
+            ],
+        );
+        build_site(&dir.path, &[]).unwrap();
+        let sitemap = std::fs::read_to_string(dir.path.join("dist/sitemap.xml")).unwrap();
+        // The index changed when its newest post did.
+        assert_eq!(
+            sitemap,
+            r#"<?xml version="1.0" encoding="utf-8"?>
+<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+  <url>
+    <loc>https://example.com/</loc>
+    <lastmod>2024-02-03</lastmod>
+  </url>
+  <url>
+    <loc>https://example.com/a.html</loc>
+    <lastmod>2024-01-02</lastmod>
+  </url>
+  <url>
+    <loc>https://example.com/about.html</loc>
+  </url>
+  <url>
+    <loc>https://example.com/b.html</loc>
+    <lastmod>2024-02-03</lastmod>
+  </url>
+</urlset>
+"#
+        );
+
+        // Drafts and scheduled posts are previewed, but never indexed.
+        build_site(&dir.path, &["--drafts"]).unwrap();
+        let drafts = std::fs::read_to_string(dir.path.join("dist/sitemap.xml")).unwrap();
+        assert!(dir.path.join("dist/draft.html").is_file());
+        assert!(dir.path.join("dist/later.html").is_file());
+        assert!(!drafts.contains("draft.html") && !drafts.contains("later.html"));
+    }
+
+    #[test]
+    fn copies_only_assets_that_are_not_ignored() {
+        let dir = TempDir::new().unwrap();
+        write_files(
+            &dir.path,
+            &[
+                (
+                    "post.md",
+                    "![a](img/a.png) ![x](_private/x.png) ![b](notes/b.png)\n",
+                ),
+                ("img/a.png", "a"),
+                ("_private/x.png", "x"),
+                ("notes/b.png", "b"),
+                (ignore::FILE_NAME, "notes/\n"),
+            ],
+        );
+        build_site(&dir.path, &[]).unwrap();
+        let output = dir.path.join("dist");
+        assert!(output.join("img/a.png").is_file());
+        assert!(!output.join("_private").exists());
+        assert!(!output.join("notes").exists());
+    }
+
+    #[test]
+    fn drafts_and_scheduled_posts() {
+        let dir = TempDir::new().unwrap();
+        write_files(
+            &dir.path,
+            &[
+                ("blog.toml", "base_url = \"https://example.com\"\n"),
+                ("post.md", "+++\ndate = 2024-01-02\n+++\n# Post\n"),
+                (
+                    "draft.md",
+                    "+++\ndate = 2024-01-03\ndraft = true\n+++\n# Draft\n",
+                ),
+                ("later.md", "---\ndate: 2999-01-01\n---\n# Later\n"),
+                ("soon.md", "+++\ndate = 2999-01-01T00:00:00Z\n+++\n# Soon\n"),
+            ],
+        );
+        let output = dir.path.join("dist");
+        let read = |name: &str| std::fs::read_to_string(output.join(name)).unwrap();
+
+        build_site(&dir.path, &[]).unwrap();
+        assert!(output.join("post.html").is_file());
+        for name in ["draft", "later", "soon"] {
+            assert!(!output.join(format!("{name}.html")).exists(), "{name}");
+            for listing in ["index.html", "feed.xml", "rss.xml", "feed.json"] {
+                assert!(
+                    !read(listing).contains(&format!("{name}.html")),
+                    "{name} in {listing}"
+                );
+            }
+        }
+        assert!(!read("post.html").contains("DRAFT"));
+
+        // Previews include both, with a banner and kept out of search
+        // engines.
+        build_site(&dir.path, &["--drafts"]).unwrap();
+        for name in ["draft", "later", "soon"] {
+            let page = read(&format!("{name}.html"));
+            assert!(
+                page.contains(r#"<div class="draft-banner">DRAFT</div>"#),
+                "{name}"
+            );
+            assert!(
+                page.contains(r#"<meta name="robots" content="noindex" />"#),
+                "{name}"
+            );
+            assert!(
+                read("index.html").contains(&format!("{name}.html")),
+                "{name}"
+            );
+        }
+        assert!(!read("post.html").contains("DRAFT"));
+
+        // A production build removes them again.
+        build_site(&dir.path, &[]).unwrap();
+        assert!(!output.join("draft.html").exists());
+        assert!(!read("index.html").contains("draft.html"));
+    }
+
+    #[test]
+    fn incremental_builds() {
+        let dir = TempDir::new().unwrap();
+        write_files(
+            &dir.path,
+            &[
+                ("a.md", "# A\n"),
+                ("posts/b/index.md", "# B\n\n![cat](cat.png)\n"),
+                ("posts/b/cat.png", "cat"),
+            ],
+        );
+        let output = dir.path.join("dist");
+        build_site(&dir.path, &[]).unwrap();
+        assert!(output.join("posts/b/cat.png").is_file());
+
+        // Outputs no source produces any more are removed, with the
+        // directories they leave empty.
+        std::fs::write(output.join("a.html"), "edited").unwrap();
+        std::fs::remove_dir_all(dir.path.join("posts")).unwrap();
+        build_site(&dir.path, &[]).unwrap();
+        assert!(!output.join("posts").exists());
+        // Outputs are rewritten only when their inputs change.
+        assert_eq!(
+            std::fs::read_to_string(output.join("a.html")).unwrap(),
+            "edited"
+        );
+        write_files(&dir.path, &[("a.md", "# A again\n")]);
+        build_site(&dir.path, &[]).unwrap();
+        assert!(std::fs::read_to_string(output.join("a.html"))
+            .unwrap()
+            .contains("A again"));
+
+        // Without a usable manifest, everything is built from scratch.
+        std::fs::write(output.join("a.html"), "edited").unwrap();
+        std::fs::write(output.join("stray.html"), "").unwrap();
+        std::fs::write(dir.path.join(cache::FILE_NAME), "{ corrupt").unwrap();
+        build_site(&dir.path, &[]).unwrap();
+        assert!(std::fs::read_to_string(output.join("a.html"))
+            .unwrap()
+            .contains("A again"));
+        assert!(!output.join("stray.html").exists());
+    }
+
+    #[test]
+    fn recorded_outputs() {
+        let dir = TempDir::new().unwrap();
+        write_files(
+            &dir.path,
+            &[("a.md", "# A\n\n```sh run\necho hello\n```\n")],
+        );
+        let recorded = dir.path.join(run::OUTPUTS_FILE_NAME);
+
+        let error = build_site(&dir.path, &[]).unwrap_err().to_string();
+        assert!(
+            error.contains("has no output in `.blog-outputs.json`"),
+            "{error}"
+        );
+        assert!(!recorded.exists());
+
+        build_site(&dir.path, &["--run"]).unwrap();
+        let outputs = run::load_outputs(&recorded).unwrap();
+        assert_eq!(
+            outputs["a.md"].values().collect::<Vec<_>>(),
+            [&"hello\n".to_string()]
+        );
+
+        // A fresh checkout, with only the recorded outputs, builds without
+        // running anything.
+        std::fs::remove_file(dir.path.join(cache::FILE_NAME)).unwrap();
+        std::fs::remove_dir_all(dir.path.join("dist")).unwrap();
+        build_site(&dir.path, &[]).unwrap();
+        assert!(std::fs::read_to_string(dir.path.join("dist/a.html"))
+            .unwrap()
+            .contains("hello"));
+
+        // Outputs of blocks that are gone are dropped.
+        write_files(&dir.path, &[("a.md", "# A\n")]);
+        build_site(&dir.path, &[]).unwrap();
+        assert!(!recorded.exists());
+    }
+}
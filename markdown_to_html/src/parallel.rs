@@ -0,0 +1,74 @@
+//! Spreading work across every core.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Applies `f` to every item on a pool of threads, one per core, and returns
+/// the results in the order of `items`. Each thread claims the next item as
+/// soon as it is done with the last, so a few slow items do not hold up the
+/// others.
+pub fn map<T: Sync, R: Send>(items: &[T], f: impl Fn(&T) -> R + Sync) -> Vec<R> {
+    let threads = std::thread::available_parallelism()
+        .map_or(1, |threads| threads.get())
+        .min(items.len());
+    let next = AtomicUsize::new(0);
+    let mut results: Vec<(usize, R)> = std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut done = Vec::new();
+                    loop {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(item) = items.get(i) else {
+                            return done;
+                        };
+                        done.push((i, f(item)));
+                    }
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|worker| {
+                worker
+                    .join()
+                    .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+            })
+            .collect()
+    });
+    results.sort_by_key(|(i, _)| *i);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Duration;
+
+    #[test]
+    fn keeps_order() {
+        let items: Vec<u64> = (0..64).collect();
+        // Early items finish last.
+        let results = map(&items, |&i| {
+            std::thread::sleep(Duration::from_micros((64 - i) * 50));
+            i * 2
+        });
+        assert_eq!(results, items.iter().map(|i| i * 2).collect::<Vec<_>>());
+        assert!(map(&[] as &[u64], |&i| i).is_empty());
+    }
+
+    #[test]
+    fn reports_panics() {
+        let items: Vec<usize> = (0..16).collect();
+        let panic = std::panic::catch_unwind(|| {
+            map(&items, |&i| {
+                if i == 3 {
+                    panic!("job {i} failed");
+                }
+            })
+        })
+        .unwrap_err();
+        // The job's own panic reaches the caller.
+        assert_eq!(panic.downcast_ref::<String>().unwrap(), "job 3 failed");
+    }
+}
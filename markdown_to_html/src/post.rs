@@ -0,0 +1,20 @@
+//! Posts collected while rendering, for the site-wide pages built from them.
+
+use crate::date::Date;
+
+/// A page with a date. Posts are listed on the index and in the feeds.
+pub struct Post {
+    pub title: String,
+    pub date: Date,
+    pub updated: Option<Date>,
+    pub description: Option<String>,
+    pub author: Option<String>,
+    pub tags: Vec<String>,
+    /// Image for the post, from its front matter or else the first image in
+    /// its content.
+    pub image: Option<String>,
+    /// Path of the rendered page relative to the site root.
+    pub url: String,
+    /// Rendered body, with root-relative links already under the base path.
+    pub content: String,
+}
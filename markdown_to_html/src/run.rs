@@ -0,0 +1,512 @@
+//! Running code blocks at build time, so the output shown under them is
+//! always what the code really prints.
+//!
+//! A `console` block marked `run` runs the commands after its `$ ` prompts
+//! in one `sh`, so a `cd` or `export` carries over to the commands after it.
+//! `sh`, `bash` and `zsh` blocks marked `run` run as scripts, and a `rust`
+//! block marked `output` or `run` is compiled with `rustc` the way
+//! `check-snippets` compiles it, then run. Each block runs in a new temporary
+//! directory with an environment of little more than `PATH`, which keeps
+//! blocks from depending on where the site is built.
+//!
+//! Blocks run in a sandbox made with `bwrap`, or with `unshare` where that
+//! is missing: in new namespaces without a network, with every file system
+//! read-only but the block's directory, and with no processes outside it.
+//! Building fails if neither tool can make one, rather than run blocks
+//! without it. That still trusts blocks with everything the building user
+//! can read, so they also only run when the build is given `--run`, which
+//! cannot be set from the site's configuration, and a page with a block that
+//! asks to be run otherwise fails to build rather than go out without its
+//! output.
+//!
+//! Outputs are recorded in [`OUTPUTS_FILE_NAME`] at the site root, by page
+//! and under a hash of the block, so a block only runs again when it changes
+//! and outputs already there are used without `--run`. The file is meant to
+//! be committed with the posts: the publish workflow builds without `--run`,
+//! so a block whose output is not recorded there fails the deploy instead of
+//! running on CI.
+
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use crate::cache;
+use crate::code::CodeBlock;
+use crate::json;
+use crate::snippets;
+use crate::value::{Table, Value};
+
+/// Where the outputs of blocks that were run are recorded, next to the
+/// sources.
+pub const OUTPUTS_FILE_NAME: &str = ".blog-outputs.json";
+
+/// Outputs of blocks that were run, by the page they are on, relative to the
+/// site root, and then by [`key`].
+pub type Outputs = BTreeMap<String, BTreeMap<String, String>>;
+
+/// Variables passed on to the commands, which toolchains need to be found.
+const KEPT_VARIABLES: [&str; 5] = [
+    "PATH",
+    "HOME",
+    "CARGO_HOME",
+    "RUSTUP_HOME",
+    "RUSTUP_TOOLCHAIN",
+];
+
+/// How long a block may run before it is stopped.
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Run by `sh` inside the namespaces `unshare` makes, with the block's
+/// directory and then the command as arguments: makes every mount but the
+/// directory read-only, keeping the flags a user namespace may not drop, then
+/// runs the command in the directory's `work`. Fails with 125, as `bwrap`
+/// does, when it cannot.
+const UNSHARE_SETUP: &str = r#"dir=$1
+shift
+mount --bind "$dir" "$dir" || exit 125
+while read -r _ target _ options _; do
+  [ "$target" = "$dir" ] && continue
+  flags=remount,bind,ro
+  for option in nosuid nodev noexec; do
+    case ",$options," in *",$option,"*) flags=$flags,$option ;; esac
+  done
+  mount -o "$flags" "$target" 2>/dev/null || exit 125
+done < /proc/self/mounts
+cd "$dir/work" || exit 125
+exec "$@"
+"#;
+
+/// Whether `block` asks to be run.
+pub fn runnable(block: &CodeBlock) -> bool {
+    let flag = |name: &str| block.flags.iter().any(|flag| flag == name);
+    match block.language.as_deref() {
+        Some("console" | "sh" | "bash" | "zsh") => flag("run"),
+        Some("rust") => flag("run") || flag("output"),
+        _ => false,
+    }
+}
+
+/// Identifies what running `block` with `edition` as the default edition
+/// would do. Only Rust blocks depend on the edition.
+pub fn key(block: &CodeBlock, edition: &str) -> String {
+    let mut hasher = cache::Hasher::new();
+    let language = block.language.as_deref().unwrap_or_default();
+    hasher.write(language);
+    if language == "rust" {
+        hasher.write(block.edition().unwrap_or(edition));
+    }
+    hasher.write(&block.code);
+    hasher.finish()
+}
+
+/// Loads the outputs recorded at `path`, or none if there is no such file.
+pub fn load_outputs(path: &Path) -> Result<Outputs, String> {
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Outputs::new()),
+        Err(e) => return Err(format!("{}: {e}", path.display())),
+    };
+    let invalid = || {
+        format!(
+            "{}: expected a table of pages, each a table of outputs",
+            path.display()
+        )
+    };
+    let Value::Table(pages) =
+        json::parse(&text).map_err(|e| format!("{}:{}: {}", path.display(), e.line, e.message))?
+    else {
+        return Err(invalid());
+    };
+    pages
+        .into_iter()
+        .map(|(page, outputs)| {
+            let Value::Table(outputs) = outputs else {
+                return Err(invalid());
+            };
+            let outputs = outputs
+                .into_iter()
+                .map(|(key, output)| match output {
+                    Value::String(output) => Ok((key, output)),
+                    _ => Err(invalid()),
+                })
+                .collect::<Result<_, _>>()?;
+            Ok((page, outputs))
+        })
+        .collect()
+}
+
+/// Records `outputs` at `path`, removing the file when there are none.
+pub fn save_outputs(path: &Path, outputs: &Outputs) -> std::io::Result<()> {
+    if outputs.is_empty() {
+        return match std::fs::remove_file(path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        };
+    }
+    let pages = outputs
+        .iter()
+        .map(|(page, outputs)| {
+            let outputs = outputs
+                .iter()
+                .map(|(key, output)| (key.clone(), Value::String(output.clone())))
+                .collect();
+            (page.clone(), Value::Table(outputs))
+        })
+        .collect::<Table>();
+    std::fs::write(path, json::to_string_pretty(&Value::Table(pages)))
+}
+
+/// Runs `block` and returns everything it printed, with standard output and
+/// error interleaved as in a terminal. A command that fails is an error.
+pub fn run(block: &CodeBlock, edition: &str) -> Result<String, String> {
+    let dir = TempDir::new().map_err(|e| format!("cannot run the block: {e}"))?;
+    let write = |name: &str, contents: &str| {
+        let path = dir.path.join(name);
+        std::fs::write(&path, contents)
+            .map(|()| path)
+            .map_err(|e| format!("cannot run the block: {e}"))
+    };
+    match block.language.as_deref() {
+        Some("console") => {
+            let commands = commands(&block.code);
+            let script = write("transcript", &console_script(&commands))?;
+            let failed = dir.path.join("failed");
+            dir.execute("sh", &[script.as_ref(), failed.as_ref()], TIMEOUT)
+                .map_err(|e| {
+                    let command = std::fs::read_to_string(&failed)
+                        .ok()
+                        .and_then(|index| commands.get(index.trim().parse::<usize>().ok()?));
+                    match command {
+                        Some(command) => format!("`{command}` {e}"),
+                        None => format!("the transcript {e}"),
+                    }
+                })
+        }
+        Some("rust") => {
+            let source = write("main.rs", &snippets::program(&block.code))?;
+            let executable = dir.path.join("main");
+            let edition = block.edition().unwrap_or(edition);
+            dir.execute(
+                "rustc",
+                &[
+                    "--edition".as_ref(),
+                    edition.as_ref(),
+                    "-o".as_ref(),
+                    executable.as_ref(),
+                    source.as_ref(),
+                ],
+                TIMEOUT,
+            )
+            .map_err(|e| format!("failed to compile: rustc {e}"))?;
+            dir.execute(&executable, &[], TIMEOUT)
+                .map_err(|e| format!("the program {e}"))
+        }
+        Some(shell) => {
+            let script = write("script", &block.code)?;
+            dir.execute(shell, &[script.as_ref()], TIMEOUT)
+                .map_err(|e| format!("the script {e}"))
+        }
+        None => Ok(String::new()),
+    }
+}
+
+/// The commands in a console transcript: the lines after a `$ ` prompt.
+fn commands(transcript: &str) -> Vec<&str> {
+    transcript
+        .lines()
+        .filter_map(|line| line.strip_prefix("$ "))
+        .collect()
+}
+
+/// A script running `commands` one after another in the same shell, which
+/// stops at the first that fails and writes its index to the file named by
+/// its first argument.
+fn console_script(commands: &[&str]) -> String {
+    let mut script = "__failed=$1\nshift\n".to_string();
+    for (index, command) in commands.iter().enumerate() {
+        script += command;
+        script += &format!(
+            "\n__status=$?\n\
+             if [ \"$__status\" -ne 0 ]; then echo {index} > \"$__failed\"; exit \"$__status\"; fi\n"
+        );
+    }
+    script
+}
+
+/// A tool that runs commands in a sandbox.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Sandbox {
+    Bubblewrap,
+    Unshare,
+}
+
+impl Sandbox {
+    /// The first sandbox that works here, found by running `true` in each.
+    fn find() -> Result<Sandbox, String> {
+        static FOUND: OnceLock<Result<Sandbox, String>> = OnceLock::new();
+        FOUND
+            .get_or_init(|| {
+                let dir = TempDir::new().map_err(|e| format!("cannot run the block: {e}"))?;
+                [Sandbox::Bubblewrap, Sandbox::Unshare]
+                    .into_iter()
+                    .find(|sandbox| {
+                        sandbox
+                            .command(&dir.path, "true".as_ref(), &[])
+                            .stdout(Stdio::null())
+                            .stderr(Stdio::null())
+                            .status()
+                            .is_ok_and(|status| status.success())
+                    })
+                    .ok_or_else(|| {
+                        "cannot run the block: running blocks needs `bwrap`, or `unshare` \
+                         with user namespaces enabled"
+                            .to_string()
+                    })
+            })
+            .clone()
+    }
+
+    /// A command running `program` with `args` in this sandbox, in the `work`
+    /// subdirectory of `dir`, the only place it may write to.
+    fn command(self, dir: &Path, program: &OsStr, args: &[&OsStr]) -> Command {
+        let mut command;
+        match self {
+            Sandbox::Bubblewrap => {
+                command = Command::new("bwrap");
+                command
+                    .args(["--ro-bind", "/", "/", "--dev", "/dev", "--proc", "/proc"])
+                    .args(["--tmpfs", "/tmp", "--unshare-all", "--die-with-parent"])
+                    .arg("--bind")
+                    .args([dir, dir])
+                    .arg("--chdir")
+                    .arg(dir.join("work"))
+                    .arg("--");
+            }
+            Sandbox::Unshare => {
+                command = Command::new("unshare");
+                command
+                    .args(["--user", "--map-root-user", "--net", "--mount", "--pid"])
+                    .args(["--fork", "--kill-child", "--mount-proc", "--"])
+                    .args(["sh", "-c", UNSHARE_SETUP, "sh"])
+                    .arg(dir);
+            }
+        }
+        command.arg(program).args(args);
+        command
+    }
+}
+
+/// A temporary directory to run a block in, removed once it is dropped.
+/// Commands run in its `work` subdirectory, so that the files around them
+/// stay out of their way.
+pub(crate) struct TempDir {
+    pub(crate) path: PathBuf,
+}
+
+impl TempDir {
+    pub(crate) fn new() -> std::io::Result<Self> {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "markdown_to_html-{}-{}",
+            std::process::id(),
+            COUNT.fetch_add(1, Ordering::Relaxed)
+        ));
+        if path.exists() {
+            std::fs::remove_dir_all(&path)?;
+        }
+        std::fs::create_dir_all(path.join("work"))?;
+        Ok(TempDir { path })
+    }
+
+    /// Runs `program` with `args` in a sandbox and returns its output,
+    /// stopping it after `timeout`. The error describes how it failed, to
+    /// follow the command's name.
+    fn execute(
+        &self,
+        program: impl AsRef<OsStr>,
+        args: &[&OsStr],
+        timeout: Duration,
+    ) -> Result<String, String> {
+        let sandbox = Sandbox::find()?;
+        let log_path = self.path.join("output");
+        let log = std::fs::File::create(&log_path).map_err(|e| format!("could not start: {e}"))?;
+        let stderr = log
+            .try_clone()
+            .map_err(|e| format!("could not start: {e}"))?;
+        let temp = self.path.join("tmp");
+        std::fs::create_dir_all(&temp).map_err(|e| format!("could not start: {e}"))?;
+        let mut command = sandbox.command(&self.path, program.as_ref(), args);
+        command
+            .env_clear()
+            .envs(
+                KEPT_VARIABLES
+                    .iter()
+                    .filter_map(|name| Some((name, std::env::var_os(name)?))),
+            )
+            .env("TMPDIR", temp)
+            .stdin(Stdio::null())
+            .stdout(log)
+            .stderr(stderr);
+        let mut child = command
+            .spawn()
+            .map_err(|e| format!("could not start: {e}"))?;
+        let started = Instant::now();
+        let status = loop {
+            match child.try_wait().map_err(|e| e.to_string())? {
+                Some(status) => break status,
+                None if started.elapsed() > timeout => {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    return Err(format!("took longer than {} seconds", timeout.as_secs()));
+                }
+                None => std::thread::sleep(Duration::from_millis(10)),
+            }
+        };
+        let output = std::fs::read(&log_path).map_err(|e| e.to_string())?;
+        let output = String::from_utf8_lossy(&output).into_owned();
+        match status.success() {
+            true => Ok(output),
+            false => Err(format!("exited with {status}\n{output}")),
+        }
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code::{blocks, Includes};
+
+    fn block(markdown: &str) -> CodeBlock {
+        let includes = Includes {
+            root: Path::new("."),
+            dir: Path::new("."),
+        };
+        blocks(markdown, &includes).unwrap().remove(0)
+    }
+
+    #[test]
+    fn runnable_blocks() {
+        for (info, expected) in [
+            ("console,run", true),
+            ("console", false),
+            ("sh run", true),
+            ("bash,run", true),
+            ("zsh,run", true),
+            ("sh", false),
+            ("rust,run", true),
+            ("rust,output", true),
+            ("rust,no_run", false),
+            ("rust", false),
+            ("python,run", false),
+            ("", false),
+        ] {
+            let markdown = format!("```{info}\necho hello\n```\n");
+            assert_eq!(runnable(&block(&markdown)), expected, "{info}");
+        }
+    }
+
+    #[test]
+    fn keys() {
+        let key_of = |markdown: &str, edition| key(&block(markdown), edition);
+        let rust = "```rust,run\nfn main() {}\n```\n";
+        assert_eq!(key_of(rust, "2021"), key_of(rust, "2021"));
+        for (markdown, edition) in [
+            (rust, "2018"),
+            ("```rust,run,edition2018\nfn main() {}\n```\n", "2021"),
+            ("```rust,run\nfn main() { }\n```\n", "2021"),
+            ("```sh,run\nfn main() {}\n```\n", "2021"),
+        ] {
+            assert_ne!(
+                key_of(markdown, edition),
+                key_of(rust, "2021"),
+                "{markdown}"
+            );
+        }
+        // The default edition only matters to blocks that use it.
+        let sh = "```sh,run\necho hello\n```\n";
+        let explicit = "```rust,run,edition2018\nfn main() {}\n```\n";
+        assert_eq!(key_of(sh, "2018"), key_of(sh, "2021"));
+        assert_eq!(key_of(explicit, "2018"), key_of(explicit, "2021"));
+    }
+
+    #[test]
+    fn transcripts() {
+        assert_eq!(
+            commands("$ cd src\n$ ls\nmain.rs\n\n$ echo $HOME\n"),
+            ["cd src", "ls", "echo $HOME"]
+        );
+
+        // Commands share a shell, and the output of all of them is kept.
+        let console = "```console,run\n$ mkdir a && cd a\n$ export NAME=b\n$ touch $NAME\n\
+                       $ greet() { echo \"hello $1\"; }\n$ greet $(ls)\nhello b\n```\n";
+        assert_eq!(run(&block(console), "2021"), Ok("hello b\n".to_string()));
+
+        // A command that fails stops the transcript and is named in the error.
+        let console = "```console,run\n$ echo one\n$ false\n$ echo three\n```\n";
+        let error = run(&block(console), "2021").unwrap_err();
+        assert!(error.starts_with("`false` exited with"), "{error}");
+        assert!(error.ends_with("\none\n"), "{error}");
+    }
+
+    #[test]
+    fn timeout() {
+        let dir = TempDir::new().unwrap();
+        let started = Instant::now();
+        let error = dir
+            .execute("sleep", &["10".as_ref()], Duration::from_millis(100))
+            .unwrap_err();
+        assert!(error.starts_with("took longer than"), "{error}");
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn sandbox() {
+        let script = "```sh,run\necho hi > file && cat file\n\
+                      touch /tmp/file 2>/dev/null || echo read-only\n```\n";
+        assert_eq!(
+            run(&block(script), "2021"),
+            Ok("hi\nread-only\n".to_string())
+        );
+    }
+
+    #[test]
+    fn outputs() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path.join(OUTPUTS_FILE_NAME);
+        assert_eq!(load_outputs(&path), Ok(Outputs::new()));
+
+        let outputs = Outputs::from([(
+            "posts/a.md".to_string(),
+            BTreeMap::from([("0123".to_string(), "hello\n\"world\"\n".to_string())]),
+        )]);
+        save_outputs(&path, &outputs).unwrap();
+        assert_eq!(load_outputs(&path), Ok(outputs));
+        save_outputs(&path, &Outputs::new()).unwrap();
+        assert!(!path.exists());
+
+        for text in ["[]", "{\"a.md\": \"hello\"}", "{\"a.md\": {\"0123\": 1}}"] {
+            std::fs::write(&path, text).unwrap();
+            assert_eq!(
+                load_outputs(&path),
+                Err(format!(
+                    "{}: expected a table of pages, each a table of outputs",
+                    path.display()
+                ))
+            );
+        }
+        std::fs::write(&path, "{\n\"a.md\": }").unwrap();
+        assert!(load_outputs(&path)
+            .unwrap_err()
+            .starts_with(&format!("{}:2: ", path.display())));
+    }
+}
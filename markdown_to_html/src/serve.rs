@@ -0,0 +1,479 @@
+//! The `serve` command: a development server that rebuilds the site when a
+//! source changes and reloads open pages.
+//!
+//! Pages are served under the configured base path, as they are once
+//! published. Every HTML response gets a small script that listens for
+//! server-sent events on [`EVENTS_PATH`]: `reload` after a successful build
+//! and `build-error` with the message after a failed one, which is shown over
+//! the page until the next successful build.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, SystemTime};
+
+use crate::{assets, cache, cli, config, ignore};
+
+const EVENTS_PATH: &str = "/__livereload";
+
+/// How often the sources are checked for changes.
+const POLL_INTERVAL: Duration = Duration::from_millis(300);
+
+/// How long an idle event stream waits before sending a comment, so that
+/// dropped connections are noticed.
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+const LIVE_RELOAD_SCRIPT: &str = r#"<script>
+(() => {
+    const events = new EventSource("/__livereload");
+    events.addEventListener("reload", () => location.reload());
+    events.addEventListener("build-error", (event) => {
+        let overlay = document.getElementById("__livereload-error");
+        if (!overlay) {
+            overlay = document.createElement("pre");
+            overlay.id = "__livereload-error";
+            overlay.style.cssText = "position: fixed; inset: 0; z-index: 2147483647; margin: 0; padding: 2em; overflow: auto; white-space: pre-wrap; font: 14px/1.5 monospace; color: #fff; background: rgba(24, 0, 0, 0.92);";
+            document.body.appendChild(overlay);
+        }
+        overlay.textContent = "Build failed\n\n" + event.data;
+    });
+})();
+</script>
+"#;
+
+/// The outcome of the latest build, shared with the connections waiting on
+/// it.
+struct Status {
+    /// Counts finished builds, so waiting connections can tell a new one.
+    build: u64,
+    error: Option<String>,
+}
+
+type Shared = Arc<(Mutex<Status>, Condvar)>;
+
+/// Builds the site, then serves it on `port` while rebuilding on every
+/// change. Only returns if the server cannot start.
+pub fn serve(mut args: cli::Args, port: u16) -> Result<(), Box<dyn std::error::Error>> {
+    let config = config::Config::load(&args.config, &args.overrides)?;
+    let base_path = config.base_path;
+    // Feeds, canonical links and the like point at the local server.
+    args.overrides.push((
+        "base_url".to_string(),
+        format!("\"http://localhost:{port}{base_path}\""),
+    ));
+    let listener = TcpListener::bind(("127.0.0.1", port))?;
+    let address = format!("http://localhost:{port}{base_path}/");
+
+    let shared: Shared = Arc::new((
+        Mutex::new(Status {
+            build: 0,
+            error: None,
+        }),
+        Condvar::new(),
+    ));
+    let output = args.output.clone();
+    let server_shared = Arc::clone(&shared);
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let output = output.clone();
+            let base_path = base_path.clone();
+            let shared = Arc::clone(&server_shared);
+            std::thread::spawn(move || {
+                // Errors only mean the browser went away.
+                let _ = handle(stream, &output, &base_path, &shared);
+            });
+        }
+    });
+
+    let mut snapshot = BTreeMap::new();
+    // Kept through failed builds, which write no manifest, so that fixing an
+    // included file rebuilds the site.
+    let mut included = BTreeSet::new();
+    loop {
+        let current = watched_files(&args, &included);
+        if current != snapshot {
+            let first = snapshot.is_empty();
+            snapshot = current;
+            rebuild(&args, &shared);
+            if let Some(files) = included_files(&args) {
+                included = files;
+            }
+            if first {
+                println!("serving on {address}");
+            }
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+fn rebuild(args: &cli::Args, shared: &Shared) {
+    let result = crate::build(args);
+    if let Err(error) = &result {
+        eprintln!("error: {error}");
+    }
+    let (status, changed) = &**shared;
+    let mut status = status.lock().unwrap();
+    status.build += 1;
+    status.error = result.err().map(|error| error.to_string());
+    changed.notify_all();
+}
+
+/// The modification time and size of every file that can affect the site:
+/// the configuration, `.blogignore`, everything under the source directory
+/// that is not ignored or part of the output, and the `included` files, which
+/// may be ignored themselves.
+fn watched_files(
+    args: &cli::Args,
+    included: &BTreeSet<PathBuf>,
+) -> BTreeMap<PathBuf, (SystemTime, u64)> {
+    let patterns = config::Config::load(&args.config, &args.overrides)
+        .map(|config| config.ignore)
+        .unwrap_or_default();
+    let ignore = ignore::Ignore::load(&args.source, &patterns).ok();
+    let output = args.output.canonicalize().ok();
+    let mut files = BTreeMap::new();
+    let always = [args.config.clone(), args.source.join(ignore::FILE_NAME)];
+    for path in always.into_iter().chain(included.iter().cloned()) {
+        if let Ok(metadata) = std::fs::metadata(&path) {
+            files.insert(path, stamp(&metadata));
+        }
+    }
+    let mut pending = vec![args.source.clone()];
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            let relative = path.strip_prefix(&args.source).unwrap_or(&path);
+            let ignored = ignore
+                .as_ref()
+                .is_some_and(|ignore| ignore.matched(relative, metadata.is_dir()).is_some());
+            if ignored || (output.is_some() && path.canonicalize().ok() == output) {
+                continue;
+            }
+            if metadata.is_dir() {
+                pending.push(path);
+            } else {
+                files.insert(path, stamp(&metadata));
+            }
+        }
+    }
+    files
+}
+
+/// The files code blocks included in the last successful build, from its
+/// manifest.
+fn included_files(args: &cli::Args) -> Option<BTreeSet<PathBuf>> {
+    let output = args.output.canonicalize().ok()?;
+    let manifest = cache::Manifest::load(
+        &args.source.join(cache::FILE_NAME),
+        &output.to_string_lossy(),
+    )?;
+    Some(
+        manifest
+            .outputs
+            .values()
+            .flat_map(|output| &output.includes)
+            .map(|include| args.source.join(include))
+            .collect(),
+    )
+}
+
+fn stamp(metadata: &std::fs::Metadata) -> (SystemTime, u64) {
+    let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+    (modified, metadata.len())
+}
+
+/// Answers one request: the event stream, or a file from the output.
+fn handle(
+    mut stream: TcpStream,
+    output: &Path,
+    base_path: &str,
+    shared: &Shared,
+) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+    }
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    let target = parts.next().unwrap_or_default();
+    if method != "GET" && method != "HEAD" {
+        return respond(&mut stream, "405 Method Not Allowed", "text/plain", b"");
+    }
+    let path = target.split(['?', '#']).next().unwrap_or_default();
+    if path == EVENTS_PATH {
+        return events(stream, shared);
+    }
+
+    let rest = match path.strip_prefix(base_path) {
+        Some(rest) if rest.starts_with('/') => rest,
+        _ if path == "/" || path == base_path => {
+            return redirect(&mut stream, &format!("{base_path}/"));
+        }
+        _ => return not_found(&mut stream),
+    };
+    let Some(relative) = assets::resolve(Path::new(""), &assets::percent_decode(rest)) else {
+        return not_found(&mut stream);
+    };
+    let mut file = output.join(relative);
+    if file.is_dir() {
+        if !path.ends_with('/') {
+            return redirect(&mut stream, &format!("{path}/"));
+        }
+        file = file.join("index.html");
+    }
+    let Ok(body) = std::fs::read(&file) else {
+        return not_found(&mut stream);
+    };
+    let content_type = content_type(&file);
+    if content_type.starts_with("text/html") {
+        let body = inject_script(&String::from_utf8_lossy(&body));
+        return respond(&mut stream, "200 OK", content_type, body.as_bytes());
+    }
+    respond(&mut stream, "200 OK", content_type, &body)
+}
+
+/// Streams build results to a page until it goes away. The lock is only held
+/// while waiting, so a slow page never holds up a build.
+fn events(mut stream: TcpStream, shared: &Shared) -> std::io::Result<()> {
+    stream.write_all(
+        b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\n\r\n",
+    )?;
+    let (status, changed) = &**shared;
+    let (mut seen, error) = {
+        let status = status.lock().unwrap();
+        (status.build, status.error.clone())
+    };
+    if let Some(error) = error {
+        stream.write_all(error_event(&error).as_bytes())?;
+    }
+    loop {
+        let message = {
+            let status = status.lock().unwrap();
+            let (status, timeout) = changed
+                .wait_timeout_while(status, KEEPALIVE_INTERVAL, |status| status.build == seen)
+                .unwrap();
+            seen = status.build;
+            match (&status.error, timeout.timed_out()) {
+                (_, true) => ": keepalive\n\n".to_string(),
+                (Some(error), false) => error_event(error),
+                (None, false) => "event: reload\ndata:\n\n".to_string(),
+            }
+        };
+        stream.write_all(message.as_bytes())?;
+    }
+}
+
+fn error_event(error: &str) -> String {
+    let mut event = "event: build-error\n".to_string();
+    for line in error.lines() {
+        event += &format!("data: {line}\n");
+    }
+    event + "\n"
+}
+
+fn inject_script(html: &str) -> String {
+    match html.rfind("</body>") {
+        Some(end) => format!("{}{LIVE_RELOAD_SCRIPT}{}", &html[..end], &html[end..]),
+        None => format!("{html}{LIVE_RELOAD_SCRIPT}"),
+    }
+}
+
+fn not_found(stream: &mut TcpStream) -> std::io::Result<()> {
+    // The page still reloads, so it turns up once a build writes it.
+    let body = inject_script("<!DOCTYPE html>\n<title>Not found</title>\n<h1>Not found</h1>\n");
+    respond(
+        stream,
+        "404 Not Found",
+        "text/html; charset=utf-8",
+        body.as_bytes(),
+    )
+}
+
+fn redirect(stream: &mut TcpStream, location: &str) -> std::io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 302 Found\r\nLocation: {location}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+    )
+}
+
+fn respond(
+    stream: &mut TcpStream,
+    status: &str,
+    content_type: &str,
+    body: &[u8],
+) -> std::io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n",
+        body.len()
+    )?;
+    stream.write_all(body)
+}
+
+fn content_type(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase());
+    match extension.as_deref().unwrap_or_default() {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" => "text/javascript; charset=utf-8",
+        "json" => "application/json",
+        "xml" => "application/xml",
+        "txt" => "text/plain; charset=utf-8",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "ico" => "image/x-icon",
+        "woff2" => "font/woff2",
+        "pdf" => "application/pdf",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Read;
+
+    use crate::run::TempDir;
+
+    fn shared(error: Option<&str>) -> Shared {
+        Arc::new((
+            Mutex::new(Status {
+                build: 1,
+                error: error.map(str::to_string),
+            }),
+            Condvar::new(),
+        ))
+    }
+
+    /// Connects to a server for `output` handling one request on another
+    /// thread, and sends it `request`.
+    fn connect(output: &Path, shared: &Shared, request: &str) -> TcpStream {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        let output = output.to_path_buf();
+        let shared = Arc::clone(shared);
+        std::thread::spawn(move || handle(stream, &output, "/blog", &shared));
+        client.write_all(request.as_bytes()).unwrap();
+        client
+    }
+
+    /// The status line and either the `Location` or the body of the
+    /// response to a GET of `target`.
+    fn get(output: &Path, target: &str) -> (String, String) {
+        let request = format!("GET {target} HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        let mut client = connect(output, &shared(None), &request);
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        let mut lines = head.lines();
+        let status = lines.next().unwrap().to_string();
+        match lines.find_map(|line| line.strip_prefix("Location: ")) {
+            Some(location) => (status, location.to_string()),
+            None => (status, body.to_string()),
+        }
+    }
+
+    #[test]
+    fn routes_under_the_base_path() {
+        let dir = TempDir::new().unwrap();
+        let output = dir.path.join("dist");
+        std::fs::create_dir_all(output.join("post")).unwrap();
+        std::fs::write(output.join("index.html"), "<body>index</body>").unwrap();
+        std::fs::write(output.join("post/index.html"), "<body>post</body>").unwrap();
+        std::fs::write(output.join("a b.txt"), "text").unwrap();
+        std::fs::write(dir.path.join("secret.txt"), "secret").unwrap();
+
+        let found = |body: &str| ("HTTP/1.1 200 OK".to_string(), body.to_string());
+        let redirect = |location: &str| ("HTTP/1.1 302 Found".to_string(), location.to_string());
+        let html = |text: &str| found(&inject_script(&format!("<body>{text}</body>")));
+        assert_eq!(get(&output, "/blog/"), html("index"));
+        assert_eq!(get(&output, "/blog/index.html?x=1"), html("index"));
+        assert_eq!(get(&output, "/blog/post/"), html("post"));
+        assert_eq!(get(&output, "/blog/a%20b.txt"), found("text"));
+        assert_eq!(get(&output, "/"), redirect("/blog/"));
+        assert_eq!(get(&output, "/blog"), redirect("/blog/"));
+        assert_eq!(get(&output, "/blog/post"), redirect("/blog/post/"));
+
+        let not_found = |target: &str| {
+            let (status, body) = get(&output, target);
+            assert_eq!(status, "HTTP/1.1 404 Not Found", "{target}");
+            assert!(!body.contains("secret"), "{target}");
+        };
+        not_found("/blog/missing.html");
+        not_found("/blogpost/");
+        not_found("/index.html");
+        not_found("/blog/../secret.txt");
+        not_found("/blog/%2e%2e/secret.txt");
+        not_found("/blog/post/../../secret.txt");
+
+        let mut client = connect(&output, &shared(None), "POST /blog/ HTTP/1.1\r\n\r\n");
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 405 Method Not Allowed\r\n"));
+    }
+
+    #[test]
+    fn build_events() {
+        assert_eq!(
+            error_event("src/a.md:3: oops\n  detail"),
+            "event: build-error\ndata: src/a.md:3: oops\ndata:   detail\n\n"
+        );
+
+        let dir = TempDir::new().unwrap();
+        let shared = shared(Some("broken"));
+        let request = format!("GET {EVENTS_PATH} HTTP/1.1\r\n\r\n");
+        let client = connect(&dir.path, &shared, &request);
+        let mut reader = BufReader::new(client);
+        // Reads the response head or one event, up to a blank line.
+        let mut event = || {
+            let mut event = String::new();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                event += &line;
+                if line.trim().is_empty() {
+                    return event;
+                }
+            }
+        };
+        assert!(event().starts_with("HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\n"));
+        // The page learns about the current error as soon as it connects.
+        assert_eq!(event(), "event: build-error\ndata: broken\n\n");
+
+        let (status, changed) = &*shared;
+        {
+            let mut status = status.lock().unwrap();
+            status.build += 1;
+            status.error = None;
+            changed.notify_all();
+        }
+        assert_eq!(event(), "event: reload\ndata:\n\n");
+        {
+            let mut status = status.lock().unwrap();
+            status.build += 1;
+            status.error = Some("broken again".into());
+            changed.notify_all();
+        }
+        assert_eq!(event(), "event: build-error\ndata: broken again\n\n");
+    }
+}
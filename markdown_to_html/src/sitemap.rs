@@ -0,0 +1,148 @@
+//! `sitemap.xml` listing the pages search engines should index, and the
+//! `robots.txt` that points crawlers at it.
+
+use crate::config::Config;
+use crate::date::Date;
+use crate::front_matter::PageMeta;
+use crate::html::escape;
+
+pub const SITEMAP_FILE_NAME: &str = "sitemap.xml";
+pub const ROBOTS_FILE_NAME: &str = "robots.txt";
+
+pub struct Entry {
+    /// Path of the page relative to the site root.
+    pub url: String,
+    pub lastmod: Option<Date>,
+}
+
+/// When a page last changed: its `updated` or `date`. Pages with neither
+/// have no `lastmod`, since the modification time of a fresh checkout says
+/// nothing about the page.
+pub fn lastmod(meta: &PageMeta) -> Option<Date> {
+    meta.updated.or(meta.date)
+}
+
+pub fn sitemap(base_url: &str, entries: &[Entry]) -> String {
+    let mut entries: Vec<&Entry> = entries.iter().collect();
+    entries.sort_by(|a, b| a.url.cmp(&b.url));
+    let mut out = String::from(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+"#,
+    );
+    for entry in entries {
+        out += &format!(
+            "  <url>\n    <loc>{}</loc>\n",
+            escape(&format!("{base_url}/{}", entry.url))
+        );
+        if let Some(lastmod) = &entry.lastmod {
+            // Dates without a time are written as plain dates rather than
+            // pretending to know it was midnight.
+            let lastmod = match lastmod.time {
+                Some(_) => lastmod.rfc3339(),
+                None => format!(
+                    "{:04}-{:02}-{:02}",
+                    lastmod.year, lastmod.month, lastmod.day
+                ),
+            };
+            out += &format!("    <lastmod>{lastmod}</lastmod>\n");
+        }
+        out += "  </url>\n";
+    }
+    out += "</urlset>\n";
+    out
+}
+
+/// Rules for every crawler, plus the location of the sitemap when the site
+/// knows its own URL.
+pub fn robots(config: &Config) -> String {
+    let mut out = String::from("User-agent: *\n");
+    if config.robots.disallow.is_empty() {
+        out += "Disallow:\n";
+    }
+    for path in &config.robots.disallow {
+        out += &format!("Disallow: {path}\n");
+    }
+    if let Some(base_url) = &config.base_url {
+        out += &format!("\nSitemap: {base_url}/{SITEMAP_FILE_NAME}\n");
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::run::TempDir;
+
+    #[test]
+    fn urls() {
+        let entries = [
+            Entry {
+                url: "b&c.html".into(),
+                lastmod: Date::parse_iso("2024-03-01T12:30:00+01:00"),
+            },
+            Entry {
+                url: String::new(),
+                lastmod: Date::parse_iso("2024-03-01"),
+            },
+            Entry {
+                url: "about/".into(),
+                lastmod: None,
+            },
+        ];
+        assert_eq!(
+            sitemap("https://example.com/blog", &entries),
+            r#"<?xml version="1.0" encoding="utf-8"?>
+<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+  <url>
+    <loc>https://example.com/blog/</loc>
+    <lastmod>2024-03-01</lastmod>
+  </url>
+  <url>
+    <loc>https://example.com/blog/about/</loc>
+  </url>
+  <url>
+    <loc>https://example.com/blog/b&amp;c.html</loc>
+    <lastmod>2024-03-01T12:30:00+01:00</lastmod>
+  </url>
+</urlset>
+"#
+        );
+    }
+
+    #[test]
+    fn lastmod_from_front_matter() {
+        let date = Date::parse_iso("2024-01-02");
+        let updated = Date::parse_iso("2024-02-03");
+        let meta = |date, updated| PageMeta {
+            date,
+            updated,
+            ..Default::default()
+        };
+        let iso = |meta| lastmod(&meta).map(|date| date.iso());
+        assert_eq!(iso(meta(date, updated)).as_deref(), Some("2024-02-03"));
+        assert_eq!(iso(meta(date, None)).as_deref(), Some("2024-01-02"));
+        assert_eq!(iso(meta(None, None)), None);
+    }
+
+    #[test]
+    fn robots_txt() {
+        let load = |overrides: &[(&str, &str)]| {
+            let dir = TempDir::new().unwrap();
+            let overrides: Vec<_> = overrides
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect();
+            Config::load(&dir.path.join("blog.toml"), &overrides).unwrap()
+        };
+        assert_eq!(robots(&load(&[])), "User-agent: *\nDisallow:\n");
+        assert_eq!(
+            robots(&load(&[
+                ("base_url", "https://example.com/blog"),
+                ("robots.disallow", r#"["/drafts/", "/tmp/"]"#),
+            ])),
+            "User-agent: *\nDisallow: /drafts/\nDisallow: /tmp/\n\nSitemap: https://example.com/blog/sitemap.xml\n"
+        );
+    }
+}
@@ -0,0 +1,399 @@
+//! The `check-snippets` command: compiles the Rust code blocks in every post
+//! the way rustdoc compiles doctests.
+//!
+//! Each post with `rust` blocks gets a Cargo package under [`DIR_NAME`] at the
+//! site root, with a binary for every block and the crates listed under
+//! `dependencies` in its front matter. Those are taken from the registry
+//! vendored at `snippets.vendor` with `cargo vendor`, so checking works
+//! offline.
+//!
+//! As in rustdoc, lines hidden with `# ` are compiled, blocks without a
+//! `fn main` are wrapped in one, and attributes after the language adjust the
+//! check: `ignore` skips the block, `no_run` only compiles it,
+//! `compile_fail` expects it not to compile, `should_panic` expects it to
+//! panic and `edition2018` and the like pick the edition.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::value::{Table, Value};
+use crate::{cli, code, config, front_matter, ignore, toml};
+
+/// Where the packages are generated, which also keeps the compiled
+/// dependencies between runs.
+pub const DIR_NAME: &str = ".snippets";
+
+/// Rustdoc's exit code for a panicking test.
+const PANIC_EXIT_CODE: i32 = 101;
+
+/// Checks every `rust` block and reports the ones that fail with the file and
+/// line of their opening fence.
+pub fn check(args: &cli::Args) -> Result<(), Box<dyn std::error::Error>> {
+    let config = config::Config::load(&args.config, &args.overrides)?;
+    let ignore = ignore::Ignore::load(&args.source, &config.ignore)?;
+    let mut skip = vec![args.source.join("templates"), args.source.join(DIR_NAME)];
+    skip.extend(args.output.canonicalize());
+    skip.extend(
+        config
+            .snippets
+            .vendor
+            .iter()
+            .map(|vendor| args.source.join(vendor)),
+    );
+    let mut sources = Vec::new();
+    crate::find_pages(args, &args.source, &skip, &ignore, &mut sources)?;
+    sources.sort();
+    let vendor = match &config.snippets.vendor {
+        Some(vendor) => {
+            let path = args.source.join(vendor);
+            let path = path
+                .canonicalize()
+                .map_err(|e| format!("`snippets.vendor`: {}: {e}", path.display()))?;
+            Some(path)
+        }
+        None => None,
+    };
+    let dir = args.source.join(DIR_NAME);
+    std::fs::create_dir_all(dir.join("target"))?;
+    let target = dir.join("target").canonicalize()?;
+
+    let mut packages = vec![target.clone()];
+    let mut checked = 0;
+    let mut failed = 0;
+    for path in &sources {
+        let source = std::fs::read_to_string(path)?;
+        let (meta, body) = front_matter::parse(path, &source, &config.dates)?;
+        let front_matter_lines = source[..source.len() - body.len()].lines().count();
+        let includes = code::Includes {
+            root: &args.source,
+            dir: path.parent().unwrap_or(&args.source),
+        };
+        let blocks: Vec<_> = code::blocks(body, &includes)
+            .map_err(|e| {
+                let line = front_matter_lines + e.line;
+                format!("{}:{line}: {}", path.display(), e.message)
+            })?
+            .into_iter()
+            .filter(|block| block.language.as_deref() == Some("rust"))
+            .collect();
+        if blocks.is_empty() {
+            continue;
+        }
+        if !meta.dependencies.is_empty() && vendor.is_none() {
+            return Err(format!(
+                "{}: `dependencies` need a vendored registry, set `snippets.vendor` in the configuration",
+                path.display()
+            )
+            .into());
+        }
+        let name = package_name(path.strip_prefix(&args.source)?);
+        let package = Package {
+            dir: dir.join(&name),
+            bins: blocks
+                .iter()
+                .map(|block| {
+                    let edition = block.edition().unwrap_or(&config.snippets.edition);
+                    Bin {
+                        name: format!("{name}_{}", front_matter_lines + block.line),
+                        edition: edition.to_string(),
+                        program: program(&block.code),
+                    }
+                })
+                .collect(),
+        };
+        package.write(&meta.dependencies)?;
+        packages.push(package.dir.canonicalize()?);
+
+        for (block, bin) in blocks.iter().zip(&package.bins) {
+            let location = format!("{}:{}", path.display(), front_matter_lines + block.line);
+            let flag = |name: &str| block.flags.iter().any(|flag| flag == name);
+            if flag("ignore") {
+                println!("{location} ... ignored");
+                continue;
+            }
+            checked += 1;
+            let cargo = Cargo {
+                package: &package,
+                target: &target,
+                vendor: vendor.as_deref(),
+            };
+            let expectation = if flag("compile_fail") {
+                Expect::CompileFail
+            } else if flag("no_run") {
+                Expect::Compile
+            } else if flag("should_panic") {
+                Expect::Panic
+            } else {
+                Expect::Success
+            };
+            match cargo.check(bin, expectation)? {
+                Ok(()) => println!("{location} ... ok"),
+                Err(message) => {
+                    failed += 1;
+                    println!("{location} ... FAILED");
+                    eprintln!("{location}: {}", message.trim_end());
+                }
+            }
+        }
+    }
+    // Packages of posts that are gone or have no Rust blocks any more.
+    for entry in std::fs::read_dir(&dir)? {
+        let path = entry?.path();
+        if path.is_dir() && !packages.contains(&path.canonicalize()?) {
+            std::fs::remove_dir_all(path)?;
+        }
+    }
+    match failed {
+        0 => {
+            println!("{checked} snippets ok");
+            Ok(())
+        }
+        _ => Err(format!("{failed} of {checked} snippets failed").into()),
+    }
+}
+
+/// A name for the package of the post at `relative`, also usable in the
+/// names of its binaries.
+fn package_name(relative: &Path) -> String {
+    let name: String = relative
+        .with_extension("")
+        .to_string_lossy()
+        .chars()
+        .map(|c| match c.is_ascii_alphanumeric() {
+            true => c.to_ascii_lowercase(),
+            false => '_',
+        })
+        .collect();
+    format!("snippets_{name}")
+}
+
+/// The program rustdoc would compile for `code`: hidden lines included,
+/// crate attributes and `extern crate` at the top and everything else in
+/// `fn main` unless it has one. Code that ends in `Ok::<..>(())` runs in a
+/// function returning a `Result`, so it can use `?`.
+pub fn program(code: &str) -> String {
+    let mut header = String::from("#![allow(unused)]\n");
+    let mut body = String::new();
+    for line in code.lines() {
+        let (line, _) = code::rust_line(line);
+        let trimmed = line.trim_start();
+        let crate_level = trimmed.starts_with("#![") || trimmed.starts_with("extern crate");
+        if crate_level && body.trim().is_empty() {
+            header += &line;
+            header.push('\n');
+        } else {
+            body += &line;
+            body.push('\n');
+        }
+    }
+    if body.contains("fn main") {
+        return header + &body;
+    }
+    let last = body
+        .lines()
+        .rev()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .unwrap_or_default();
+    if last.starts_with("Ok::<") && last.trim_end_matches(';').ends_with("(())") {
+        format!(
+            "{header}fn main() {{\n    fn _inner() -> Result<(), impl core::fmt::Debug> {{\n{body}    }}\n    _inner().unwrap()\n}}\n"
+        )
+    } else {
+        format!("{header}fn main() {{\n{body}}}\n")
+    }
+}
+
+/// The Cargo package for the code blocks of one post.
+struct Package {
+    dir: PathBuf,
+    bins: Vec<Bin>,
+}
+
+struct Bin {
+    name: String,
+    edition: String,
+    program: String,
+}
+
+impl Package {
+    /// Writes the manifest and sources, leaving files that have not changed
+    /// alone so Cargo does not rebuild them.
+    fn write(&self, dependencies: &Table) -> std::io::Result<()> {
+        let bin_dir = self.dir.join("src").join("bin");
+        std::fs::create_dir_all(&bin_dir)?;
+        let name = self
+            .dir
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned();
+        let mut manifest = format!(
+            "[package]\nname = {}\nversion = \"0.0.0\"\nedition = \"2021\"\npublish = false\nautobins = false\n\n",
+            toml::to_inline_string(&Value::String(name))
+        );
+        for bin in &self.bins {
+            manifest += &format!(
+                "[[bin]]\nname = \"{0}\"\npath = \"src/bin/{0}.rs\"\nedition = \"{1}\"\n\n",
+                bin.name, bin.edition
+            );
+        }
+        manifest += "[dependencies]\n";
+        for (name, spec) in dependencies {
+            manifest += &format!(
+                "{} = {}\n",
+                toml::to_inline_string(&Value::String(name.clone())),
+                toml::to_inline_string(spec)
+            );
+        }
+        // Keeps the package out of any workspace the site happens to be in.
+        manifest += "\n[workspace]\n";
+        write_if_changed(&self.dir.join("Cargo.toml"), &manifest)?;
+
+        let mut stale: Vec<PathBuf> = std::fs::read_dir(&bin_dir)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<_, _>>()?;
+        for bin in &self.bins {
+            let path = bin_dir.join(format!("{}.rs", bin.name));
+            stale.retain(|stale| *stale != path);
+            write_if_changed(&path, &bin.program)?;
+        }
+        for path in stale {
+            std::fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+}
+
+fn write_if_changed(path: &Path, contents: &str) -> std::io::Result<()> {
+    if std::fs::read_to_string(path).ok().as_deref() != Some(contents) {
+        std::fs::write(path, contents)?;
+    }
+    Ok(())
+}
+
+#[derive(Clone, Copy)]
+enum Expect {
+    /// The block compiles and runs successfully.
+    Success,
+    /// The block compiles, and is not run.
+    Compile,
+    CompileFail,
+    /// The block compiles and panics when run.
+    Panic,
+}
+
+struct Cargo<'a> {
+    package: &'a Package,
+    /// Shared by every package, so dependencies are only compiled once.
+    target: &'a Path,
+    vendor: Option<&'a Path>,
+}
+
+impl Cargo<'_> {
+    /// Builds and maybe runs `bin`. The outer error is for Cargo failing to
+    /// start and the inner one describes how the block did not meet
+    /// `expectation`, with the compiler's or the program's output.
+    fn check(
+        &self,
+        bin: &Bin,
+        expectation: Expect,
+    ) -> Result<Result<(), String>, Box<dyn std::error::Error>> {
+        let mut build = Command::new("cargo");
+        build
+            .args(["build", "--quiet", "--offline", "--bin", &bin.name])
+            .arg("--manifest-path")
+            .arg(self.package.dir.join("Cargo.toml"))
+            .arg("--target-dir")
+            .arg(self.target);
+        if let Some(vendor) = self.vendor {
+            let directory =
+                toml::to_inline_string(&Value::String(vendor.to_string_lossy().into_owned()));
+            build
+                .args(["--config", "source.crates-io.replace-with = \"vendored\""])
+                .arg("--config")
+                .arg(format!("source.vendored.directory = {directory}"));
+        }
+        let built = build
+            .output()
+            .map_err(|e| format!("could not run cargo: {e}"))?;
+        let compiler_output = String::from_utf8_lossy(&built.stderr);
+        match (expectation, built.status.success()) {
+            (Expect::CompileFail, true) => {
+                return Ok(Err(
+                    "expected to fail to compile, but it compiled".to_string()
+                ));
+            }
+            (Expect::CompileFail, false) => return Ok(Ok(())),
+            (_, false) => return Ok(Err(format!("failed to compile\n{compiler_output}"))),
+            (Expect::Compile, true) => return Ok(Ok(())),
+            (Expect::Success | Expect::Panic, true) => {}
+        }
+
+        let executable =
+            self.target
+                .join("debug")
+                .join(format!("{}{}", bin.name, std::env::consts::EXE_SUFFIX));
+        let ran = Command::new(&executable)
+            .current_dir(&self.package.dir)
+            .output()
+            .map_err(|e| format!("could not run {}: {e}", executable.display()))?;
+        let output = format!(
+            "{}{}",
+            String::from_utf8_lossy(&ran.stdout),
+            String::from_utf8_lossy(&ran.stderr)
+        );
+        let panicked = ran.status.code() == Some(PANIC_EXIT_CODE);
+        Ok(match (expectation, ran.status.success(), panicked) {
+            (Expect::Panic, _, true) => Ok(()),
+            (Expect::Panic, _, false) => Err(format!(
+                "expected to panic, but it exited with {}\n{output}",
+                ran.status
+            )),
+            (_, true, _) => Ok(()),
+            (_, false, _) => Err(format!("exited with {}\n{output}", ran.status)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wraps_code_in_main() {
+        assert_eq!(
+            program("let x = 1;\nprintln!(\"{x}\");"),
+            "#![allow(unused)]\nfn main() {\nlet x = 1;\nprintln!(\"{x}\");\n}\n"
+        );
+        assert_eq!(program(""), "#![allow(unused)]\nfn main() {\n}\n");
+    }
+
+    #[test]
+    fn keeps_an_existing_main() {
+        let code = "# use std::fmt;\nfn main() {\n    println!(\"hi\");\n}";
+        assert_eq!(
+            program(code),
+            "#![allow(unused)]\nuse std::fmt;\nfn main() {\n    println!(\"hi\");\n}\n"
+        );
+    }
+
+    #[test]
+    fn crate_attributes_go_first() {
+        assert_eq!(
+            program("# #![feature(never_type)]\n#![deny(warnings)]\nextern crate alloc;\nlet v = alloc::vec![1];\n#![not_at_the_top]"),
+            "#![allow(unused)]\n#![feature(never_type)]\n#![deny(warnings)]\nextern crate alloc;\nfn main() {\nlet v = alloc::vec![1];\n#![not_at_the_top]\n}\n"
+        );
+    }
+
+    #[test]
+    fn question_marks_get_a_result() {
+        let code = "let n: i32 = \"1\".parse()?;\n# Ok::<(), std::num::ParseIntError>(())\n";
+        assert_eq!(
+            program(code),
+            "#![allow(unused)]\nfn main() {\n    fn _inner() -> Result<(), impl core::fmt::Debug> {\nlet n: i32 = \"1\".parse()?;\nOk::<(), std::num::ParseIntError>(())\n    }\n    _inner().unwrap()\n}\n"
+        );
+        assert!(program("Ok::<(), ()>(());").contains("fn _inner()"));
+        assert!(!program("Ok::<(), ()>(()).unwrap();").contains("fn _inner()"));
+    }
+}
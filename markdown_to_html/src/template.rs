@@ -0,0 +1,1268 @@
+//! A small template engine for the page shell.
+//!
+//! The syntax is a subset of Jinja:
+//!
+//! - `{{ page.title }}` prints a value, HTML-escaped unless it ends with the
+//!   `safe` filter. Filters are `safe`, `trim`, `lower`, `upper`, `length`,
+//!   `join(sep)` and `default(value)`. A variable that is not defined at all
+//!   is an error unless `default` gives it a value, while a missing key of a
+//!   table is empty.
+//! - `{% if %}`, `{% elif %}`, `{% else %}` and `{% endif %}` test truthiness.
+//!   Conditions may use `and`, `or`, `not`, `==` and `!=`.
+//! - `{% for post in posts %} ... {% endfor %}` loops over arrays, with
+//!   `loop.index`, `loop.first` and `loop.last` available inside.
+//! - `{% extends "base.html" %}` and `{% block name %} ... {% endblock %}`
+//!   let a layout fill in the named blocks of a base layout, and
+//!   `{{ super() }}` in a block prints the block it overrides.
+//! - `{% include "partials/header.html" %}` renders a partial in place.
+//! - `{# ... #}` is a comment.
+//!
+//! A `-` just inside a delimiter, as in `{%- if x -%}`, trims the whitespace
+//! on that side of the tag.
+//!
+//! Templates are looked up in the site's `templates/` directory first and
+//! then in the built-in default theme, so a site only needs to provide the
+//! templates it wants to change.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::path::Path;
+
+use crate::cache;
+use crate::html;
+use crate::value::{Table, Value};
+
+/// The default theme, matching the markup the generator has always produced.
+const BUILTIN: &[(&str, &str)] = &[
+    ("base.html", include_str!("../templates/base.html")),
+    ("page.html", include_str!("../templates/page.html")),
+    ("index.html", include_str!("../templates/index.html")),
+    (
+        "partials/header.html",
+        include_str!("../templates/partials/header.html"),
+    ),
+    (
+        "partials/footer.html",
+        include_str!("../templates/partials/footer.html"),
+    ),
+];
+
+const FILTERS: &[&str] = &[
+    "safe", "trim", "lower", "upper", "length", "join", "default",
+];
+
+/// How deeply includes and layouts may nest before we assume a cycle.
+const MAX_DEPTH: usize = 32;
+
+/// An error in a template, with the template name and 1-based line.
+#[derive(Debug)]
+pub struct Error {
+    pub template: String,
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "template `{}`, line {}: {}",
+            self.template, self.line, self.message
+        )
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Every template available to the site, parsed up front.
+pub struct Templates {
+    templates: HashMap<String, Template>,
+    /// Hash of every template's name and source, which changes whenever any
+    /// of them does.
+    fingerprint: String,
+}
+
+impl Templates {
+    /// Loads the built-in theme, then every `.html` file under `dir`, which
+    /// replace built-in templates of the same name.
+    pub fn load(dir: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut templates = HashMap::new();
+        let mut sources = BTreeMap::new();
+        for (name, source) in BUILTIN {
+            templates.insert(name.to_string(), Template::parse(name, source)?);
+            sources.insert(name.to_string(), source.to_string());
+        }
+        if dir.is_dir() {
+            let mut pending = vec![dir.to_path_buf()];
+            while let Some(current) = pending.pop() {
+                for entry in std::fs::read_dir(&current)? {
+                    let path = entry?.path();
+                    if path.is_dir() {
+                        pending.push(path);
+                    } else if path
+                        .extension()
+                        .is_some_and(|extension| extension == "html")
+                    {
+                        let name = path
+                            .strip_prefix(dir)?
+                            .components()
+                            .map(|component| component.as_os_str().to_string_lossy())
+                            .collect::<Vec<_>>()
+                            .join("/");
+                        let source = std::fs::read_to_string(&path)?;
+                        let template = Template::parse(&name, &source)?;
+                        templates.insert(name.clone(), template);
+                        sources.insert(name, source);
+                    }
+                }
+            }
+        }
+        let mut hasher = cache::Hasher::new();
+        for (name, source) in &sources {
+            hasher.write(name);
+            hasher.write(source);
+        }
+        Ok(Templates {
+            templates,
+            fingerprint: hasher.finish(),
+        })
+    }
+
+    pub fn fingerprint(&self) -> &str {
+        &self.fingerprint
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.templates.contains_key(name)
+    }
+
+    /// Renders the template `name` with `context` as its global variables.
+    pub fn render(&self, name: &str, context: &Table) -> Result<String, Error> {
+        let mut renderer = Renderer {
+            templates: self,
+            context,
+            scopes: Vec::new(),
+            blocks: HashMap::new(),
+            rendering: Vec::new(),
+            depth: 0,
+        };
+        let mut out = String::new();
+        renderer.render_template(name, 1, name, &mut out)?;
+        Ok(out)
+    }
+}
+
+struct Template {
+    name: String,
+    extends: Option<(String, usize)>,
+    nodes: Vec<Node>,
+}
+
+enum Node {
+    Text(String),
+    Print {
+        expr: Expr,
+        safe: bool,
+        line: usize,
+    },
+    If {
+        branches: Vec<(Expr, Vec<Node>)>,
+        otherwise: Vec<Node>,
+        line: usize,
+    },
+    For {
+        variable: String,
+        iterable: Expr,
+        body: Vec<Node>,
+        line: usize,
+    },
+    Block {
+        name: String,
+        body: Vec<Node>,
+    },
+    /// `{{ super() }}`: the block that the one it is in overrides.
+    Super {
+        line: usize,
+    },
+    Include {
+        name: String,
+        line: usize,
+    },
+}
+
+#[derive(Clone, Debug)]
+enum Expr {
+    Literal(Value),
+    Path(Vec<String>),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Equal(Box<Expr>, Box<Expr>, bool),
+    Filter(Box<Expr>, String, Vec<Expr>),
+}
+
+enum Token<'a> {
+    Text(String),
+    Print(&'a str, usize),
+    Tag(&'a str, usize),
+}
+
+impl Template {
+    fn parse(name: &str, source: &str) -> Result<Self, Error> {
+        let error = |line, message: String| Error {
+            template: name.to_string(),
+            line,
+            message,
+        };
+        let tokens = tokenize(source).map_err(|(line, message)| error(line, message))?;
+        let mut parser = Parser { tokens, next: 0 };
+        let mut extends = None;
+        // `{% extends %}` must come before anything but whitespace.
+        while let Some(token) = parser.tokens.get(parser.next) {
+            match token {
+                Token::Text(text) if text.trim().is_empty() => parser.next += 1,
+                Token::Tag(tag, line) if tag.starts_with("extends ") => {
+                    let target = parse_string(&tag["extends ".len()..]).ok_or_else(|| {
+                        error(*line, "expected a template name after `extends`".into())
+                    })?;
+                    extends = Some((target, *line));
+                    parser.next += 1;
+                    break;
+                }
+                _ => break,
+            }
+        }
+        let (nodes, end) = parser
+            .parse_nodes(&[], 1)
+            .map_err(|(line, message)| error(line, message))?;
+        if let Some((tag, line)) = end {
+            return Err(error(line, format!("unexpected `{{% {tag} %}}`")));
+        }
+        Ok(Template {
+            name: name.to_string(),
+            extends,
+            nodes,
+        })
+    }
+}
+
+/// Splits `source` into text, print and tag tokens, applying `-` whitespace
+/// trimming and dropping comments.
+fn tokenize(source: &str) -> Result<Vec<Token<'_>>, (usize, String)> {
+    let mut tokens = Vec::new();
+    let mut rest = source;
+    let mut line = 1;
+    let mut trim_next = false;
+    loop {
+        let start = ["{{", "{%", "{#"]
+            .iter()
+            .filter_map(|open| rest.find(open))
+            .min();
+        let Some(start) = start else {
+            let text = if trim_next { rest.trim_start() } else { rest };
+            tokens.push(Token::Text(text.to_string()));
+            return Ok(tokens);
+        };
+        let close = match &rest[start..start + 2] {
+            "{{" => "}}",
+            "{%" => "%}",
+            _ => "#}",
+        };
+        let trim_before = rest[start + 2..].starts_with('-');
+        let mut text = &rest[..start];
+        if trim_next {
+            text = text.trim_start();
+        }
+        if trim_before {
+            text = text.trim_end();
+        }
+        tokens.push(Token::Text(text.to_string()));
+        line += rest[..start].matches('\n').count();
+
+        let inner_start = start + 2 + usize::from(trim_before);
+        let Some(length) = rest[inner_start..].find(close) else {
+            return Err((line, format!("unclosed `{}`", &rest[start..start + 2])));
+        };
+        let mut inner = &rest[inner_start..inner_start + length];
+        trim_next = inner.ends_with('-') && close != "#}";
+        if trim_next {
+            inner = &inner[..inner.len() - 1];
+        }
+        match close {
+            "}}" => tokens.push(Token::Print(inner.trim(), line)),
+            "%}" => tokens.push(Token::Tag(inner.trim(), line)),
+            _ => trim_next = inner.ends_with('-'),
+        }
+        line += inner.matches('\n').count();
+        rest = &rest[inner_start + length + 2..];
+    }
+}
+
+type ParseResult<T> = Result<T, (usize, String)>;
+
+/// Parsed nodes and the tag that ended them, with its line.
+type Nodes<'a> = (Vec<Node>, Option<(&'a str, usize)>);
+
+struct Parser<'a> {
+    tokens: Vec<Token<'a>>,
+    next: usize,
+}
+
+impl<'a> Parser<'a> {
+    /// Parses nodes until one of the `ends` tags, which is returned with its
+    /// arguments. Reaching the end of the template is only allowed when
+    /// `ends` is empty; otherwise the error points at `opened`, the line of
+    /// the tag being closed.
+    fn parse_nodes(&mut self, ends: &[&str], opened: usize) -> ParseResult<Nodes<'a>> {
+        let mut nodes = Vec::new();
+        while let Some(token) = self.tokens.get_mut(self.next) {
+            self.next += 1;
+            match token {
+                Token::Text(text) => nodes.push(Node::Text(std::mem::take(text))),
+                Token::Print("super()", line) => nodes.push(Node::Super { line: *line }),
+                Token::Print(source, line) => {
+                    let (source, line) = (*source, *line);
+                    let expr = parse_expr(source).map_err(|message| (line, message))?;
+                    let (expr, safe) = match expr {
+                        Expr::Filter(inner, filter, args)
+                            if filter == "safe" && args.is_empty() =>
+                        {
+                            (*inner, true)
+                        }
+                        expr => (expr, false),
+                    };
+                    nodes.push(Node::Print { expr, safe, line });
+                }
+                Token::Tag(tag, line) => {
+                    let (tag, line) = (*tag, *line);
+                    let keyword = tag.split_whitespace().next().unwrap_or_default();
+                    let args = tag[keyword.len()..].trim();
+                    if ends.contains(&keyword) {
+                        return Ok((nodes, Some((tag, line))));
+                    }
+                    nodes.push(self.parse_tag(keyword, args, line)?);
+                }
+            }
+        }
+        match ends.last() {
+            Some(end) => Err((
+                opened,
+                format!("this tag is never closed with `{{% {end} %}}`"),
+            )),
+            None => Ok((nodes, None)),
+        }
+    }
+
+    fn parse_tag(&mut self, keyword: &str, args: &str, line: usize) -> ParseResult<Node> {
+        let expr = |source: &str| parse_expr(source).map_err(|message| (line, message));
+        match keyword {
+            "if" => {
+                let mut branches = Vec::new();
+                let mut condition = expr(args)?;
+                loop {
+                    let (body, end) = self.parse_nodes(&["elif", "else", "endif"], line)?;
+                    let (tag, end_line) = end.expect("parse_nodes errors without an end tag");
+                    branches.push((condition, body));
+                    if let Some(next) = tag.strip_prefix("elif") {
+                        condition = parse_expr(next).map_err(|message| (end_line, message))?;
+                    } else if tag == "else" {
+                        let (otherwise, _) = self.parse_nodes(&["endif"], line)?;
+                        return Ok(Node::If {
+                            branches,
+                            otherwise,
+                            line,
+                        });
+                    } else {
+                        return Ok(Node::If {
+                            branches,
+                            otherwise: Vec::new(),
+                            line,
+                        });
+                    }
+                }
+            }
+            "for" => {
+                let Some((variable, iterable)) = args.split_once(" in ") else {
+                    return Err((line, "expected `{% for item in items %}`".into()));
+                };
+                let variable = variable.trim();
+                if !is_identifier(variable) {
+                    return Err((line, format!("invalid loop variable `{variable}`")));
+                }
+                let iterable = expr(iterable)?;
+                let (body, _) = self.parse_nodes(&["endfor"], line)?;
+                Ok(Node::For {
+                    variable: variable.to_string(),
+                    iterable,
+                    body,
+                    line,
+                })
+            }
+            "block" => {
+                if !is_identifier(args) {
+                    return Err((line, format!("invalid block name `{args}`")));
+                }
+                let (body, end) = self.parse_nodes(&["endblock"], line)?;
+                let (tag, end_line) = end.expect("parse_nodes errors without an end tag");
+                let closing = tag["endblock".len()..].trim();
+                if !closing.is_empty() && closing != args {
+                    return Err((
+                        end_line,
+                        format!("`{{% endblock {closing} %}}` closes block `{args}`"),
+                    ));
+                }
+                Ok(Node::Block {
+                    name: args.to_string(),
+                    body,
+                })
+            }
+            "include" => match parse_string(args) {
+                Some(name) => Ok(Node::Include { name, line }),
+                None => Err((line, "expected a template name after `include`".into())),
+            },
+            "extends" => Err((line, "`extends` must be the first tag in a template".into())),
+            _ => Err((line, format!("unknown tag `{keyword}`"))),
+        }
+    }
+}
+
+fn is_identifier(text: &str) -> bool {
+    !text.is_empty()
+        && !text.starts_with(|c: char| c.is_ascii_digit())
+        && text.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Parses a quoted string literal that makes up all of `text`.
+fn parse_string(text: &str) -> Option<String> {
+    match parse_expr(text) {
+        Ok(Expr::Literal(Value::String(s))) => Some(s),
+        _ => None,
+    }
+}
+
+fn parse_expr(source: &str) -> Result<Expr, String> {
+    let tokens = lex_expr(source)?;
+    let mut parser = ExprParser { tokens, next: 0 };
+    let expr = parser.parse_or()?;
+    match parser.tokens.get(parser.next) {
+        Some(token) => Err(format!("unexpected `{}` in `{source}`", token.text())),
+        None => Ok(expr),
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum ExprToken {
+    Name(String),
+    String(String),
+    Number(Value),
+    Symbol(&'static str),
+}
+
+impl ExprToken {
+    fn text(&self) -> String {
+        match self {
+            ExprToken::Name(name) => name.clone(),
+            ExprToken::String(s) => format!("{s:?}"),
+            ExprToken::Number(Value::Integer(i)) => i.to_string(),
+            ExprToken::Number(Value::Float(f)) => f.to_string(),
+            ExprToken::Number(_) => String::new(),
+            ExprToken::Symbol(symbol) => symbol.to_string(),
+        }
+    }
+}
+
+fn lex_expr(source: &str) -> Result<Vec<ExprToken>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = source.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '"' | '\'' => {
+                chars.next();
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        None => return Err(format!("unterminated string in `{source}`")),
+                        Some((_, '\\')) => match chars.next() {
+                            Some((_, 'n')) => value.push('\n'),
+                            Some((_, escaped)) => value.push(escaped),
+                            None => return Err(format!("unterminated string in `{source}`")),
+                        },
+                        Some((_, end)) if end == c => break,
+                        Some((_, other)) => value.push(other),
+                    }
+                }
+                tokens.push(ExprToken::String(value));
+            }
+            c if c.is_ascii_digit() || c == '-' => {
+                let mut end = start + c.len_utf8();
+                chars.next();
+                while let Some(&(i, c)) = chars.peek() {
+                    if !(c.is_ascii_digit() || c == '.') {
+                        break;
+                    }
+                    end = i + c.len_utf8();
+                    chars.next();
+                }
+                let text = &source[start..end];
+                let number = text
+                    .parse()
+                    .map(Value::Integer)
+                    .or_else(|_| text.parse().map(Value::Float))
+                    .map_err(|_| format!("invalid number `{text}`"))?;
+                tokens.push(ExprToken::Number(number));
+            }
+            c if c.is_alphanumeric() || c == '_' => {
+                let mut end = start;
+                while let Some(&(i, c)) = chars.peek() {
+                    if !(c.is_alphanumeric() || c == '_') {
+                        break;
+                    }
+                    end = i + c.len_utf8();
+                    chars.next();
+                }
+                tokens.push(ExprToken::Name(source[start..end].to_string()));
+            }
+            _ => {
+                let rest = &source[start..];
+                let symbol = ["==", "!=", "|", ".", "(", ")", ","]
+                    .into_iter()
+                    .find(|symbol| rest.starts_with(symbol))
+                    .ok_or_else(|| format!("unexpected `{c}` in `{source}`"))?;
+                for _ in 0..symbol.len() {
+                    chars.next();
+                }
+                tokens.push(ExprToken::Symbol(symbol));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+struct ExprParser {
+    tokens: Vec<ExprToken>,
+    next: usize,
+}
+
+impl ExprParser {
+    fn peek(&self) -> Option<&ExprToken> {
+        self.tokens.get(self.next)
+    }
+
+    fn eat_name(&mut self, name: &str) -> bool {
+        let found = matches!(self.peek(), Some(ExprToken::Name(n)) if n == name);
+        self.next += usize::from(found);
+        found
+    }
+
+    fn eat_symbol(&mut self, symbol: &str) -> bool {
+        let found = matches!(self.peek(), Some(ExprToken::Symbol(s)) if *s == symbol);
+        self.next += usize::from(found);
+        found
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.parse_and()?;
+        while self.eat_name("or") {
+            expr = Expr::Or(Box::new(expr), Box::new(self.parse_and()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.parse_not()?;
+        while self.eat_name("and") {
+            expr = Expr::And(Box::new(expr), Box::new(self.parse_not()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_not(&mut self) -> Result<Expr, String> {
+        if self.eat_name("not") {
+            return Ok(Expr::Not(Box::new(self.parse_not()?)));
+        }
+        let left = self.parse_filtered()?;
+        for (symbol, equal) in [("==", true), ("!=", false)] {
+            if self.eat_symbol(symbol) {
+                let right = self.parse_filtered()?;
+                return Ok(Expr::Equal(Box::new(left), Box::new(right), equal));
+            }
+        }
+        Ok(left)
+    }
+
+    fn parse_filtered(&mut self) -> Result<Expr, String> {
+        let mut expr = self.parse_primary()?;
+        while self.eat_symbol("|") {
+            let Some(ExprToken::Name(filter)) = self.peek().cloned() else {
+                return Err("expected a filter name after `|`".into());
+            };
+            if !FILTERS.contains(&filter.as_str()) {
+                return Err(format!("unknown filter `{filter}`"));
+            }
+            self.next += 1;
+            let mut args = Vec::new();
+            if self.eat_symbol("(") && !self.eat_symbol(")") {
+                loop {
+                    args.push(self.parse_or()?);
+                    if self.eat_symbol(")") {
+                        break;
+                    }
+                    if !self.eat_symbol(",") {
+                        return Err(format!(
+                            "expected `,` or `)` in the arguments of `{filter}`"
+                        ));
+                    }
+                }
+            }
+            expr = Expr::Filter(Box::new(expr), filter, args);
+        }
+        Ok(expr)
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, String> {
+        let token = self.peek().cloned();
+        self.next += 1;
+        match token {
+            Some(ExprToken::String(s)) => Ok(Expr::Literal(Value::String(s))),
+            Some(ExprToken::Number(n)) => Ok(Expr::Literal(n)),
+            Some(ExprToken::Name(name)) if name == "true" || name == "false" => {
+                Ok(Expr::Literal(Value::Boolean(name == "true")))
+            }
+            Some(ExprToken::Name(name)) => {
+                let mut path = vec![name];
+                while self.eat_symbol(".") {
+                    match self.peek().cloned() {
+                        Some(ExprToken::Name(segment)) => path.push(segment),
+                        Some(ExprToken::Number(Value::Integer(i))) => path.push(i.to_string()),
+                        _ => return Err("expected a name after `.`".into()),
+                    }
+                    self.next += 1;
+                }
+                Ok(Expr::Path(path))
+            }
+            Some(ExprToken::Symbol("(")) => {
+                let expr = self.parse_or()?;
+                if !self.eat_symbol(")") {
+                    return Err("expected `)`".into());
+                }
+                Ok(expr)
+            }
+            Some(token) => Err(format!("unexpected `{}`", token.text())),
+            None => Err("expected an expression".into()),
+        }
+    }
+}
+
+struct Renderer<'t> {
+    templates: &'t Templates,
+    context: &'t Table,
+    scopes: Vec<Table>,
+    /// The definitions of each block in the current layout chain, from the
+    /// most derived, with the templates they are in.
+    blocks: HashMap<String, Vec<(&'t Template, &'t [Node])>>,
+    /// The blocks being rendered, innermost last, with the index of the
+    /// definition in `blocks`.
+    rendering: Vec<(&'t str, usize)>,
+    depth: usize,
+}
+
+impl<'t> Renderer<'t> {
+    fn render_template(
+        &mut self,
+        name: &str,
+        line: usize,
+        from: &str,
+        out: &mut String,
+    ) -> Result<(), Error> {
+        let error = |message: String| Error {
+            template: from.to_string(),
+            line,
+            message,
+        };
+        if self.depth >= MAX_DEPTH {
+            return Err(error(format!(
+                "`{name}` is nested too deeply, is there a cycle?"
+            )));
+        }
+        let Some(mut template) = self.templates.templates.get(name) else {
+            return Err(error(format!("template `{name}` not found")));
+        };
+        let mut chain = vec![template];
+        while let Some((parent, line)) = &template.extends {
+            template = self.templates.templates.get(parent).ok_or_else(|| Error {
+                template: template.name.clone(),
+                line: *line,
+                message: format!("template `{parent}` not found"),
+            })?;
+            if chain.len() >= MAX_DEPTH {
+                return Err(error(format!(
+                    "`{name}` extends too many layouts, is there a cycle?"
+                )));
+            }
+            chain.push(template);
+        }
+        self.depth += 1;
+        let saved_blocks = std::mem::take(&mut self.blocks);
+        let saved_rendering = std::mem::take(&mut self.rendering);
+        for template in chain {
+            collect_blocks(template, &template.nodes, &mut self.blocks);
+        }
+        let result = self.render_nodes(template, &template.nodes, out);
+        self.blocks = saved_blocks;
+        self.rendering = saved_rendering;
+        self.depth -= 1;
+        result
+    }
+
+    fn render_nodes(
+        &mut self,
+        template: &'t Template,
+        nodes: &'t [Node],
+        out: &mut String,
+    ) -> Result<(), Error> {
+        let error = |line, message: String| Error {
+            template: template.name.clone(),
+            line,
+            message,
+        };
+        for node in nodes {
+            match node {
+                Node::Text(text) => out.push_str(text),
+                Node::Print { expr, safe, line } => {
+                    let value = self.eval(expr).map_err(|message| error(*line, message))?;
+                    let text = display(&value).map_err(|message| error(*line, message))?;
+                    if *safe {
+                        out.push_str(&text);
+                    } else {
+                        out.push_str(&html::escape(&text));
+                    }
+                }
+                Node::If {
+                    branches,
+                    otherwise,
+                    line,
+                } => {
+                    let mut body = otherwise;
+                    for (condition, branch) in branches {
+                        let value = self
+                            .eval(condition)
+                            .map_err(|message| error(*line, message))?;
+                        if truthy(&value) {
+                            body = branch;
+                            break;
+                        }
+                    }
+                    self.render_nodes(template, body, out)?;
+                }
+                Node::For {
+                    variable,
+                    iterable,
+                    body,
+                    line,
+                } => {
+                    let items = match self
+                        .eval(iterable)
+                        .map_err(|message| error(*line, message))?
+                    {
+                        Value::Array(items) => items,
+                        Value::Null => Vec::new(),
+                        other => {
+                            return Err(error(
+                                *line,
+                                format!("cannot loop over a {}", other.type_name()),
+                            ))
+                        }
+                    };
+                    let count = items.len();
+                    for (index, item) in items.into_iter().enumerate() {
+                        let mut scope = Table::new();
+                        scope.insert(variable.clone(), item);
+                        scope.insert(
+                            "loop".into(),
+                            Value::Table(Table::from([
+                                ("index".into(), Value::Integer(index as i64 + 1)),
+                                ("first".into(), Value::Boolean(index == 0)),
+                                ("last".into(), Value::Boolean(index + 1 == count)),
+                            ])),
+                        );
+                        self.scopes.push(scope);
+                        let result = self.render_nodes(template, body, out);
+                        self.scopes.pop();
+                        result?;
+                    }
+                }
+                Node::Block { name, body } => {
+                    let (template, body) = self
+                        .blocks
+                        .get(name.as_str())
+                        .map_or((template, &body[..]), |definitions| definitions[0]);
+                    self.rendering.push((name, 0));
+                    let result = self.render_nodes(template, body, out);
+                    self.rendering.pop();
+                    result?;
+                }
+                Node::Super { line } => {
+                    let Some(&(name, index)) = self.rendering.last() else {
+                        return Err(error(*line, "`super()` is only allowed in a block".into()));
+                    };
+                    let Some(&(parent, body)) = self.blocks[name].get(index + 1) else {
+                        return Err(error(
+                            *line,
+                            format!("block `{name}` does not override another"),
+                        ));
+                    };
+                    self.rendering.push((name, index + 1));
+                    let result = self.render_nodes(parent, body, out);
+                    self.rendering.pop();
+                    result?;
+                }
+                Node::Include { name, line } => {
+                    // Partials render with their own blocks, but see the
+                    // variables of the template that includes them.
+                    self.render_template(name, *line, &template.name, out)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn lookup(&self, path: &[String]) -> Result<Value, String> {
+        let (first, rest) = path.split_first().expect("paths are never empty");
+        let root = self
+            .scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(first))
+            .or_else(|| self.context.get(first));
+        let Some(mut value) = root else {
+            return Err(format!("unknown variable `{first}`"));
+        };
+        for segment in rest {
+            let next = match value {
+                Value::Table(table) => table.get(segment),
+                Value::Array(items) => segment.parse().ok().and_then(|i: usize| items.get(i)),
+                _ => None,
+            };
+            match next {
+                Some(next) => value = next,
+                None => return Ok(Value::Null),
+            }
+        }
+        Ok(value.clone())
+    }
+
+    fn eval(&self, expr: &Expr) -> Result<Value, String> {
+        Ok(match expr {
+            Expr::Literal(value) => value.clone(),
+            Expr::Path(path) => self.lookup(path)?,
+            Expr::Not(inner) => Value::Boolean(!truthy(&self.eval(inner)?)),
+            Expr::And(left, right) => {
+                let left = self.eval(left)?;
+                if truthy(&left) {
+                    self.eval(right)?
+                } else {
+                    left
+                }
+            }
+            Expr::Or(left, right) => {
+                let left = self.eval(left)?;
+                if truthy(&left) {
+                    left
+                } else {
+                    self.eval(right)?
+                }
+            }
+            Expr::Equal(left, right, equal) => {
+                Value::Boolean((self.eval(left)? == self.eval(right)?) == *equal)
+            }
+            Expr::Filter(inner, filter, args) => {
+                let value = match (filter.as_str(), &**inner) {
+                    // Only the root of a path can be unknown.
+                    ("default", Expr::Path(path)) => self.lookup(path).unwrap_or(Value::Null),
+                    _ => self.eval(inner)?,
+                };
+                let args = args
+                    .iter()
+                    .map(|arg| self.eval(arg))
+                    .collect::<Result<Vec<_>, _>>()?;
+                apply_filter(value, filter, &args)?
+            }
+        })
+    }
+}
+
+/// Adds the blocks defined in `nodes`, which are in `template`, to `blocks`.
+/// Templates are collected from the most derived.
+fn collect_blocks<'t>(
+    template: &'t Template,
+    nodes: &'t [Node],
+    blocks: &mut HashMap<String, Vec<(&'t Template, &'t [Node])>>,
+) {
+    for node in nodes {
+        match node {
+            Node::Block { name, body } => {
+                blocks
+                    .entry(name.clone())
+                    .or_default()
+                    .push((template, body));
+                collect_blocks(template, body, blocks);
+            }
+            Node::If {
+                branches,
+                otherwise,
+                ..
+            } => {
+                for (_, body) in branches {
+                    collect_blocks(template, body, blocks);
+                }
+                collect_blocks(template, otherwise, blocks);
+            }
+            Node::For { body, .. } => collect_blocks(template, body, blocks),
+            _ => {}
+        }
+    }
+}
+
+fn apply_filter(value: Value, filter: &str, args: &[Value]) -> Result<Value, String> {
+    let arity = |expected: usize| {
+        if args.len() == expected {
+            Ok(())
+        } else {
+            Err(format!(
+                "`{filter}` takes {expected} argument(s), found {}",
+                args.len()
+            ))
+        }
+    };
+    Ok(match filter {
+        "safe" => return Err("`safe` must be the last filter".into()),
+        "trim" | "lower" | "upper" => {
+            arity(0)?;
+            let text = display(&value)?;
+            Value::String(match filter {
+                "trim" => text.trim().to_string(),
+                "lower" => text.to_lowercase(),
+                _ => text.to_uppercase(),
+            })
+        }
+        "length" => {
+            arity(0)?;
+            let length = match &value {
+                Value::Array(items) => items.len(),
+                Value::Table(table) => table.len(),
+                Value::String(s) => s.chars().count(),
+                Value::Null => 0,
+                other => return Err(format!("a {} has no length", other.type_name())),
+            };
+            Value::Integer(length as i64)
+        }
+        "join" => {
+            arity(1)?;
+            let separator = display(&args[0])?;
+            match value {
+                Value::Array(items) => Value::String(
+                    items
+                        .iter()
+                        .map(display)
+                        .collect::<Result<Vec<_>, _>>()?
+                        .join(&separator),
+                ),
+                other => other,
+            }
+        }
+        "default" => {
+            arity(1)?;
+            if truthy(&value) {
+                value
+            } else {
+                args[0].clone()
+            }
+        }
+        _ => return Err(format!("unknown filter `{filter}`")),
+    })
+}
+
+fn truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Boolean(b) => *b,
+        Value::Integer(i) => *i != 0,
+        Value::Float(f) => *f != 0.0,
+        Value::String(s) => !s.is_empty(),
+        Value::Array(items) => !items.is_empty(),
+        Value::Table(table) => !table.is_empty(),
+    }
+}
+
+fn display(value: &Value) -> Result<String, String> {
+    match value {
+        Value::Null => Ok(String::new()),
+        Value::Boolean(b) => Ok(b.to_string()),
+        Value::Integer(i) => Ok(i.to_string()),
+        Value::Float(f) => Ok(f.to_string()),
+        Value::String(s) => Ok(s.clone()),
+        other => Err(format!("cannot print a {}", other.type_name())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::run::TempDir;
+
+    /// Loads the built-in theme with `files`, pairs of a template name and
+    /// source, in a site's template directory.
+    fn load(files: &[(&str, &str)]) -> Result<Templates, String> {
+        let dir = TempDir::new().unwrap();
+        for (name, source) in files {
+            let path = dir.path.join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, source).unwrap();
+        }
+        Templates::load(&dir.path).map_err(|e| e.to_string())
+    }
+
+    fn render(files: &[(&str, &str)], context: &Table) -> Result<String, String> {
+        load(files)?
+            .render(files[0].0, context)
+            .map_err(|e| e.to_string())
+    }
+
+    fn table(pairs: &[(&str, Value)]) -> Table {
+        pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.clone()))
+            .collect()
+    }
+
+    fn string(s: &str) -> Value {
+        Value::String(s.into())
+    }
+
+    #[test]
+    fn blocks() {
+        let layout = (
+            "layout.html",
+            "<h1>{% block title %}Base{% endblock %}</h1>{% block body %}<p>base</p>{% endblock %}",
+        );
+        let middle = (
+            "middle.html",
+            r#"{% extends "layout.html" %}{% block body %}{{ super() }}<p>middle</p>{% endblock %}"#,
+        );
+        let child = (
+            "child.html",
+            r#"{% extends "middle.html" %}
+{% block title %}Child ({{ super() }}){% endblock %}
+{% block body %}{{ super() }}<p>child</p>{% endblock %}"#,
+        );
+        let context = Table::new();
+        assert_eq!(
+            render(&[middle, layout, child], &context).unwrap(),
+            "<h1>Base</h1><p>base</p><p>middle</p>"
+        );
+        assert_eq!(
+            render(&[child, middle, layout], &context).unwrap(),
+            "<h1>Child (Base)</h1><p>base</p><p>middle</p><p>child</p>"
+        );
+        assert_eq!(
+            render(&[("outside.html", "\n{{ super() }}")], &context).unwrap_err(),
+            "template `outside.html`, line 2: `super()` is only allowed in a block"
+        );
+        assert_eq!(
+            render(
+                &[("top.html", "{% block a %}{{ super() }}{% endblock %}")],
+                &context
+            )
+            .unwrap_err(),
+            "template `top.html`, line 1: block `a` does not override another"
+        );
+    }
+
+    #[test]
+    fn includes() {
+        let context = table(&[("name", string("x"))]);
+        assert_eq!(
+            render(
+                &[
+                    ("page.html", r#"[{% include "partials/name.html" %}]"#),
+                    ("partials/name.html", "{{ name }}"),
+                ],
+                &context
+            )
+            .unwrap(),
+            "[x]"
+        );
+        let error = render(
+            &[("loop.html", "\n\n{% include \"loop.html\" %}")],
+            &context,
+        )
+        .unwrap_err();
+        assert_eq!(
+            error,
+            "template `loop.html`, line 3: `loop.html` is nested too deeply, is there a cycle?"
+        );
+        assert_eq!(
+            render(&[("missing.html", "{% include \"nope.html\" %}")], &context).unwrap_err(),
+            "template `missing.html`, line 1: template `nope.html` not found"
+        );
+    }
+
+    #[test]
+    fn escaping() {
+        let context = table(&[("s", string("<b class=\"x\">Tom & Jerry's</b>"))]);
+        assert_eq!(
+            render(&[("page.html", "{{ s }}")], &context).unwrap(),
+            "&lt;b class=&quot;x&quot;&gt;Tom &amp; Jerry&#39;s&lt;/b&gt;"
+        );
+        assert_eq!(
+            render(&[("page.html", "{{ s | safe }}")], &context).unwrap(),
+            "<b class=\"x\">Tom & Jerry's</b>"
+        );
+        assert_eq!(
+            render(&[("page.html", "{{ s | upper }}")], &context).unwrap(),
+            "&lt;B CLASS=&quot;X&quot;&gt;TOM &amp; JERRY&#39;S&lt;/B&gt;"
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let context = table(&[("page", Value::Table(table(&[("title", string("T"))])))]);
+        assert_eq!(
+            render(&[("page.html", "\n\n{{ page.title | shout }}")], &context).unwrap_err(),
+            "template `page.html`, line 3: unknown filter `shout`"
+        );
+        assert_eq!(
+            render(
+                &[
+                    (
+                        "child.html",
+                        "{% extends \"layout.html\" %}\n{% block body %}\n{{ missing }}{% endblock %}"
+                    ),
+                    ("layout.html", "{% block body %}{% endblock %}"),
+                ],
+                &context
+            )
+            .unwrap_err(),
+            "template `child.html`, line 3: unknown variable `missing`"
+        );
+        assert_eq!(
+            render(
+                &[(
+                    "page.html",
+                    "{{ missing | default(\"none\") }} [{{ page.missing }}] {{ page.title }}"
+                )],
+                &context
+            )
+            .unwrap(),
+            "none [] T"
+        );
+        assert_eq!(
+            render(
+                &[("page.html", "{% for x in page %}{% endfor %}")],
+                &context
+            )
+            .unwrap_err(),
+            "template `page.html`, line 1: cannot loop over a table"
+        );
+    }
+
+    /// The default theme renders the page shell the generator produced
+    /// before it had templates. Only style rules have been added since.
+    #[test]
+    fn default_theme() {
+        let theme = table(&[
+            ("text", string("#111")),
+            ("background", string("#eee")),
+            ("link", string("blue")),
+            ("dark_text", string("#ddd")),
+            ("dark_background", string("#222")),
+            ("dark_link", string("skyblue")),
+        ]);
+        let config = table(&[
+            ("language", string("en")),
+            ("title", string("Blog")),
+            ("author", string("Ann")),
+            ("base_path", string("")),
+            ("theme", Value::Table(theme)),
+        ]);
+        let page = table(&[
+            ("title", string("Fish & Chips")),
+            ("url", string("fish.html")),
+            ("content", string("<p>Hello</p>")),
+            ("description", string(" About fish. ")),
+            ("tags", Value::Array(vec![string("food"), string("uk")])),
+            ("date", string("2024-01-02")),
+            ("draft", Value::Boolean(false)),
+            ("scheduled", Value::Boolean(false)),
+        ]);
+        let context = table(&[
+            ("config", Value::Table(config)),
+            ("page", Value::Table(page)),
+        ]);
+        let rendered = load(&[]).unwrap().render("page.html", &context).unwrap();
+
+        let (head, rest) = rendered.split_once("    <style>\n").unwrap();
+        let (style, body) = rest.split_once("    </style>\n").unwrap();
+        assert_eq!(
+            head,
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta charset="utf-8" />
+    <meta name="viewport" content="width=device-width, initial-scale=1.0" />
+    <meta name="apple-mobile-web-app-capable" content="yes">
+    <title>Fish &amp; Chips</title>
+    <meta name="description" content="About fish." />
+    <meta name="author" content="Ann" />
+    <meta name="keywords" content="food, uk" />
+    <meta property="article:published_time" content="2024-01-02" />
+"#
+        );
+        assert_eq!(
+            body,
+            "</head>\n<body>\n<main>\n<p>Hello</p>\n</main>\n</body>\n</html>"
+        );
+        let old_style = "        html, body {
+            overflow-x: hidden;
+        }
+        body {
+            color: #111;
+            background-color: #eee;
+        }
+        :link, :visited, :visited:active {
+            color: blue;
+        }
+        @media (prefers-color-scheme: dark) {
+            body {
+                color: #ddd;
+                background-color: #222;
+            }
+            :link, :visited, :visited:active {
+                color: skyblue;
+            }
+        }
+        body {
+            position: relative;
+            box-sizing: border-box;
+            -webkit-box-sizing: border-box;
+            -moz-box-sizing: border-box;
+        }
+        pre {
+            overflow-x: scroll;
+        }
+        img {
+            max-width: 100%;
+        }";
+        let mut lines = style.lines();
+        for line in old_style.lines() {
+            assert!(
+                lines.any(|rendered| rendered == line),
+                "`{line}` is missing or out of order in:\n{style}"
+            );
+        }
+    }
+}
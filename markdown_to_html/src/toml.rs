@@ -0,0 +1,714 @@
+//! A small TOML parser, and a writer for single values.
+//!
+//! Covers tables, arrays of tables, dotted keys, inline tables, arrays and all
+//! string, integer, float and boolean forms. Dates and times are kept as
+//! strings so callers can interpret them with their own rules.
+
+use std::collections::HashSet;
+
+use crate::value::{ParseError, Table, Value};
+
+pub fn parse(source: &str) -> Result<Table, ParseError> {
+    let mut parser = Parser {
+        src: source,
+        pos: 0,
+        line: 1,
+    };
+    let mut root = Table::new();
+    let mut current: Vec<String> = Vec::new();
+    let mut headers: HashSet<Vec<String>> = HashSet::new();
+    loop {
+        parser.skip_whitespace();
+        match parser.peek() {
+            None => break,
+            Some('#' | '\n' | '\r') => parser.expect_line_end()?,
+            Some('[') => {
+                parser.bump();
+                let array = parser.eat('[');
+                let path = parser.parse_key()?;
+                parser.skip_whitespace();
+                if !parser.eat(']') || (array && !parser.eat(']')) {
+                    return parser.error("expected `]` to close the table header");
+                }
+                let (last, parents) = path.split_last().expect("keys are never empty");
+                let parent = descend(&mut root, parents).or_else(|e| parser.error(e))?;
+                if array {
+                    let entry = parent
+                        .entry(last.clone())
+                        .or_insert_with(|| Value::Array(Vec::new()));
+                    match entry {
+                        Value::Array(items) => items.push(Value::Table(Table::new())),
+                        other => {
+                            return parser.error(format!(
+                                "`{last}` is already defined as a {}",
+                                other.type_name()
+                            ))
+                        }
+                    }
+                    // Each element of the array starts with none of its
+                    // tables defined.
+                    headers.retain(|header| !header.starts_with(&path));
+                } else {
+                    if let Some(Value::Array(_)) = parent.get(last) {
+                        return parser.error(format!(
+                            "`{}` is already defined as an array",
+                            path.join(".")
+                        ));
+                    }
+                    if !headers.insert(path.clone()) {
+                        return parser.error(format!("table `{}` defined twice", path.join(".")));
+                    }
+                    descend(parent, std::slice::from_ref(last)).or_else(|e| parser.error(e))?;
+                }
+                parser.expect_line_end()?;
+                current = path;
+            }
+            Some(_) => {
+                let line = parser.line;
+                let key = parser.parse_key()?;
+                parser.skip_whitespace();
+                if !parser.eat('=') {
+                    return parser.error("expected `=` after key");
+                }
+                parser.skip_whitespace();
+                let value = parser.parse_value()?;
+                let table = descend(&mut root, &current).or_else(|e| parser.error(e))?;
+                insert(table, &key, value).map_err(|message| ParseError { line, message })?;
+                parser.expect_line_end()?;
+            }
+        }
+    }
+    Ok(root)
+}
+
+/// Walks `path` from `table`, creating missing tables and entering the last
+/// element of arrays of tables.
+fn descend<'t>(mut table: &'t mut Table, path: &[String]) -> Result<&'t mut Table, String> {
+    for key in path {
+        let value = table
+            .entry(key.clone())
+            .or_insert_with(|| Value::Table(Table::new()));
+        table = match value {
+            Value::Table(inner) => inner,
+            Value::Array(items) => match items.last_mut() {
+                Some(Value::Table(inner)) => inner,
+                _ => return Err(format!("`{key}` is not an array of tables")),
+            },
+            other => {
+                return Err(format!(
+                    "`{key}` is already defined as a {}",
+                    other.type_name()
+                ))
+            }
+        };
+    }
+    Ok(table)
+}
+
+fn insert(table: &mut Table, key: &[String], value: Value) -> Result<(), String> {
+    let (last, parents) = key.split_last().expect("keys are never empty");
+    let table = descend(table, parents)?;
+    if table.contains_key(last) {
+        return Err(format!("duplicate key `{}`", key.join(".")));
+    }
+    table.insert(last.clone(), value);
+    Ok(())
+}
+
+fn is_bare_key_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '-'
+}
+
+struct Parser<'a> {
+    src: &'a str,
+    pos: usize,
+    line: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.src[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        if c == '\n' {
+            self.line += 1;
+        }
+        Some(c)
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.bump();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn error<T>(&self, message: impl Into<String>) -> Result<T, ParseError> {
+        Err(ParseError {
+            line: self.line,
+            message: message.into(),
+        })
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t')) {
+            self.bump();
+        }
+    }
+
+    fn skip_comment(&mut self) {
+        if self.peek() == Some('#') {
+            while !matches!(self.peek(), None | Some('\n' | '\r')) {
+                self.bump();
+            }
+        }
+    }
+
+    /// Skips whitespace, comments and newlines, as allowed inside arrays.
+    fn skip_trivia(&mut self) {
+        loop {
+            self.skip_whitespace();
+            self.skip_comment();
+            if !(self.eat('\n') || self.eat('\r')) {
+                break;
+            }
+        }
+    }
+
+    fn expect_line_end(&mut self) -> Result<(), ParseError> {
+        self.skip_whitespace();
+        self.skip_comment();
+        self.eat('\r');
+        match self.peek() {
+            None => Ok(()),
+            Some('\n') => {
+                self.bump();
+                Ok(())
+            }
+            Some(c) => self.error(format!("expected the end of the line, found `{c}`")),
+        }
+    }
+
+    fn parse_key(&mut self) -> Result<Vec<String>, ParseError> {
+        let mut segments = Vec::new();
+        loop {
+            self.skip_whitespace();
+            let segment = match self.peek() {
+                Some('"') => self.parse_basic_string()?,
+                Some('\'') => self.parse_literal_string()?,
+                Some(c) if is_bare_key_char(c) => {
+                    let start = self.pos;
+                    while self.peek().is_some_and(is_bare_key_char) {
+                        self.bump();
+                    }
+                    self.src[start..self.pos].to_string()
+                }
+                _ => return self.error("expected a key"),
+            };
+            segments.push(segment);
+            self.skip_whitespace();
+            if !self.eat('.') {
+                return Ok(segments);
+            }
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Value, ParseError> {
+        let rest = self.rest();
+        match self.peek() {
+            Some('"') if rest.starts_with("\"\"\"") => {
+                self.parse_multiline_string('"').map(Value::String)
+            }
+            Some('"') => self.parse_basic_string().map(Value::String),
+            Some('\'') if rest.starts_with("'''") => {
+                self.parse_multiline_string('\'').map(Value::String)
+            }
+            Some('\'') => self.parse_literal_string().map(Value::String),
+            Some('[') => self.parse_array(),
+            Some('{') => self.parse_inline_table(),
+            Some(_) => self.parse_scalar(),
+            None => self.error("expected a value"),
+        }
+    }
+
+    fn parse_scalar(&mut self) -> Result<Value, ParseError> {
+        let start = self.pos;
+        while self
+            .peek()
+            .is_some_and(|c| c.is_ascii_alphanumeric() || "_+-.:".contains(c))
+        {
+            self.bump();
+        }
+        // Allow the space-separated form of local date-times.
+        let token = &self.src[start..self.pos];
+        let next = self.rest().as_bytes();
+        if is_date(token)
+            && next.len() >= 4
+            && next[0] == b' '
+            && next[1].is_ascii_digit()
+            && next[2].is_ascii_digit()
+            && next[3] == b':'
+        {
+            self.bump();
+            while self
+                .peek()
+                .is_some_and(|c| c.is_ascii_alphanumeric() || "+-.:".contains(c))
+            {
+                self.bump();
+            }
+        }
+        let token = &self.src[start..self.pos];
+        if token.is_empty() {
+            return match self.peek() {
+                Some(c) => self.error(format!("expected a value, found `{c}`")),
+                None => self.error("expected a value"),
+            };
+        }
+        let value = match token {
+            "true" => Some(Value::Boolean(true)),
+            "false" => Some(Value::Boolean(false)),
+            "inf" | "+inf" => Some(Value::Float(f64::INFINITY)),
+            "-inf" => Some(Value::Float(f64::NEG_INFINITY)),
+            "nan" | "+nan" | "-nan" => Some(Value::Float(f64::NAN)),
+            _ if is_date(token) || is_time(token) => Some(Value::String(token.to_string())),
+            _ => parse_number(token),
+        };
+        value.map_or_else(|| self.error(format!("invalid value `{token}`")), Ok)
+    }
+
+    fn parse_basic_string(&mut self) -> Result<String, ParseError> {
+        self.bump();
+        let mut out = String::new();
+        loop {
+            match self.bump() {
+                None => return self.error("unterminated string"),
+                Some('\n') => {
+                    self.line -= 1;
+                    return self.error("unterminated string");
+                }
+                Some('"') => return Ok(out),
+                Some('\\') => self.parse_escape(&mut out)?,
+                Some(c) => out.push(c),
+            }
+        }
+    }
+
+    fn parse_literal_string(&mut self) -> Result<String, ParseError> {
+        self.bump();
+        let start = self.pos;
+        loop {
+            match self.bump() {
+                None => return self.error("unterminated string"),
+                Some('\n') => {
+                    self.line -= 1;
+                    return self.error("unterminated string");
+                }
+                Some('\'') => return Ok(self.src[start..self.pos - 1].to_string()),
+                Some(_) => {}
+            }
+        }
+    }
+
+    fn parse_multiline_string(&mut self, quote: char) -> Result<String, ParseError> {
+        self.pos += 3;
+        // A newline directly after the opening delimiter is trimmed.
+        if self.rest().starts_with("\r\n") {
+            self.bump();
+        }
+        self.eat('\n');
+        let mut out = String::new();
+        loop {
+            let quotes = self.rest().chars().take_while(|&c| c == quote).count();
+            if quotes >= 3 {
+                if quotes > 5 {
+                    return self.error("too many quotes at the end of a multi-line string");
+                }
+                out.extend(std::iter::repeat_n(quote, quotes - 3));
+                self.pos += quotes;
+                return Ok(out);
+            }
+            match self.bump() {
+                None => return self.error("unterminated multi-line string"),
+                Some('\\') if quote == '"' => {
+                    let rest = self.rest();
+                    let trimmed = rest.trim_start_matches([' ', '\t']);
+                    if trimmed.starts_with('\n') || trimmed.starts_with("\r\n") {
+                        while matches!(self.peek(), Some(' ' | '\t' | '\n' | '\r')) {
+                            self.bump();
+                        }
+                    } else {
+                        self.parse_escape(&mut out)?;
+                    }
+                }
+                Some(c) => out.push(c),
+            }
+        }
+    }
+
+    fn parse_escape(&mut self, out: &mut String) -> Result<(), ParseError> {
+        let c = match self.bump() {
+            Some('b') => '\u{8}',
+            Some('t') => '\t',
+            Some('n') => '\n',
+            Some('f') => '\u{c}',
+            Some('r') => '\r',
+            Some('e') => '\u{1b}',
+            Some('"') => '"',
+            Some('\\') => '\\',
+            Some(kind @ ('u' | 'U')) => {
+                let len = if kind == 'u' { 4 } else { 8 };
+                let hex = self.rest().get(..len).unwrap_or_default();
+                let c = u32::from_str_radix(hex, 16).ok().and_then(char::from_u32);
+                match c {
+                    Some(c) if hex.len() == len => {
+                        self.pos += len;
+                        c
+                    }
+                    _ => return self.error(format!("invalid unicode escape `\\{kind}{hex}`")),
+                }
+            }
+            Some(c) => return self.error(format!("invalid escape `\\{c}`")),
+            None => return self.error("unterminated string"),
+        };
+        out.push(c);
+        Ok(())
+    }
+
+    fn parse_array(&mut self) -> Result<Value, ParseError> {
+        self.bump();
+        let mut items = Vec::new();
+        loop {
+            self.skip_trivia();
+            if self.eat(']') {
+                return Ok(Value::Array(items));
+            }
+            items.push(self.parse_value()?);
+            self.skip_trivia();
+            if self.eat(']') {
+                return Ok(Value::Array(items));
+            }
+            if !self.eat(',') {
+                return self.error("expected `,` or `]` in array");
+            }
+        }
+    }
+
+    fn parse_inline_table(&mut self) -> Result<Value, ParseError> {
+        self.bump();
+        let mut table = Table::new();
+        loop {
+            self.skip_trivia();
+            if self.eat('}') {
+                return Ok(Value::Table(table));
+            }
+            let key = self.parse_key()?;
+            if !self.eat('=') {
+                return self.error("expected `=` after key");
+            }
+            self.skip_whitespace();
+            let value = self.parse_value()?;
+            insert(&mut table, &key, value).or_else(|e| self.error(e))?;
+            self.skip_trivia();
+            if self.eat('}') {
+                return Ok(Value::Table(table));
+            }
+            if !self.eat(',') {
+                return self.error("expected `,` or `}` in inline table");
+            }
+        }
+    }
+}
+
+/// Serializes `value` on one line, with tables written inline. Nulls, which
+/// TOML cannot represent, are left out of arrays and tables and are otherwise
+/// written as an empty string.
+pub fn to_inline_string(value: &Value) -> String {
+    match value {
+        Value::Null => "\"\"".to_string(),
+        Value::Boolean(b) => b.to_string(),
+        Value::Integer(i) => i.to_string(),
+        Value::Float(f) if f.is_nan() => "nan".to_string(),
+        Value::Float(f) if f.is_infinite() => if *f > 0.0 { "inf" } else { "-inf" }.to_string(),
+        Value::Float(f) => format!("{f:?}"),
+        Value::String(s) => {
+            let mut out = String::from('"');
+            for c in s.chars() {
+                match c {
+                    '"' => out.push_str("\\\""),
+                    '\\' => out.push_str("\\\\"),
+                    '\n' => out.push_str("\\n"),
+                    '\t' => out.push_str("\\t"),
+                    c if c.is_control() => out.push_str(&format!("\\u{:04X}", c as u32)),
+                    c => out.push(c),
+                }
+            }
+            out.push('"');
+            out
+        }
+        Value::Array(items) => {
+            let items: Vec<String> = items
+                .iter()
+                .filter(|item| !matches!(item, Value::Null))
+                .map(to_inline_string)
+                .collect();
+            format!("[{}]", items.join(", "))
+        }
+        Value::Table(table) => {
+            let entries: Vec<String> = table
+                .iter()
+                .filter(|(_, item)| !matches!(item, Value::Null))
+                .map(|(key, item)| format!("{} = {}", key_string(key), to_inline_string(item)))
+                .collect();
+            match entries.is_empty() {
+                true => "{}".to_string(),
+                false => format!("{{ {} }}", entries.join(", ")),
+            }
+        }
+    }
+}
+
+/// `key` bare if it can be, and quoted otherwise.
+pub fn key_string(key: &str) -> String {
+    let bare = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    match bare {
+        true => key.to_string(),
+        false => to_inline_string(&Value::String(key.to_string())),
+    }
+}
+
+/// Parses an integer or a float as TOML writes them: underscores only between
+/// digits, no leading zeros, and digits on both sides of a decimal point.
+fn parse_number(token: &str) -> Option<Value> {
+    for (prefix, radix) in [("0x", 16), ("0o", 8), ("0b", 2)] {
+        if let Some(digits) = token.strip_prefix(prefix) {
+            if !separated_digits(digits, radix) {
+                return None;
+            }
+            return i64::from_str_radix(&digits.replace('_', ""), radix)
+                .ok()
+                .map(Value::Integer);
+        }
+    }
+    let unsigned = token.strip_prefix(['+', '-']).unwrap_or(token);
+    let (mantissa, exponent) = match unsigned.split_once(['e', 'E']) {
+        Some((mantissa, exponent)) => (mantissa, Some(exponent)),
+        None => (unsigned, None),
+    };
+    let (integer, fraction) = match mantissa.split_once('.') {
+        Some((integer, fraction)) => (integer, Some(fraction)),
+        None => (mantissa, None),
+    };
+    let valid = separated_digits(integer, 10)
+        && (integer == "0" || !integer.starts_with('0'))
+        && fraction.is_none_or(|fraction| separated_digits(fraction, 10))
+        && exponent.is_none_or(|exponent| {
+            separated_digits(exponent.strip_prefix(['+', '-']).unwrap_or(exponent), 10)
+        });
+    if !valid {
+        return None;
+    }
+    let digits = token.replace('_', "");
+    match fraction.is_some() || exponent.is_some() {
+        true => digits.parse().ok().map(Value::Float),
+        false => digits.parse().ok().map(Value::Integer),
+    }
+}
+
+/// Whether `digits` are digits in `radix` with single underscores between
+/// them.
+fn separated_digits(digits: &str, radix: u32) -> bool {
+    !digits.is_empty()
+        && !digits.starts_with('_')
+        && !digits.ends_with('_')
+        && !digits.contains("__")
+        && digits.chars().all(|c| c == '_' || c.is_digit(radix))
+}
+
+/// Whether `token` starts with a `YYYY-MM-DD` date.
+fn is_date(token: &str) -> bool {
+    let b = token.as_bytes();
+    b.len() >= 10 && b[..4].iter().all(u8::is_ascii_digit) && b[4] == b'-' && b[7] == b'-'
+}
+
+/// Whether `token` is a local `HH:MM[:SS]` time.
+fn is_time(token: &str) -> bool {
+    let b = token.as_bytes();
+    b.len() >= 5 && b[..2].iter().all(u8::is_ascii_digit) && b[2] == b':'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn value(source: &str) -> Value {
+        parse(&format!("v = {source}"))
+            .unwrap_or_else(|e| panic!("`{source}`: {e}"))
+            .remove("v")
+            .unwrap()
+    }
+
+    fn error(source: &str) -> ParseError {
+        match parse(source) {
+            Ok(table) => panic!("`{source}` parsed as {table:?}"),
+            Err(e) => e,
+        }
+    }
+
+    fn string(s: &str) -> Value {
+        Value::String(s.to_string())
+    }
+
+    #[test]
+    fn strings() {
+        assert_eq!(value(r#""a\tb\u00e9\"""#), string("a\tbé\""));
+        assert_eq!(value(r"'C:\path'"), string(r"C:\path"));
+        assert_eq!(value("\"\"\"\none\ntwo\"\"\""), string("one\ntwo"));
+        assert_eq!(value("\"\"\"one \\\n    two\"\"\""), string("one two"));
+        assert_eq!(value("'''\n'quoted' \\n'''"), string("'quoted' \\n"));
+        assert_eq!(value(r#""""a""""""#), string("a\"\""));
+        assert!(parse("v = \"open\n\"").is_err());
+        assert!(parse(r#"v = "\q""#).is_err());
+    }
+
+    #[test]
+    fn integers() {
+        assert_eq!(value("42"), Value::Integer(42));
+        assert_eq!(value("+42"), Value::Integer(42));
+        assert_eq!(value("-17"), Value::Integer(-17));
+        assert_eq!(value("0"), Value::Integer(0));
+        assert_eq!(value("-0"), Value::Integer(0));
+        assert_eq!(value("1_000_000"), Value::Integer(1_000_000));
+        assert_eq!(value("0xdead_BEEF"), Value::Integer(0xdead_beef));
+        assert_eq!(value("0o755"), Value::Integer(0o755));
+        assert_eq!(value("0b1101"), Value::Integer(0b1101));
+    }
+
+    #[test]
+    fn floats() {
+        assert_eq!(value("2.5"), Value::Float(2.5));
+        assert_eq!(value("-0.5"), Value::Float(-0.5));
+        assert_eq!(value("5e+22"), Value::Float(5e22));
+        assert_eq!(value("1E06"), Value::Float(1e6));
+        assert_eq!(value("6.626e-34"), Value::Float(6.626e-34));
+        assert_eq!(value("9_224.617_445"), Value::Float(9224.617445));
+        assert_eq!(value("-inf"), Value::Float(f64::NEG_INFINITY));
+        assert!(matches!(value("nan"), Value::Float(f) if f.is_nan()));
+    }
+
+    #[test]
+    fn invalid_numbers() {
+        for number in [
+            "01", "+01", "00", "1__0", "_1", "1_", "1.", ".5", "1.e5", "1e", "1e_5", "01.5", "0x",
+            "0x_1", "+0x1", "0b2", "0o8", "1.2.3",
+        ] {
+            let e = error(&format!("v = {number}"));
+            assert_eq!(e.message, format!("invalid value `{number}`"));
+        }
+    }
+
+    #[test]
+    fn dates_are_kept_as_strings() {
+        assert_eq!(value("2024-01-11"), string("2024-01-11"));
+        assert_eq!(
+            value("2024-01-11T10:00:00+01:00"),
+            string("2024-01-11T10:00:00+01:00")
+        );
+        assert_eq!(value("2024-01-11 10:00:00"), string("2024-01-11 10:00:00"));
+        assert_eq!(value("07:32:00"), string("07:32:00"));
+    }
+
+    #[test]
+    fn arrays_and_inline_tables() {
+        assert_eq!(
+            value("[ 1, 'two', [3], ]"),
+            Value::Array(vec![
+                Value::Integer(1),
+                string("two"),
+                Value::Array(vec![Value::Integer(3)]),
+            ])
+        );
+        assert_eq!(
+            value("[\n  1, # one\n  2,\n]"),
+            Value::Array(vec![Value::Integer(1), Value::Integer(2)])
+        );
+        assert_eq!(
+            value("{ a = 1, b.c = true }"),
+            Value::Table(Table::from([
+                ("a".into(), Value::Integer(1)),
+                (
+                    "b".into(),
+                    Value::Table(Table::from([("c".into(), Value::Boolean(true))]))
+                ),
+            ]))
+        );
+        assert!(parse("v = [1 2]").is_err());
+        assert!(parse("v = { a = 1, a = 2 }").is_err());
+    }
+
+    #[test]
+    fn keys_and_tables() {
+        let table = parse(
+            "a.b = 1\n\"quoted key\" = 2\n'lit'.x = 3\n\n[t] # comment\nk = 4\n[t.u]\nk = 5\n",
+        )
+        .unwrap();
+        assert_eq!(
+            Value::Table(table),
+            value("{ a.b = 1, \"quoted key\" = 2, lit.x = 3, t = { k = 4, u.k = 5 } }")
+        );
+    }
+
+    #[test]
+    fn arrays_of_tables() {
+        let table = parse("[[x]]\nn = 1\n[x.y]\nm = 1\n[[x]]\nn = 2\n[x.y]\nm = 2\n").unwrap();
+        assert_eq!(
+            table["x"],
+            value("[{ n = 1, y = { m = 1 } }, { n = 2, y = { m = 2 } }]")
+        );
+        let table = parse("[[a.b]]\nn = 1\n[[a.b]]\nn = 2\n").unwrap();
+        assert_eq!(table["a"], value("{ b = [{ n = 1 }, { n = 2 }] }"));
+    }
+
+    #[test]
+    fn redefinitions() {
+        let e = error("[x]\na = 1\n[x]\n");
+        assert_eq!((e.line, e.message.as_str()), (3, "table `x` defined twice"));
+        let e = error("[[x]]\n[x.y]\n[x.y]\n");
+        assert_eq!(e.message, "table `x.y` defined twice");
+        let e = error("a = 1\na = [\n  2,\n]\n");
+        assert_eq!((e.line, e.message.as_str()), (2, "duplicate key `a`"));
+        let e = error("[[x]]\n[x]\n");
+        assert_eq!(e.message, "`x` is already defined as an array");
+        let e = error("a = 1\n[a]\n");
+        assert_eq!(e.message, "`a` is already defined as a integer");
+    }
+
+    #[test]
+    fn syntax_errors_have_lines() {
+        let e = error("a = 1\n\nb = \n");
+        assert_eq!(e.line, 3);
+        let e = error("a = 1 b\n");
+        assert_eq!(e.message, "expected the end of the line, found `b`");
+        let e = error("[t\n");
+        assert_eq!(e.message, "expected `]` to close the table header");
+    }
+
+    #[test]
+    fn inline_strings_round_trip() {
+        let original = value("{ a = \"x\\\"y\\n\", \"b c\" = [1, 2.5, true], d = {} }");
+        assert_eq!(value(&to_inline_string(&original)), original);
+        assert_eq!(key_string("serde_json"), "serde_json");
+        assert_eq!(key_string("a.b"), "\"a.b\"");
+    }
+}
@@ -0,0 +1,905 @@
+//! A parser for the block-style YAML subset used in front matter.
+//!
+//! Handles nested mappings and sequences, flow collections, quoted and plain
+//! scalars, block scalars (`|` and `>`), comments, anchors and aliases, the
+//! standard `!!` tags and the `---` and `...` markers around a document.
+//! Other tags are accepted and ignored. Streams of several documents are
+//! rejected.
+
+use std::collections::HashMap;
+
+use crate::value::{ParseError, Table, Value};
+
+pub fn parse(source: &str) -> Result<Value, ParseError> {
+    let lines = source
+        .lines()
+        .enumerate()
+        .map(|(index, raw)| {
+            let text = raw.trim_start_matches(' ');
+            Line {
+                number: index + 1,
+                indent: raw.len() - text.len(),
+                text,
+            }
+        })
+        .collect();
+    let mut parser = Parser {
+        lines,
+        next: 0,
+        anchors: HashMap::new(),
+    };
+    if let Some(index) = parser.lines.iter().position(|line| !line.is_blank()) {
+        if parser.lines[index].is_marker("---") {
+            parser.next = index + 1;
+        }
+    }
+    let value = parser.parse_block(None)?;
+    if parser
+        .lines
+        .get(parser.next)
+        .is_some_and(|line| line.is_marker("..."))
+    {
+        parser.next += 1;
+    }
+    let rest = parser.lines[parser.next.min(parser.lines.len())..]
+        .iter()
+        .find(|line| !line.is_blank());
+    match rest {
+        Some(line) if line.is_marker("---") || line.is_marker("...") => {
+            error(line.number, "multiple documents are not supported")
+        }
+        Some(line) => error(line.number, "unexpected content after the document"),
+        None => Ok(value),
+    }
+}
+
+fn error<T>(line: usize, message: impl Into<String>) -> Result<T, ParseError> {
+    Err(ParseError {
+        line,
+        message: message.into(),
+    })
+}
+
+#[derive(Clone, Copy)]
+struct Line<'a> {
+    number: usize,
+    indent: usize,
+    text: &'a str,
+}
+
+impl Line<'_> {
+    fn is_blank(&self) -> bool {
+        let text = self.text.trim();
+        text.is_empty() || text.starts_with('#')
+    }
+
+    /// Whether the line is the document marker `marker`, such as `---`.
+    fn is_marker(&self, marker: &str) -> bool {
+        self.indent == 0 && strip_comment(self.text).trim_end() == marker
+    }
+}
+
+/// Values by the anchor they were given with `&name`, for `*name` aliases.
+type Anchors = HashMap<String, Value>;
+
+struct Parser<'a> {
+    lines: Vec<Line<'a>>,
+    next: usize,
+    anchors: Anchors,
+}
+
+impl<'a> Parser<'a> {
+    /// The next line that carries content, skipping blank and comment lines.
+    fn peek_line(&mut self) -> Result<Option<Line<'a>>, ParseError> {
+        while let Some(line) = self.lines.get(self.next) {
+            if !line.is_blank() {
+                if line.text.starts_with('\t') {
+                    return error(line.number, "tabs are not allowed for indentation");
+                }
+                // The end of the document ends every node in it.
+                if line.is_marker("---") || line.is_marker("...") {
+                    return Ok(None);
+                }
+                return Ok(Some(*line));
+            }
+            self.next += 1;
+        }
+        Ok(None)
+    }
+
+    /// Parses the node starting on the next line, if it is indented deeper
+    /// than `parent`.
+    fn parse_block(&mut self, parent: Option<usize>) -> Result<Value, ParseError> {
+        let Some(line) = self.peek_line()? else {
+            return Ok(Value::Null);
+        };
+        if parent.is_some_and(|parent| line.indent <= parent) {
+            return Ok(Value::Null);
+        }
+        if is_sequence_item(line.text) {
+            self.parse_sequence(line.indent)
+        } else if split_key(line.text).is_some() {
+            self.parse_mapping(line.indent)
+        } else {
+            self.next += 1;
+            let mut text = strip_comment(line.text).trim().to_string();
+            // Plain scalars may continue on more indented lines.
+            while let Some(next) = self.peek_line()? {
+                match parent {
+                    Some(parent) if next.indent > parent => {}
+                    _ => break,
+                }
+                text.push(' ');
+                text.push_str(strip_comment(next.text).trim());
+                self.next += 1;
+            }
+            parse_flow(&text, line.number, &mut self.anchors)
+        }
+    }
+
+    fn parse_mapping(&mut self, indent: usize) -> Result<Value, ParseError> {
+        let mut table = Table::new();
+        while let Some(line) = self.peek_line()? {
+            if line.indent < indent {
+                break;
+            }
+            if line.indent > indent {
+                return error(line.number, "unexpected indentation");
+            }
+            let Some((key, rest)) = split_key(line.text) else {
+                return error(line.number, "expected `key: value`");
+            };
+            self.next += 1;
+            let key = match parse_flow(key, line.number, &mut self.anchors)? {
+                Value::String(key) => key,
+                Value::Null => String::new(),
+                Value::Boolean(b) => b.to_string(),
+                Value::Integer(i) => i.to_string(),
+                Value::Float(f) => f.to_string(),
+                _ => return error(line.number, "mapping keys must be scalars"),
+            };
+            let value = self.parse_value(indent, rest, line.number)?;
+            if table.insert(key.clone(), value).is_some() {
+                return error(line.number, format!("duplicate key `{key}`"));
+            }
+        }
+        Ok(Value::Table(table))
+    }
+
+    fn parse_sequence(&mut self, indent: usize) -> Result<Value, ParseError> {
+        let mut items = Vec::new();
+        while let Some(line) = self.peek_line()? {
+            if line.indent < indent || !is_sequence_item(line.text) {
+                if line.indent > indent {
+                    return error(line.number, "unexpected indentation");
+                }
+                break;
+            }
+            if line.indent > indent {
+                return error(line.number, "unexpected indentation");
+            }
+            let rest = &line.text[1..];
+            let item = rest.trim_start_matches(' ');
+            if item.is_empty() || item.starts_with('#') {
+                self.next += 1;
+                items.push(self.parse_block(Some(indent))?);
+            } else if split_key(item).is_some() || is_sequence_item(item) {
+                // `- key: value` opens a nested node at the column of `key`.
+                self.lines[self.next] = Line {
+                    number: line.number,
+                    indent: indent + 1 + rest.len() - item.len(),
+                    text: item,
+                };
+                items.push(self.parse_block(Some(indent))?);
+            } else {
+                self.next += 1;
+                items.push(self.parse_value(indent, item, line.number)?);
+            }
+        }
+        Ok(Value::Array(items))
+    }
+
+    /// Parses the value after `key:` or `- `, which may be inline, a block
+    /// scalar or a nested node on the following lines, with its anchor and
+    /// tag.
+    fn parse_value(
+        &mut self,
+        indent: usize,
+        rest: &str,
+        number: usize,
+    ) -> Result<Value, ParseError> {
+        let (anchor, tag, rest) = properties(strip_comment(rest).trim());
+        let value = match rest.strip_prefix('*') {
+            Some(alias) => resolve_alias(&self.anchors, alias, number)?,
+            None => {
+                let value =
+                    self.parse_node(indent, rest, tag.is_some_and(is_string_tag), number)?;
+                apply_tag(tag, value, number)?
+            }
+        };
+        if let Some(anchor) = anchor {
+            self.anchors.insert(anchor.to_string(), value.clone());
+        }
+        Ok(value)
+    }
+
+    /// Parses the value after `key:` or `- ` without its properties. A plain
+    /// scalar is kept as a string when `string` is set.
+    fn parse_node(
+        &mut self,
+        indent: usize,
+        rest: &str,
+        string: bool,
+        number: usize,
+    ) -> Result<Value, ParseError> {
+        if rest.is_empty() {
+            return match self.peek_line()? {
+                Some(next) if next.indent == indent && is_sequence_item(next.text) => {
+                    self.parse_sequence(indent)
+                }
+                _ => self.parse_block(Some(indent)),
+            };
+        }
+        if rest.starts_with(['|', '>']) {
+            return self.parse_block_scalar(indent, rest, number);
+        }
+        if rest.starts_with(['"', '\'', '[', '{']) {
+            return parse_flow(rest, number, &mut self.anchors);
+        }
+        // Plain scalars may continue on more indented lines.
+        let mut text = rest.to_string();
+        while let Some(next) = self.peek_line()? {
+            // A plain scalar can't hold `: `, so a key there is misplaced.
+            if next.indent <= indent || split_key(next.text).is_some() {
+                break;
+            }
+            text.push(' ');
+            text.push_str(strip_comment(next.text).trim());
+            self.next += 1;
+        }
+        match string {
+            true => Ok(Value::String(text)),
+            false => parse_flow(&text, number, &mut self.anchors),
+        }
+    }
+
+    fn parse_block_scalar(
+        &mut self,
+        indent: usize,
+        header: &str,
+        number: usize,
+    ) -> Result<Value, ParseError> {
+        let folded = header.starts_with('>');
+        let mut chomp = ' ';
+        let mut explicit = None;
+        for c in header[1..].chars() {
+            match c {
+                '-' | '+' => chomp = c,
+                '1'..='9' => explicit = c.to_digit(10).map(|d| indent + d as usize),
+                _ => return error(number, format!("invalid block scalar header `{header}`")),
+            }
+        }
+        let mut content_indent = explicit;
+        let mut lines = Vec::new();
+        while let Some(line) = self.lines.get(self.next) {
+            let blank = line.text.trim().is_empty();
+            if !blank {
+                let required = *content_indent.get_or_insert(line.indent);
+                if line.indent <= indent || line.indent < required {
+                    break;
+                }
+            }
+            let strip = content_indent.unwrap_or(0).min(line.indent);
+            let raw_indent = " ".repeat(line.indent - strip);
+            lines.push(if blank {
+                String::new()
+            } else {
+                raw_indent + line.text
+            });
+            self.next += 1;
+        }
+        let trailing = lines
+            .iter()
+            .rev()
+            .take_while(|line| line.is_empty())
+            .count();
+        let body = &lines[..lines.len() - trailing];
+        let mut text = String::new();
+        for (i, line) in body.iter().enumerate() {
+            if i > 0 {
+                let previous = &body[i - 1];
+                let folds = folded && !previous.is_empty() && !previous.starts_with(' ');
+                match (folds, line.is_empty() || line.starts_with(' ')) {
+                    // The break before empty lines is dropped, and each empty
+                    // line stands for one.
+                    (true, _) if line.is_empty() => {}
+                    (true, false) => text.push(' '),
+                    _ => text.push('\n'),
+                }
+            }
+            text.push_str(line);
+        }
+        match chomp {
+            '-' => {}
+            '+' => text.push_str(&"\n".repeat(trailing + usize::from(!body.is_empty()))),
+            _ if !body.is_empty() => text.push('\n'),
+            _ => {}
+        }
+        Ok(Value::String(text))
+    }
+}
+
+fn is_sequence_item(text: &str) -> bool {
+    text == "-" || text.starts_with("- ")
+}
+
+/// Splits `key: rest` on the first mapping colon outside of quotes.
+fn split_key(text: &str) -> Option<(&str, &str)> {
+    if text.starts_with(['[', '{', '#', '|', '>']) || is_sequence_item(text) {
+        return None;
+    }
+    let mut quote = None;
+    for (i, c) in text.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') if i == 0 => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (None, ':') => {
+                let rest = &text[i + 1..];
+                if rest.is_empty() || rest.starts_with([' ', '\t']) {
+                    return Some((text[..i].trim_end(), rest));
+                }
+            }
+            (None, '#') if text[..i].ends_with([' ', '\t']) => return None,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Removes a trailing ` # comment` that is not inside quotes.
+fn strip_comment(text: &str) -> &str {
+    let mut quote = None;
+    let mut previous = ' ';
+    for (i, c) in text.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some('"'), '\\') if previous != '\\' => {}
+            (Some(q), c) if c == q && !(q == '"' && previous == '\\') => quote = None,
+            (None, '#') if previous == ' ' || previous == '\t' => return &text[..i],
+            _ => {}
+        }
+        previous = if previous == '\\' && c == '\\' {
+            ' '
+        } else {
+            c
+        };
+    }
+    text
+}
+
+/// Splits the anchor (`&name`) and tag (`!tag`) that may start a node off
+/// `text`, in either order.
+fn properties(mut text: &str) -> (Option<&str>, Option<&str>, &str) {
+    let (mut anchor, mut tag) = (None, None);
+    loop {
+        let property = match text.chars().next() {
+            Some('&') if anchor.is_none() => &mut anchor,
+            Some('!') if tag.is_none() => &mut tag,
+            _ => return (anchor, tag, text),
+        };
+        let end = text.find([' ', '\t', ',', ']', '}']).unwrap_or(text.len());
+        let name = &text[..end];
+        *property = Some(name.strip_prefix('&').unwrap_or(name));
+        text = text[end..].trim_start();
+    }
+}
+
+/// Whether `tag` makes a plain scalar a string: `!!str`, or the
+/// non-specific `!`.
+fn is_string_tag(tag: &str) -> bool {
+    matches!(tag, "!!str" | "!")
+}
+
+fn resolve_alias(anchors: &Anchors, alias: &str, line: usize) -> Result<Value, ParseError> {
+    match anchors.get(alias.trim()) {
+        Some(value) => Ok(value.clone()),
+        None => error(line, format!("unknown alias `*{}`", alias.trim())),
+    }
+}
+
+/// Checks `value` against a standard `!!` tag, converting integers tagged
+/// `!!float`. Other tags belong to applications and leave values as they are.
+fn apply_tag(tag: Option<&str>, value: Value, line: usize) -> Result<Value, ParseError> {
+    let Some(tag) = tag else {
+        return Ok(value);
+    };
+    let expected = match (tag, value) {
+        ("!!float", Value::Integer(i)) => return Ok(Value::Float(i as f64)),
+        ("!!str", value @ Value::String(_))
+        | ("!!int", value @ Value::Integer(_))
+        | ("!!float", value @ Value::Float(_))
+        | ("!!bool", value @ Value::Boolean(_))
+        | ("!!null", value @ Value::Null)
+        | ("!!seq", value @ Value::Array(_))
+        | ("!!map", value @ Value::Table(_)) => return Ok(value),
+        ("!!str", _) => "a string",
+        ("!!int", _) => "an integer",
+        ("!!float", _) => "a float",
+        ("!!bool", _) => "a boolean",
+        ("!!null", _) => "null",
+        ("!!seq", _) => "a sequence",
+        ("!!map", _) => "a mapping",
+        (_, value) => return Ok(value),
+    };
+    error(line, format!("a value tagged `{tag}` must be {expected}"))
+}
+
+/// Parses an inline value: a quoted or plain scalar or a flow collection.
+fn parse_flow(text: &str, line: usize, anchors: &mut Anchors) -> Result<Value, ParseError> {
+    let mut flow = Flow {
+        text,
+        pos: 0,
+        line,
+        anchors,
+    };
+    let value = flow.parse_value(false)?;
+    flow.skip_whitespace();
+    if flow.pos < text.len() {
+        return error(line, format!("unexpected `{}`", &text[flow.pos..]));
+    }
+    Ok(value)
+}
+
+struct Flow<'a> {
+    text: &'a str,
+    pos: usize,
+    line: usize,
+    anchors: &'a mut Anchors,
+}
+
+impl Flow<'_> {
+    fn peek(&self) -> Option<char> {
+        self.text[self.pos..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t')) {
+            self.bump();
+        }
+    }
+
+    fn parse_value(&mut self, nested: bool) -> Result<Value, ParseError> {
+        self.skip_whitespace();
+        let (anchor, tag, rest) = properties(&self.text[self.pos..]);
+        self.pos = self.text.len() - rest.len();
+        let value = match rest.strip_prefix('*') {
+            Some(alias) => {
+                let len = alias
+                    .find([' ', '\t', ',', ']', '}'])
+                    .unwrap_or(alias.len());
+                self.pos += 1 + len;
+                resolve_alias(self.anchors, &alias[..len], self.line)?
+            }
+            None => {
+                let value = self.parse_node(nested, tag.is_some_and(is_string_tag))?;
+                apply_tag(tag, value, self.line)?
+            }
+        };
+        if let Some(anchor) = anchor {
+            self.anchors.insert(anchor.to_string(), value.clone());
+        }
+        Ok(value)
+    }
+
+    /// Parses a value without its properties. A plain scalar is kept as a
+    /// string when `string` is set.
+    fn parse_node(&mut self, nested: bool, string: bool) -> Result<Value, ParseError> {
+        match self.peek() {
+            Some('[') => self.parse_sequence(),
+            Some('{') => self.parse_mapping(),
+            Some('"') => self.parse_double_quoted().map(Value::String),
+            Some('\'') => self.parse_single_quoted().map(Value::String),
+            _ => {
+                let start = self.pos;
+                while let Some(c) = self.peek() {
+                    if nested && matches!(c, ',' | ']' | '}') {
+                        break;
+                    }
+                    if nested && c == ':' && self.text[self.pos + 1..].starts_with([' ', ',', '}'])
+                    {
+                        break;
+                    }
+                    self.bump();
+                }
+                let text = self.text[start..self.pos].trim();
+                match string {
+                    true => Ok(Value::String(text.to_string())),
+                    false => Ok(resolve_plain(text)),
+                }
+            }
+        }
+    }
+
+    fn parse_sequence(&mut self) -> Result<Value, ParseError> {
+        self.bump();
+        let mut items = Vec::new();
+        loop {
+            self.skip_whitespace();
+            if self.peek() == Some(']') {
+                self.bump();
+                return Ok(Value::Array(items));
+            }
+            items.push(self.parse_value(true)?);
+            self.skip_whitespace();
+            match self.bump() {
+                Some(',') => {}
+                Some(']') => return Ok(Value::Array(items)),
+                _ => return error(self.line, "expected `,` or `]` in flow sequence"),
+            }
+        }
+    }
+
+    fn parse_mapping(&mut self) -> Result<Value, ParseError> {
+        self.bump();
+        let mut table = Table::new();
+        loop {
+            self.skip_whitespace();
+            if self.peek() == Some('}') {
+                self.bump();
+                return Ok(Value::Table(table));
+            }
+            let key = match self.parse_value(true)? {
+                Value::String(key) => key,
+                _ => return error(self.line, "flow mapping keys must be strings"),
+            };
+            self.skip_whitespace();
+            let value = if self.peek() == Some(':') {
+                self.bump();
+                self.parse_value(true)?
+            } else {
+                Value::Null
+            };
+            if table.insert(key.clone(), value).is_some() {
+                return error(self.line, format!("duplicate key `{key}`"));
+            }
+            self.skip_whitespace();
+            match self.bump() {
+                Some(',') => {}
+                Some('}') => return Ok(Value::Table(table)),
+                _ => return error(self.line, "expected `,` or `}` in flow mapping"),
+            }
+        }
+    }
+
+    fn parse_single_quoted(&mut self) -> Result<String, ParseError> {
+        self.bump();
+        let mut out = String::new();
+        loop {
+            match self.bump() {
+                None => return error(self.line, "unterminated string"),
+                Some('\'') if self.peek() == Some('\'') => {
+                    self.bump();
+                    out.push('\'');
+                }
+                Some('\'') => return Ok(out),
+                Some(c) => out.push(c),
+            }
+        }
+    }
+
+    fn parse_double_quoted(&mut self) -> Result<String, ParseError> {
+        self.bump();
+        let mut out = String::new();
+        loop {
+            match self.bump() {
+                None => return error(self.line, "unterminated string"),
+                Some('"') => return Ok(out),
+                Some('\\') => {
+                    let c = match self.bump() {
+                        Some('0') => '\0',
+                        Some('a') => '\u{7}',
+                        Some('b') => '\u{8}',
+                        Some('t') => '\t',
+                        Some('n') => '\n',
+                        Some('v') => '\u{b}',
+                        Some('f') => '\u{c}',
+                        Some('r') => '\r',
+                        Some('e') => '\u{1b}',
+                        Some(' ') => ' ',
+                        Some('"') => '"',
+                        Some('/') => '/',
+                        Some('\\') => '\\',
+                        Some(kind @ ('x' | 'u' | 'U')) => {
+                            let len = match kind {
+                                'x' => 2,
+                                'u' => 4,
+                                _ => 8,
+                            };
+                            let hex = self.text[self.pos..].get(..len).unwrap_or_default();
+                            match u32::from_str_radix(hex, 16).ok().and_then(char::from_u32) {
+                                Some(c) if hex.len() == len => {
+                                    self.pos += len;
+                                    c
+                                }
+                                _ => {
+                                    return error(
+                                        self.line,
+                                        format!("invalid escape `\\{kind}{hex}`"),
+                                    )
+                                }
+                            }
+                        }
+                        Some(c) => return error(self.line, format!("invalid escape `\\{c}`")),
+                        None => return error(self.line, "unterminated string"),
+                    };
+                    out.push(c);
+                }
+                Some(c) => out.push(c),
+            }
+        }
+    }
+}
+
+/// Resolves a plain scalar to null, a boolean, a number or a string.
+fn resolve_plain(text: &str) -> Value {
+    match text {
+        "" | "~" | "null" | "Null" | "NULL" => Value::Null,
+        "true" | "True" | "TRUE" => Value::Boolean(true),
+        "false" | "False" | "FALSE" => Value::Boolean(false),
+        ".inf" | "+.inf" | ".Inf" | "+.Inf" | ".INF" | "+.INF" => Value::Float(f64::INFINITY),
+        "-.inf" | "-.Inf" | "-.INF" => Value::Float(f64::NEG_INFINITY),
+        ".nan" | ".NaN" | ".NAN" => Value::Float(f64::NAN),
+        _ => {
+            let unsigned = text.trim_start_matches(['+', '-']);
+            let numeric = unsigned.starts_with(|c: char| c.is_ascii_digit() || c == '.');
+            if let Some(hex) = text.strip_prefix("0x") {
+                if let Ok(i) = i64::from_str_radix(hex, 16) {
+                    return Value::Integer(i);
+                }
+            } else if let Some(octal) = text.strip_prefix("0o") {
+                if let Ok(i) = i64::from_str_radix(octal, 8) {
+                    return Value::Integer(i);
+                }
+            } else if numeric {
+                if let Ok(i) = text.parse() {
+                    return Value::Integer(i);
+                }
+                if let Ok(f) = text.parse() {
+                    return Value::Float(f);
+                }
+            }
+            Value::String(text.to_string())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(s: &str) -> Value {
+        Value::String(s.to_string())
+    }
+
+    fn table<const N: usize>(entries: [(&str, Value); N]) -> Value {
+        Value::Table(
+            entries
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value))
+                .collect(),
+        )
+    }
+
+    fn error(source: &str) -> ParseError {
+        match parse(source) {
+            Ok(value) => panic!("`{source}` parsed as {value:?}"),
+            Err(e) => e,
+        }
+    }
+
+    #[test]
+    fn plain_scalars() {
+        let value = parse(
+            "a: ~\nb: true\nc: False\nd: 12\ne: -1.5\nf: 0x1f\ng: .inf\nh: 2024-01-11\ni: hello world\n",
+        )
+        .unwrap();
+        assert_eq!(
+            value,
+            table([
+                ("a", Value::Null),
+                ("b", Value::Boolean(true)),
+                ("c", Value::Boolean(false)),
+                ("d", Value::Integer(12)),
+                ("e", Value::Float(-1.5)),
+                ("f", Value::Integer(31)),
+                ("g", Value::Float(f64::INFINITY)),
+                ("h", string("2024-01-11")),
+                ("i", string("hello world")),
+            ])
+        );
+    }
+
+    #[test]
+    fn quoted_scalars() {
+        let value = parse("a: 'it''s # not a comment'\nb: \"tab\\there \\u00e9\"\nc: \"true\"\n");
+        assert_eq!(
+            value.unwrap(),
+            table([
+                ("a", string("it's # not a comment")),
+                ("b", string("tab\there é")),
+                ("c", string("true")),
+            ])
+        );
+        assert_eq!(error("a: \"open\n").message, "unterminated string");
+        assert_eq!(error("a: \"\\q\"\n").message, "invalid escape `\\q`");
+    }
+
+    #[test]
+    fn nested_collections() {
+        let value = parse(
+            "title: Post # comment\ntags:\n  - rust\n  - yaml\nauthor:\n  name: Ann\n  links: [a, 'b, c']\nitems:\n- name: one\n  n: 1\n- - nested\nflow: {a: 1, b: [x]}\n",
+        )
+        .unwrap();
+        assert_eq!(
+            value,
+            table([
+                ("title", string("Post")),
+                ("tags", Value::Array(vec![string("rust"), string("yaml")])),
+                (
+                    "author",
+                    table([
+                        ("name", string("Ann")),
+                        ("links", Value::Array(vec![string("a"), string("b, c")])),
+                    ])
+                ),
+                (
+                    "items",
+                    Value::Array(vec![
+                        table([("name", string("one")), ("n", Value::Integer(1))]),
+                        Value::Array(vec![string("nested")]),
+                    ])
+                ),
+                (
+                    "flow",
+                    table([
+                        ("a", Value::Integer(1)),
+                        ("b", Value::Array(vec![string("x")])),
+                    ])
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn block_scalars() {
+        let value = parse(
+            "literal: |\n  one\n   two\n\nfolded: >\n  one\n  two\n\n  three\nstrip: |-\n  text\n\nkeep: |+\n  text\n\nlast: x\n",
+        )
+        .unwrap();
+        assert_eq!(
+            value,
+            table([
+                ("literal", string("one\n two\n")),
+                ("folded", string("one two\nthree\n")),
+                ("strip", string("text")),
+                ("keep", string("text\n\n")),
+                ("last", string("x")),
+            ])
+        );
+    }
+
+    #[test]
+    fn multi_line_plain_scalars() {
+        let value = parse("description: a long\n  description\nnext: 1\n").unwrap();
+        assert_eq!(
+            value,
+            table([
+                ("description", string("a long description")),
+                ("next", Value::Integer(1)),
+            ])
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let e = error("a: 1\na: 2\n");
+        assert_eq!((e.line, e.message.as_str()), (2, "duplicate key `a`"));
+        let e = error("a:\n  b: 1\n   c: 2\n");
+        assert_eq!((e.line, e.message.as_str()), (3, "unexpected indentation"));
+        let e = error("a:\n\t- b\n");
+        assert_eq!(e.message, "tabs are not allowed for indentation");
+        let e = error("a: [1, 2\n");
+        assert_eq!(e.message, "expected `,` or `]` in flow sequence");
+    }
+
+    #[test]
+    fn empty_documents() {
+        assert_eq!(parse("").unwrap(), Value::Null);
+        assert_eq!(parse("# only a comment\n").unwrap(), Value::Null);
+    }
+
+    #[test]
+    fn document_markers() {
+        let expected = table([("a", Value::Integer(1))]);
+        assert_eq!(parse("---\na: 1\n").unwrap(), expected);
+        assert_eq!(
+            parse("# comment\n--- # start\na: 1\n...\n\n").unwrap(),
+            expected
+        );
+        assert_eq!(parse("a: 1\n...\n").unwrap(), expected);
+        assert_eq!(
+            parse("---\n- x\n").unwrap(),
+            Value::Array(vec![string("x")])
+        );
+        let e = error("a: 1\n---\nb: 2\n");
+        assert_eq!(
+            (e.line, e.message.as_str()),
+            (2, "multiple documents are not supported")
+        );
+        let e = error("a: 1\n...\nb: 2\n");
+        assert_eq!(e.message, "unexpected content after the document");
+    }
+
+    #[test]
+    fn anchors_and_aliases() {
+        let value = parse(
+            "base: &base\n  x: 1\ncopy: *base\nn: &n 5\nlist:\n  - &item one\n  - *item\n  - *n\nflow: [&f 2, *f, {k: *n}]\n",
+        )
+        .unwrap();
+        let base = table([("x", Value::Integer(1))]);
+        assert_eq!(
+            value,
+            table([
+                ("base", base.clone()),
+                ("copy", base),
+                ("n", Value::Integer(5)),
+                (
+                    "list",
+                    Value::Array(vec![string("one"), string("one"), Value::Integer(5)])
+                ),
+                (
+                    "flow",
+                    Value::Array(vec![
+                        Value::Integer(2),
+                        Value::Integer(2),
+                        table([("k", Value::Integer(5))]),
+                    ])
+                ),
+            ])
+        );
+        let e = error("a: 1\nb: *missing\n");
+        assert_eq!(
+            (e.line, e.message.as_str()),
+            (2, "unknown alias `*missing`")
+        );
+    }
+
+    #[test]
+    fn tags() {
+        let value = parse(
+            "a: !!str 0x10\nb: ! true\nc: !!float 1\nd: !!int 7\ne: !custom text\nf: &x !!str 1\ng: [!!str 2, !!bool false]\nh: !!map\n  k: v\n",
+        )
+        .unwrap();
+        assert_eq!(
+            value,
+            table([
+                ("a", string("0x10")),
+                ("b", string("true")),
+                ("c", Value::Float(1.0)),
+                ("d", Value::Integer(7)),
+                ("e", string("text")),
+                ("f", string("1")),
+                ("g", Value::Array(vec![string("2"), Value::Boolean(false)])),
+                ("h", table([("k", string("v"))])),
+            ])
+        );
+        let e = error("a: !!int text\n");
+        assert_eq!(e.message, "a value tagged `!!int` must be an integer");
+    }
+}
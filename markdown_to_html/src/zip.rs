@@ -0,0 +1,182 @@
+//! A writer for zip archives, with files stored rather than compressed. Every
+//! entry gets the same timestamp, so the same files always make the same
+//! archive.
+
+const LOCAL_HEADER: u32 = 0x0403_4b50;
+const CENTRAL_HEADER: u32 = 0x0201_4b50;
+const END_OF_CENTRAL_DIRECTORY: u32 = 0x0605_4b50;
+/// Version 2.0 of the format, the first with directories and the one every
+/// tool reads.
+const VERSION: u16 = 20;
+/// Made on Unix, so that the permissions in the external attributes apply.
+const VERSION_MADE_BY: u16 = (3 << 8) | VERSION;
+/// File names are UTF-8.
+const UTF8_FLAG: u16 = 1 << 11;
+/// 1980-01-01, the earliest date the format can hold, as an MS-DOS date.
+const DOS_DATE: u16 = (1 << 5) | 1;
+/// A regular file readable by everyone.
+const FILE_MODE: u32 = 0o100_644;
+
+/// Builds an archive from `files`, pairs of a `/`-separated path and the
+/// file's contents.
+pub fn archive<'a>(files: impl IntoIterator<Item = (&'a str, &'a [u8])>) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut central = Vec::new();
+    let mut count: u16 = 0;
+    for (name, contents) in files {
+        let offset = out.len() as u32;
+        let crc = crc32(contents);
+        let size = contents.len() as u32;
+
+        put_u32(&mut out, LOCAL_HEADER);
+        put_u16(&mut out, VERSION);
+        put_common(&mut out, crc, size, name);
+        put_u16(&mut out, 0); // extra field length
+        out.extend_from_slice(name.as_bytes());
+        out.extend_from_slice(contents);
+
+        put_u32(&mut central, CENTRAL_HEADER);
+        put_u16(&mut central, VERSION_MADE_BY);
+        put_u16(&mut central, VERSION);
+        put_common(&mut central, crc, size, name);
+        put_u16(&mut central, 0); // extra field length
+        put_u16(&mut central, 0); // comment length
+        put_u16(&mut central, 0); // disk number
+        put_u16(&mut central, 0); // internal attributes
+        put_u32(&mut central, FILE_MODE << 16);
+        put_u32(&mut central, offset);
+        central.extend_from_slice(name.as_bytes());
+        count += 1;
+    }
+    let central_offset = out.len() as u32;
+    let central_size = central.len() as u32;
+    out.extend(central);
+    put_u32(&mut out, END_OF_CENTRAL_DIRECTORY);
+    put_u16(&mut out, 0); // this disk
+    put_u16(&mut out, 0); // disk with the central directory
+    put_u16(&mut out, count);
+    put_u16(&mut out, count);
+    put_u32(&mut out, central_size);
+    put_u32(&mut out, central_offset);
+    put_u16(&mut out, 0); // comment length
+    out
+}
+
+/// The fields local and central headers share, from the flags to the file
+/// name's length.
+fn put_common(out: &mut Vec<u8>, crc: u32, size: u32, name: &str) {
+    put_u16(out, UTF8_FLAG);
+    put_u16(out, 0); // stored
+    put_u16(out, 0); // time
+    put_u16(out, DOS_DATE);
+    put_u32(out, crc);
+    put_u32(out, size); // compressed
+    put_u32(out, size);
+    put_u16(out, name.len() as u16);
+}
+
+fn put_u16(out: &mut Vec<u8>, value: u16) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+/// The CRC-32 checksum zip uses, computed bit by bit since archives here are
+/// small.
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc ^= u32::from(*byte);
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn u16_at(bytes: &[u8], at: usize) -> u16 {
+        u16::from_le_bytes([bytes[at], bytes[at + 1]])
+    }
+
+    fn u32_at(bytes: &[u8], at: usize) -> u32 {
+        u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+    }
+
+    /// Reads `archive` back through its central directory, checking each
+    /// entry against its local header, and returns the names and contents.
+    fn read(archive: &[u8]) -> Vec<(String, Vec<u8>)> {
+        let end = archive.len() - 22;
+        assert_eq!(u32_at(archive, end), END_OF_CENTRAL_DIRECTORY);
+        let count = u16_at(archive, end + 10);
+        assert_eq!(u16_at(archive, end + 8), count);
+        let size = u32_at(archive, end + 12) as usize;
+        let mut at = u32_at(archive, end + 16) as usize;
+        assert_eq!(at + size, end);
+        let mut entries = Vec::new();
+        for _ in 0..count {
+            assert_eq!(u32_at(archive, at), CENTRAL_HEADER);
+            assert_eq!(u16_at(archive, at + 8), UTF8_FLAG);
+            let crc = u32_at(archive, at + 16);
+            let size = u32_at(archive, at + 24) as usize;
+            let name_len = u16_at(archive, at + 28) as usize;
+            assert_eq!(u32_at(archive, at + 38), FILE_MODE << 16);
+            let offset = u32_at(archive, at + 42) as usize;
+            let name = &archive[at + 46..at + 46 + name_len];
+
+            assert_eq!(u32_at(archive, offset), LOCAL_HEADER);
+            assert_eq!(u32_at(archive, offset + 14), crc);
+            assert_eq!(u32_at(archive, offset + 18) as usize, size);
+            assert_eq!(u16_at(archive, offset + 26) as usize, name_len);
+            let data = offset + 30 + name_len;
+            assert_eq!(&archive[offset + 30..data], name);
+            let contents = archive[data..data + size].to_vec();
+            assert_eq!(crc32(&contents), crc);
+
+            entries.push((String::from_utf8(name.to_vec()).unwrap(), contents));
+            at += 46 + name_len;
+        }
+        entries
+    }
+
+    #[test]
+    fn checksums() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(
+            crc32(b"The quick brown fox jumps over the lazy dog"),
+            0x414f_a339
+        );
+    }
+
+    #[test]
+    fn empty_archive() {
+        let archive = archive([]);
+        assert_eq!(archive.len(), 22);
+        assert!(read(&archive).is_empty());
+    }
+
+    #[test]
+    fn entries_read_back() {
+        let files: [(&str, &[u8]); 3] = [
+            ("post/Cargo.toml", b"[package]\nname = \"post\"\n"),
+            ("post/src/main.rs", b"fn main() {}\n"),
+            ("post/caf\u{e9}.txt", b""),
+        ];
+        let archive = archive(files);
+        let entries = read(&archive);
+        let expected: Vec<(String, Vec<u8>)> = files
+            .iter()
+            .map(|(name, contents)| (name.to_string(), contents.to_vec()))
+            .collect();
+        assert_eq!(entries, expected);
+        // Nothing depends on when or where the archive is made.
+        assert_eq!(archive, super::archive(files));
+    }
+}